@@ -20,10 +20,11 @@
 )]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-use std::{collections::HashMap, io, num::NonZeroU8};
+use std::{collections::HashMap, error::Error, fmt, io, num::NonZeroU8};
 
 pub mod playlist;
 pub mod tags;
+pub mod variables;
 
 #[cfg_attr(docsrs, doc(cfg(feature = "steering-manifest")))]
 #[cfg(feature = "steering-manifest")]
@@ -472,8 +473,157 @@ pub enum FloatOrInteger {
     Integer(u64),
 }
 
+impl FloatOrInteger {
+    /// Returns the value as a float.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub const fn as_f64(&self) -> f64 {
+        match self {
+            Self::Float(float) => *float,
+            Self::Integer(integer) => *integer as f64,
+        }
+    }
+}
+
+/// An error encountered while strictly serializing a tag or playlist.
+///
+/// `V` is the type describing why the input is invalid, e.g.
+/// [`tags::TagViolation`].
+#[derive(Debug)]
+pub enum SerializeError<V> {
+    /// An io error was encountered while writing the output.
+    Io(io::Error),
+
+    /// The input is invalid, so nothing was written.
+    Invalid(Vec<V>),
+}
+
+impl<V: fmt::Display> fmt::Display for SerializeError<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "io error while serializing: {error}"),
+            Self::Invalid(violations) => {
+                write!(f, "invalid input")?;
+                for (i, violation) in violations.iter().enumerate() {
+                    let separator = if i == 0 { ": " } else { "; " };
+                    write!(f, "{separator}{violation}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<V: fmt::Debug + fmt::Display> Error for SerializeError<V> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+impl<V> From<io::Error> for SerializeError<V> {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// Adapts an [`io::Write`] to [`fmt::Write`], keeping the io error which a
+/// [`fmt::Error`] can not carry.
+pub(crate) struct IoWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|error| {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
+
+/// Runs `write` on an [`IoWriter`] around `output`, returning the io error
+/// which made it fail.
+pub(crate) fn write_io<W: io::Write>(
+    output: W,
+    write: impl FnOnce(&mut IoWriter<W>) -> fmt::Result,
+) -> io::Result<()> {
+    let mut writer = IoWriter {
+        inner: output,
+        error: None,
+    };
+
+    write(&mut writer).map_err(|fmt::Error| {
+        writer
+            .error
+            .take()
+            .unwrap_or_else(|| io::Error::other("formatter error"))
+    })
+}
+
+/// The size past which an [`AsyncWriter`] writes its buffer to the output.
+#[cfg(feature = "tokio")]
+const ASYNC_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Formats a playlist piece by piece into a reusable buffer, which is written
+/// to the async output whenever it grows past [`ASYNC_BUFFER_SIZE`].
+#[cfg(feature = "tokio")]
+pub(crate) struct AsyncWriter<W> {
+    output: W,
+    buffer: String,
+}
+
+#[cfg(feature = "tokio")]
+impl<W: tokio::io::AsyncWrite + Unpin> AsyncWriter<W> {
+    pub(crate) fn new(output: W) -> Self {
+        Self {
+            output,
+            buffer: String::with_capacity(ASYNC_BUFFER_SIZE),
+        }
+    }
+
+    /// Formats the next piece of the playlist with `write`.
+    pub(crate) async fn write(
+        &mut self,
+        write: impl FnOnce(&mut String) -> fmt::Result,
+    ) -> io::Result<()> {
+        write(&mut self.buffer).map_err(|fmt::Error| io::Error::other("formatter error"))?;
+        if self.buffer.len() >= ASYNC_BUFFER_SIZE {
+            self.write_buffer().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes whatever is left in the buffer.
+    pub(crate) async fn finish(mut self) -> io::Result<()> {
+        self.write_buffer().await
+    }
+
+    async fn write_buffer(&mut self) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        self.output.write_all(self.buffer.as_bytes()).await?;
+        self.buffer.clear();
+
+        Ok(())
+    }
+}
+
+/// The ID of the pathway of every Variant Stream which has no pathway ID.
+pub(crate) const DEFAULT_PATHWAY_ID: &str = ".";
+
+/// Returns the ID of the pathway a stream with `pathway_id` is on.
+pub(crate) fn pathway_id(pathway_id: Option<&str>) -> &str {
+    pathway_id.unwrap_or(DEFAULT_PATHWAY_ID)
+}
+
 impl ByteRange {
-    fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
+    fn serialize(&self, mut output: impl fmt::Write) -> fmt::Result {
         write!(output, "{}", self.length_bytes)?;
         if let Some(start_offset_bytes) = self.start_offset_bytes {
             write!(output, "@{start_offset_bytes}")?;
@@ -484,13 +634,13 @@ impl ByteRange {
 }
 
 impl ByteRangeWithOffset {
-    fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
+    fn serialize(&self, mut output: impl fmt::Write) -> fmt::Result {
         write!(output, "{}@{}", self.length_bytes, self.start_offset_bytes)
     }
 }
 
 impl EncryptionMethod {
-    fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
+    fn serialize(&self, mut output: impl fmt::Write) -> fmt::Result {
         match self {
             Self::Aes128 { uri, .. } => write!(output, "METHOD=AES-128,URI=\"{uri}\"")?,
             Self::SampleAes { uri, .. } => write!(output, "METHOD=SAMPLE-AES,URI=\"{uri}\"")?,
@@ -554,7 +704,7 @@ impl EncryptionMethod {
 }
 
 impl StreamInf {
-    fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
+    fn serialize(&self, mut output: impl fmt::Write) -> fmt::Result {
         write!(output, "BANDWIDTH={}", self.bandwidth_bits_per_second)?;
 
         if let Some(average_bandwidth) = self.average_bandwidth_bits_per_second {
@@ -680,7 +830,7 @@ impl StreamInf {
 }
 
 impl SupplementalCodec {
-    fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
+    fn serialize(&self, mut output: impl fmt::Write) -> fmt::Result {
         write!(output, "{}", self.supplemental_codec)?;
 
         for brand in &self.compatibility_brands {
@@ -692,7 +842,7 @@ impl SupplementalCodec {
 }
 
 impl ContentProtectionConfiguration {
-    fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
+    fn serialize(&self, mut output: impl fmt::Write) -> fmt::Result {
         write!(output, "{}:", self.key_format)?;
 
         if self.cpc_labels.len() == 1 {
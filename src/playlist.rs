@@ -14,7 +14,42 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod delta;
+mod directive;
+mod live;
+mod parse;
 mod serialize;
+mod validate;
+mod version;
+
+pub use delta::{ApplyDeltaUpdateError, DeltaUpdateError, SkipRequest};
+pub use directive::{BlockingRequest, DeliveryDirectiveError, DeliveryDirectives, DirectiveStatus};
+pub use live::{LiveWindow, WindowLimit};
+pub use parse::ParsePlaylistError;
+pub use validate::{MediaPlaylistViolation, MultivariantPlaylistViolation, RenditionGroupType};
+pub use version::VersionFeature;
+
+/// Either kind of extended M3U playlist.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Playlist {
+    /// A playlist listing the variants and renditions of a presentation.
+    Multivariant(MultivariantPlaylist),
+
+    /// A playlist listing the Media Segments of a single rendition.
+    Media(MediaPlaylist),
+}
+
+impl From<MultivariantPlaylist> for Playlist {
+    fn from(playlist: MultivariantPlaylist) -> Self {
+        Self::Multivariant(playlist)
+    }
+}
+
+impl From<MediaPlaylist> for Playlist {
+    fn from(playlist: MediaPlaylist) -> Self {
+        Self::Media(playlist)
+    }
+}
 
 /// A playlist representing a list of renditions and variants of a given piece of media.
 #[derive(Debug, Clone, PartialEq, Default)]
@@ -269,6 +304,10 @@ pub struct MediaPlaylist {
     /// Information about the `PartialSegments` in this playlist.
     pub part_information: Option<PartInformation>,
 
+    /// The segment after [`MediaPlaylist::segments`] which is still being
+    /// produced, and so far only has `PartialSegments`.
+    pub in_progress_segment: Option<Box<InProgressSegment>>,
+
     /// Information about the playlist that is not associated with
     /// specific Media Segments.
     pub metadata: MediaMetadata,
@@ -360,6 +399,34 @@ pub struct MediaSegment {
     pub parts: Vec<PartialSegment>,
 }
 
+/// A `MediaSegment` which is still being produced. It has no URI or duration
+/// yet, only the `PartialSegments` which are already available.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InProgressSegment {
+    /// True if this segment is a discontinuity between the Media Segment
+    /// that follows it and the one that preceded it.
+    pub is_discontinuity: bool,
+
+    /// If Some, represents the encryption method used for this segment.
+    /// If None, no encryption is used.
+    pub encryption: Option<crate::EncryptionMethod>,
+
+    /// If Some, this segment requires a Media Initialization Section
+    /// and the value describes how to acquire it.
+    pub media_initialization_section: Option<MediaInitializationSection>,
+
+    /// If Some, the approximate bit rate of this segment in kilobits per
+    /// second, as given by the EXT-X-BITRATE tag which applies to it.
+    pub bitrate: Option<u64>,
+
+    /// If Some, the first sample of this segment is associated with this
+    /// time.
+    pub absolute_time: Option<chrono::DateTime<chrono::FixedOffset>>,
+
+    /// The partial segments available so far.
+    pub parts: Vec<PartialSegment>,
+}
+
 /// A common sequence of bytes to initialize the parser before
 /// `MediaSegments` can be parsed.
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -0,0 +1,444 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{error::Error, fmt};
+
+use super::{LiveWindow, MediaPlaylist, SkipInformation};
+
+/// The value of an `_HLS_skip` delivery directive, requesting a Playlist
+/// Delta Update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SkipRequest {
+    /// `_HLS_skip=YES`, skip old Media Segments.
+    Yes,
+
+    /// `_HLS_skip=v2`, skip old Media Segments and EXT-X-DATERANGE tags.
+    V2,
+}
+
+/// An error encountered while producing a Playlist Delta Update.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaUpdateError {
+    /// The playlist has no [`MediaPlaylist::playlist_delta_updates_information`],
+    /// so it does not support delta updates.
+    Unsupported,
+
+    /// The playlist is already a delta update.
+    AlreadySkipped,
+}
+
+impl fmt::Display for DeltaUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "the playlist does not support delta updates"),
+            Self::AlreadySkipped => write!(f, "the playlist is already a delta update"),
+        }
+    }
+}
+
+impl Error for DeltaUpdateError {}
+
+/// An error encountered while applying a Playlist Delta Update to a previous
+/// copy of the playlist.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyDeltaUpdateError {
+    /// The update has no EXT-X-SKIP tag.
+    NotDeltaUpdate,
+
+    /// The previous playlist does not contain every skipped Media Segment.
+    MissingSkippedSegments {
+        /// The media sequence number of the first skipped segment.
+        first_media_sequence_number: u64,
+
+        /// The number of skipped segments.
+        number_of_skipped_segments: u64,
+    },
+}
+
+impl fmt::Display for ApplyDeltaUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotDeltaUpdate => write!(f, "the playlist is not a delta update"),
+            Self::MissingSkippedSegments {
+                first_media_sequence_number,
+                number_of_skipped_segments,
+            } => write!(
+                f,
+                "the previous playlist does not contain the {number_of_skipped_segments} skipped \
+                segments starting at media sequence number {first_media_sequence_number}"
+            ),
+        }
+    }
+}
+
+impl Error for ApplyDeltaUpdateError {}
+
+impl MediaPlaylist {
+    /// Produces the Playlist Delta Update answering an `_HLS_skip` request for
+    /// this full playlist.
+    ///
+    /// Every Media Segment which ends at least
+    /// [`crate::DeltaUpdateInfo::skip_boundary_seconds`] from the end of the
+    /// playlist is replaced by an EXT-X-SKIP tag.
+    ///
+    /// For [`SkipRequest::V2`], if the playlist can skip date ranges, the date
+    /// ranges which start before the first segment that is not skipped are
+    /// skipped too, and `recently_removed_dataranges` is listed in the
+    /// EXT-X-SKIP tag. This assumes the client already has those date ranges,
+    /// which requires a program date time to know when they start.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the playlist does not support delta updates, or is
+    /// already a delta update.
+    pub fn delta_update(
+        &self,
+        skip: SkipRequest,
+        recently_removed_dataranges: Vec<String>,
+    ) -> Result<Self, DeltaUpdateError> {
+        let information = self
+            .playlist_delta_updates_information
+            .as_ref()
+            .ok_or(DeltaUpdateError::Unsupported)?;
+        if self.metadata.skip.is_some() {
+            return Err(DeltaUpdateError::AlreadySkipped);
+        }
+
+        let mut remaining: f64 = self
+            .segments
+            .iter()
+            .map(|segment| segment.duration_seconds.as_f64())
+            .sum();
+        let skipped = self
+            .segments
+            .iter()
+            .take_while(|segment| {
+                remaining -= segment.duration_seconds.as_f64();
+                remaining >= information.skip_boundary_seconds
+            })
+            .count();
+
+        // Every field is listed, rather than cloning `self`, so the skipped
+        // segments are never cloned.
+        let mut playlist = Self {
+            segments: self.segments[skipped..].to_vec(),
+            start_offset: self.start_offset.clone(),
+            variables: self.variables.clone(),
+            is_independent_segments: self.is_independent_segments,
+            target_duration: self.target_duration,
+            first_media_sequence_number: self.first_media_sequence_number,
+            discontinuity_sequence_number: self.discontinuity_sequence_number,
+            finished: self.finished,
+            playlist_type: self.playlist_type.clone(),
+            hold_back_seconds: self.hold_back_seconds,
+            iframes_only: self.iframes_only,
+            playlist_delta_updates_information: self.playlist_delta_updates_information.clone(),
+            supports_blocking_playlist_reloads: self.supports_blocking_playlist_reloads,
+            part_information: self.part_information.clone(),
+            in_progress_segment: self.in_progress_segment.clone(),
+            metadata: self.metadata.clone(),
+        };
+        // The program date time of a skipped segment applies to the ones
+        // after it, so it's carried forward to the first remaining segment.
+        if let Some(first) = playlist.segments.first_mut() {
+            first.absolute_time = first.absolute_time.or_else(|| self.start_time(skipped));
+        }
+
+        let mut skip_information = SkipInformation {
+            number_of_skipped_segments: skipped as u64,
+            recently_removed_dataranges: Vec::new(),
+        };
+        if skip == SkipRequest::V2 && information.can_skip_dateranges {
+            if let Some(first_start) = self.start_time(skipped) {
+                playlist
+                    .metadata
+                    .date_ranges
+                    .retain(|date_range| date_range.start_date >= first_start);
+            }
+            skip_information.recently_removed_dataranges = recently_removed_dataranges;
+        }
+        playlist.metadata.skip = Some(skip_information);
+
+        Ok(playlist)
+    }
+
+    /// Merges a Playlist Delta Update into this previously loaded copy of the
+    /// playlist, producing the full playlist the update stands for.
+    ///
+    /// The skipped Media Segments are restored from this playlist by their
+    /// media sequence number. `skip` is the `_HLS_skip` directive `delta` was
+    /// requested with. Date ranges are only skipped for [`SkipRequest::V2`]
+    /// when the playlist can skip date ranges, in which case the date ranges
+    /// of this playlist are kept, unless the update replaces them or lists
+    /// them as recently removed. Otherwise, `delta` has every date range, and
+    /// its date ranges replace those of this playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if `delta` has no EXT-X-SKIP tag, or if this playlist
+    /// does not contain every skipped segment.
+    pub fn apply_delta_update(
+        &self,
+        mut delta: Self,
+        skip: SkipRequest,
+    ) -> Result<Self, ApplyDeltaUpdateError> {
+        let skipped_date_ranges = skip == SkipRequest::V2
+            && delta
+                .playlist_delta_updates_information
+                .as_ref()
+                .is_some_and(|information| information.can_skip_dateranges);
+        let skip_information = delta
+            .metadata
+            .skip
+            .take()
+            .ok_or(ApplyDeltaUpdateError::NotDeltaUpdate)?;
+
+        let skipped = delta
+            .first_media_sequence_number
+            .checked_sub(self.first_media_sequence_number)
+            .and_then(|start| usize::try_from(start).ok())
+            .zip(usize::try_from(skip_information.number_of_skipped_segments).ok())
+            .and_then(|(start, count)| self.segments.get(start..start.checked_add(count)?))
+            .ok_or(ApplyDeltaUpdateError::MissingSkippedSegments {
+                first_media_sequence_number: delta.first_media_sequence_number,
+                number_of_skipped_segments: skip_information.number_of_skipped_segments,
+            })?;
+        delta.segments.splice(0..0, skipped.iter().cloned());
+
+        if !skipped_date_ranges {
+            return Ok(delta);
+        }
+
+        let date_ranges = self
+            .metadata
+            .date_ranges
+            .iter()
+            .filter(|date_range| {
+                !skip_information
+                    .recently_removed_dataranges
+                    .contains(&date_range.id)
+                    && !delta
+                        .metadata
+                        .date_ranges
+                        .iter()
+                        .any(|other| other.id == date_range.id)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        delta.metadata.date_ranges.splice(0..0, date_ranges);
+
+        Ok(delta)
+    }
+}
+
+impl LiveWindow {
+    /// Produces the Playlist Delta Update answering an `_HLS_skip` request,
+    /// like [`MediaPlaylist::delta_update`], listing the date ranges recently
+    /// removed from the window.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the playlist does not support delta updates, or is
+    /// already a delta update.
+    pub fn delta_update(&self, skip: SkipRequest) -> Result<MediaPlaylist, DeltaUpdateError> {
+        self.playlist()
+            .delta_update(skip, self.removed_date_ranges().map(String::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delta_update() {
+        let playlist = MediaPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-VERSION:6
+#EXT-X-TARGETDURATION:4
+#EXT-X-MEDIA-SEQUENCE:10
+#EXT-X-SERVER-CONTROL:CAN-SKIP-UNTIL=24,CAN-SKIP-DATERANGES=YES
+#EXT-X-DATERANGE:ID="old",START-DATE="2024-01-01T00:00:04Z"
+#EXT-X-DATERANGE:ID="new",START-DATE="2024-01-01T00:00:18Z"
+#EXT-X-PROGRAM-DATE-TIME:2024-01-01T00:00:00Z
+#EXTINF:4,
+10.ts
+#EXTINF:4,
+11.ts
+#EXTINF:4,
+12.ts
+#EXTINF:4,
+13.ts
+#EXT-X-KEY:METHOD=AES-128,URI="key.bin"
+#EXTINF:4,
+14.ts
+#EXTINF:4,
+15.ts
+#EXTINF:4,
+16.ts
+#EXTINF:4,
+17.ts
+#EXTINF:4,
+18.ts
+#EXTINF:4,
+19.ts
+"#,
+        )
+        .unwrap();
+
+        let delta = playlist
+            .delta_update(SkipRequest::Yes, vec!["gone".into()])
+            .unwrap();
+        assert_eq!(delta.metadata.date_ranges.len(), 2);
+        assert_eq!(
+            delta.to_string(),
+            r#"#EXTM3U
+#EXT-X-VERSION:9
+#EXT-X-TARGETDURATION:4
+#EXT-X-MEDIA-SEQUENCE:10
+#EXT-X-SERVER-CONTROL:CAN-SKIP-UNTIL=24,CAN-SKIP-DATERANGES=YES
+#EXT-X-DATERANGE:ID="old",START-DATE="2024-01-01T00:00:04+00:00"
+#EXT-X-DATERANGE:ID="new",START-DATE="2024-01-01T00:00:18+00:00"
+#EXT-X-SKIP:SKIPPED-SEGMENTS=4
+#EXTINF:4
+#EXT-X-KEY:METHOD=AES-128,URI="key.bin"
+#EXT-X-PROGRAM-DATE-TIME:2024-01-01T00:00:16+00:00
+14.ts
+#EXTINF:4
+15.ts
+#EXTINF:4
+16.ts
+#EXTINF:4
+17.ts
+#EXTINF:4
+18.ts
+#EXTINF:4
+19.ts
+"#
+        );
+
+        let delta = playlist
+            .delta_update(SkipRequest::V2, vec!["gone".into()])
+            .unwrap();
+        assert_eq!(delta.metadata.date_ranges.len(), 1);
+        assert_eq!(delta.metadata.date_ranges[0].id, "new");
+        assert_eq!(
+            delta.metadata.skip,
+            Some(SkipInformation {
+                number_of_skipped_segments: 4,
+                recently_removed_dataranges: vec!["gone".into()],
+            })
+        );
+        assert_eq!(delta.required_version(), 10);
+
+        assert_eq!(
+            delta.delta_update(SkipRequest::Yes, vec![]),
+            Err(DeltaUpdateError::AlreadySkipped)
+        );
+        assert_eq!(
+            MediaPlaylist::default().delta_update(SkipRequest::Yes, vec![]),
+            Err(DeltaUpdateError::Unsupported)
+        );
+    }
+
+    #[test]
+    fn apply_delta_update() {
+        let previous = MediaPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-TARGETDURATION:4
+#EXT-X-MEDIA-SEQUENCE:9
+#EXT-X-DATERANGE:ID="gone",START-DATE="2024-01-01T00:00:00Z"
+#EXT-X-DATERANGE:ID="kept",START-DATE="2024-01-01T00:00:04Z"
+#EXT-X-DATERANGE:ID="changed",START-DATE="2024-01-01T00:00:08Z"
+#EXTINF:4,
+9.ts
+#EXTINF:4,
+10.ts
+#EXTINF:4,
+11.ts
+#EXTINF:4,
+12.ts
+"#,
+        )
+        .unwrap();
+        let delta = MediaPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-VERSION:10
+#EXT-X-TARGETDURATION:4
+#EXT-X-MEDIA-SEQUENCE:10
+#EXT-X-SERVER-CONTROL:CAN-SKIP-UNTIL=24,CAN-SKIP-DATERANGES=YES
+#EXT-X-DATERANGE:ID="changed",START-DATE="2024-01-01T00:00:08Z",DURATION=4
+#EXT-X-SKIP:SKIPPED-SEGMENTS=2,RECENTLY-REMOVED-DATERANGES="gone"
+#EXTINF:4,
+12.ts
+#EXTINF:4,
+13.ts
+"#,
+        )
+        .unwrap();
+
+        let playlist = previous
+            .apply_delta_update(delta.clone(), SkipRequest::V2)
+            .unwrap();
+        assert_eq!(playlist.metadata.skip, None);
+        assert_eq!(playlist.first_media_sequence_number, 10);
+        assert_eq!(
+            playlist
+                .segments
+                .iter()
+                .map(|segment| segment.uri.as_str())
+                .collect::<Vec<_>>(),
+            ["10.ts", "11.ts", "12.ts", "13.ts"]
+        );
+        assert_eq!(
+            playlist
+                .metadata
+                .date_ranges
+                .iter()
+                .map(|date_range| (date_range.id.as_str(), date_range.duration_seconds))
+                .collect::<Vec<_>>(),
+            [("kept", None), ("changed", Some(4.0))]
+        );
+
+        // Under `_HLS_skip=YES` the update has every date range, so "kept"
+        // has been removed since the previous playlist.
+        let playlist = previous
+            .apply_delta_update(delta.clone(), SkipRequest::Yes)
+            .unwrap();
+        assert_eq!(
+            playlist
+                .metadata
+                .date_ranges
+                .iter()
+                .map(|date_range| date_range.id.as_str())
+                .collect::<Vec<_>>(),
+            ["changed"]
+        );
+        assert_eq!(playlist.segments.len(), 4);
+
+        let mut later = previous;
+        later.first_media_sequence_number = 11;
+        assert_eq!(
+            later.apply_delta_update(delta, SkipRequest::V2),
+            Err(ApplyDeltaUpdateError::MissingSkippedSegments {
+                first_media_sequence_number: 10,
+                number_of_skipped_segments: 2,
+            })
+        );
+        assert_eq!(
+            later.apply_delta_update(MediaPlaylist::default(), SkipRequest::V2),
+            Err(ApplyDeltaUpdateError::NotDeltaUpdate)
+        );
+    }
+}
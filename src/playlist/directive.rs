@@ -0,0 +1,441 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{error::Error, fmt};
+
+use super::{MediaPlaylist, SkipRequest};
+use crate::variables::query_parameter;
+
+/// The delivery directives in the query of a Media Playlist request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct DeliveryDirectives {
+    /// The `_HLS_msn` and `_HLS_part` directives, requesting a blocking
+    /// playlist reload.
+    pub block: Option<BlockingRequest>,
+
+    /// The `_HLS_skip` directive, requesting a Playlist Delta Update.
+    pub skip: Option<SkipRequest>,
+}
+
+/// The segment, and optionally the Partial Segment, a blocking playlist
+/// reload waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BlockingRequest {
+    /// The value of `_HLS_msn`, the media sequence number of the segment.
+    pub media_sequence_number: u64,
+
+    /// The value of `_HLS_part`, the index of the Partial Segment within the
+    /// segment.
+    pub part: Option<u64>,
+}
+
+/// How a server should answer a request with [`DeliveryDirectives`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DirectiveStatus {
+    /// The playlist already contains what was requested, and can be sent
+    /// immediately.
+    Satisfied,
+
+    /// The request must be held until the playlist contains what was
+    /// requested.
+    Block,
+
+    /// The request is too far ahead of the playlist, and should be answered
+    /// with a 400 Bad Request.
+    Invalid,
+}
+
+/// An error encountered while parsing [`DeliveryDirectives`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryDirectiveError {
+    /// The value of `_HLS_msn` is not a decimal integer.
+    InvalidMediaSequenceNumber(String),
+
+    /// The value of `_HLS_part` is not a decimal integer.
+    InvalidPart(String),
+
+    /// The value of `_HLS_skip` is neither `YES` nor `v2`.
+    InvalidSkip(String),
+
+    /// `_HLS_part` was given without `_HLS_msn`.
+    PartWithoutMediaSequenceNumber,
+}
+
+impl fmt::Display for DeliveryDirectiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMediaSequenceNumber(value) => {
+                write!(f, "invalid _HLS_msn value {value:?}")
+            }
+            Self::InvalidPart(value) => write!(f, "invalid _HLS_part value {value:?}"),
+            Self::InvalidSkip(value) => write!(f, "invalid _HLS_skip value {value:?}"),
+            Self::PartWithoutMediaSequenceNumber => {
+                write!(f, "_HLS_part was given without _HLS_msn")
+            }
+        }
+    }
+}
+
+impl Error for DeliveryDirectiveError {}
+
+impl DeliveryDirectives {
+    /// Parses the delivery directives in the query of a playlist request
+    /// `uri`. Other query parameters are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if a directive has an invalid value, or if `_HLS_part`
+    /// is given without `_HLS_msn`.
+    pub fn from_uri(uri: &str) -> Result<Self, DeliveryDirectiveError> {
+        let query = uri
+            .split_once('?')
+            .map_or("", |(_, query)| query)
+            .split('#')
+            .next()
+            .unwrap_or_default();
+
+        Self::from_query(query)
+    }
+
+    /// Parses the delivery directives in `query`, the query component of a
+    /// playlist request URI without the leading `?`. Other query parameters
+    /// are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if a directive has an invalid value, or if `_HLS_part`
+    /// is given without `_HLS_msn`.
+    pub fn from_query(query: &str) -> Result<Self, DeliveryDirectiveError> {
+        let media_sequence_number = query_parameter(query, "_HLS_msn")
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| DeliveryDirectiveError::InvalidMediaSequenceNumber(value))
+            })
+            .transpose()?;
+        let part = query_parameter(query, "_HLS_part")
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| DeliveryDirectiveError::InvalidPart(value))
+            })
+            .transpose()?;
+        let skip = query_parameter(query, "_HLS_skip")
+            .map(|value| match value.as_str() {
+                "YES" => Ok(SkipRequest::Yes),
+                "v2" => Ok(SkipRequest::V2),
+                _ => Err(DeliveryDirectiveError::InvalidSkip(value)),
+            })
+            .transpose()?;
+
+        let block = match (media_sequence_number, part) {
+            (Some(media_sequence_number), part) => Some(BlockingRequest {
+                media_sequence_number,
+                part,
+            }),
+            (None, Some(_)) => return Err(DeliveryDirectiveError::PartWithoutMediaSequenceNumber),
+            (None, None) => None,
+        };
+
+        Ok(Self { block, skip })
+    }
+
+    /// Returns `uri` with the directives appended to it's query.
+    #[must_use]
+    pub fn to_uri(&self, uri: &str) -> String {
+        let query = self.to_string();
+        if query.is_empty() {
+            return uri.into();
+        }
+
+        let (uri, fragment) = uri
+            .split_once('#')
+            .map_or((uri, None), |(uri, fragment)| (uri, Some(fragment)));
+        let separator = match uri.find('?') {
+            None => "?",
+            Some(i) if i + 1 == uri.len() || uri.ends_with('&') => "",
+            Some(_) => "&",
+        };
+
+        let mut output = format!("{uri}{separator}{query}");
+        if let Some(fragment) = fragment {
+            output.push('#');
+            output.push_str(fragment);
+        }
+
+        output
+    }
+
+    /// Determines whether `playlist` satisfies the directives, or if the
+    /// request must block or be rejected.
+    ///
+    /// Directives are ignored if the playlist does not support blocking
+    /// playlist reloads, and `_HLS_part` is ignored if the playlist has no
+    /// [`PartInformation`](super::PartInformation). A request is invalid if
+    /// `_HLS_msn` is more than two segments past the last segment, or if
+    /// `_HLS_part` is more than the Advance Part Limit past the last Partial
+    /// Segment. A finished playlist never blocks, since it will not change.
+    #[must_use]
+    pub fn status(&self, playlist: &MediaPlaylist) -> DirectiveStatus {
+        let Some(block) = self
+            .block
+            .filter(|_| playlist.supports_blocking_playlist_reloads)
+        else {
+            return DirectiveStatus::Satisfied;
+        };
+        let msn = block.media_sequence_number;
+        let part = block.part.filter(|_| playlist.part_information.is_some());
+
+        // The media sequence number of the in-progress segment, after the last
+        // complete one.
+        let next = playlist
+            .first_media_sequence_number
+            .saturating_add(playlist.segments.len() as u64);
+        let parts_of = |msn: u64| {
+            let parts = if msn == next {
+                playlist
+                    .in_progress_segment
+                    .as_ref()
+                    .map_or(&[][..], |segment| &segment.parts)
+            } else {
+                msn.checked_sub(playlist.first_media_sequence_number)
+                    .and_then(|i| usize::try_from(i).ok())
+                    .and_then(|i| playlist.segments.get(i))
+                    .map_or(&[][..], |segment| &segment.parts)
+            };
+
+            parts.len() as u64
+        };
+        let last_part_msn = if parts_of(next) > 0 {
+            Some(next)
+        } else {
+            next.checked_sub(1).filter(|&last| parts_of(last) > 0)
+        };
+
+        let satisfied = part.map_or(msn < next, |part| {
+            msn < playlist.first_media_sequence_number
+                || parts_of(msn) > part
+                || last_part_msn.is_some_and(|last| last > msn)
+                || msn.saturating_add(1) < next
+        });
+        if satisfied {
+            return DirectiveStatus::Satisfied;
+        }
+
+        if msn > next.saturating_add(1) {
+            return DirectiveStatus::Invalid;
+        }
+        #[allow(clippy::cast_precision_loss)]
+        if let (Some(part), Some(information)) = (part, &playlist.part_information) {
+            // The last Partial Segment, or the start of the in-progress
+            // segment if there is none.
+            let (last_msn, last_parts) =
+                last_part_msn.map_or((next, 0), |last| (last, parts_of(last)));
+            // Segments which have not been written yet are estimated to have
+            // as many parts as fit in the target duration.
+            let segment_parts = playlist.target_duration as f64 / information.part_target_duration;
+
+            // How many parts past the last Partial Segment was requested.
+            let mut ahead = part.saturating_add(1) as f64;
+            if msn <= last_msn {
+                ahead -= (last_parts as f64).min(ahead);
+            } else {
+                if last_msn == next {
+                    ahead += (segment_parts - last_parts as f64).max(0.0);
+                }
+                let unwritten = msn.saturating_sub(last_msn.saturating_add(1).max(next));
+                ahead += unwritten as f64 * segment_parts;
+            }
+
+            let limit = if information.part_target_duration < 1.0 {
+                3.0 / information.part_target_duration
+            } else {
+                3.0
+            };
+            if ahead > limit {
+                return DirectiveStatus::Invalid;
+            }
+        }
+
+        if playlist.finished {
+            DirectiveStatus::Satisfied
+        } else {
+            DirectiveStatus::Block
+        }
+    }
+}
+
+impl fmt::Display for DeliveryDirectives {
+    /// Writes the directives as a query string, without the leading `?`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if let Some(block) = self.block {
+            write!(f, "_HLS_msn={}", block.media_sequence_number)?;
+            if let Some(part) = block.part {
+                write!(f, "&_HLS_part={part}")?;
+            }
+            "&"
+        } else {
+            ""
+        };
+        match self.skip {
+            Some(SkipRequest::Yes) => write!(f, "{separator}_HLS_skip=YES"),
+            Some(SkipRequest::V2) => write!(f, "{separator}_HLS_skip=v2"),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::InProgressSegment;
+
+    #[test]
+    fn parse_and_build_directives() {
+        let directives =
+            DeliveryDirectives::from_uri("low.m3u8?token=a&_HLS_msn=12&_HLS_part=3&_HLS_skip=v2")
+                .unwrap();
+        assert_eq!(
+            directives,
+            DeliveryDirectives {
+                block: Some(BlockingRequest {
+                    media_sequence_number: 12,
+                    part: Some(3),
+                }),
+                skip: Some(SkipRequest::V2),
+            }
+        );
+        assert_eq!(
+            directives.to_uri("low.m3u8?token=a#t=1"),
+            "low.m3u8?token=a&_HLS_msn=12&_HLS_part=3&_HLS_skip=v2#t=1"
+        );
+        assert_eq!(
+            DeliveryDirectives {
+                block: None,
+                skip: Some(SkipRequest::Yes),
+            }
+            .to_uri("low.m3u8"),
+            "low.m3u8?_HLS_skip=YES"
+        );
+        assert_eq!(
+            DeliveryDirectives::from_uri("low.m3u8"),
+            Ok(DeliveryDirectives::default())
+        );
+
+        assert_eq!(
+            DeliveryDirectives::from_query("_HLS_part=1"),
+            Err(DeliveryDirectiveError::PartWithoutMediaSequenceNumber)
+        );
+        assert_eq!(
+            DeliveryDirectives::from_query("_HLS_msn=-1"),
+            Err(DeliveryDirectiveError::InvalidMediaSequenceNumber(
+                "-1".into()
+            ))
+        );
+        assert_eq!(
+            DeliveryDirectives::from_query("_HLS_skip=yes"),
+            Err(DeliveryDirectiveError::InvalidSkip("yes".into()))
+        );
+    }
+
+    #[test]
+    fn directive_status() {
+        let playlist = MediaPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-TARGETDURATION:4
+#EXT-X-MEDIA-SEQUENCE:10
+#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=3
+#EXT-X-PART-INF:PART-TARGET=1
+#EXTINF:4,
+10.ts
+#EXT-X-PART:DURATION=1,URI="11.0.ts"
+#EXT-X-PART:DURATION=1,URI="11.1.ts"
+#EXTINF:4,
+11.ts
+"#,
+        )
+        .unwrap();
+        let status = |media_sequence_number, part| {
+            DeliveryDirectives {
+                block: Some(BlockingRequest {
+                    media_sequence_number,
+                    part,
+                }),
+                skip: None,
+            }
+            .status(&playlist)
+        };
+
+        assert_eq!(status(11, None), DirectiveStatus::Satisfied);
+        assert_eq!(status(10, Some(5)), DirectiveStatus::Satisfied);
+        assert_eq!(status(11, Some(1)), DirectiveStatus::Satisfied);
+        assert_eq!(status(11, Some(2)), DirectiveStatus::Block);
+        assert_eq!(status(12, None), DirectiveStatus::Block);
+        assert_eq!(status(13, None), DirectiveStatus::Block);
+        assert_eq!(status(14, None), DirectiveStatus::Invalid);
+        assert_eq!(status(11, Some(4)), DirectiveStatus::Block);
+        assert_eq!(status(11, Some(5)), DirectiveStatus::Invalid);
+        assert_eq!(status(12, Some(2)), DirectiveStatus::Block);
+        assert_eq!(status(12, Some(3)), DirectiveStatus::Invalid);
+        assert_eq!(status(13, Some(2)), DirectiveStatus::Invalid);
+        assert_eq!(status(u64::MAX, Some(u64::MAX)), DirectiveStatus::Invalid);
+
+        let mut playlist = playlist;
+        playlist.in_progress_segment = Some(Box::new(InProgressSegment {
+            parts: playlist.segments[1].parts.clone(),
+            ..InProgressSegment::default()
+        }));
+        let status = |media_sequence_number, part| {
+            DeliveryDirectives {
+                block: Some(BlockingRequest {
+                    media_sequence_number,
+                    part,
+                }),
+                skip: None,
+            }
+            .status(&playlist)
+        };
+        assert_eq!(status(11, Some(7)), DirectiveStatus::Satisfied);
+        assert_eq!(status(12, Some(1)), DirectiveStatus::Satisfied);
+        assert_eq!(status(12, None), DirectiveStatus::Block);
+        assert_eq!(status(12, Some(4)), DirectiveStatus::Block);
+        assert_eq!(status(12, Some(5)), DirectiveStatus::Invalid);
+        assert_eq!(status(13, Some(0)), DirectiveStatus::Block);
+        assert_eq!(status(13, Some(1)), DirectiveStatus::Invalid);
+
+        let playlist = MediaPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-TARGETDURATION:4
+#EXT-X-MEDIA-SEQUENCE:18446744073709551615
+#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES
+#EXTINF:4,
+1.ts
+#EXTINF:4,
+2.ts
+",
+        )
+        .unwrap();
+        let status = |media_sequence_number| {
+            DeliveryDirectives {
+                block: Some(BlockingRequest {
+                    media_sequence_number,
+                    part: None,
+                }),
+                skip: None,
+            }
+            .status(&playlist)
+        };
+        assert_eq!(status(u64::MAX), DirectiveStatus::Block);
+    }
+}
@@ -0,0 +1,474 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::mem;
+
+use chrono::{DateTime, FixedOffset};
+
+use super::{ByteRangeOrBitrate, InProgressSegment, MediaPlaylist, MediaSegment, PartialSegment};
+use crate::DateRange;
+
+/// How many Media Segments a [`LiveWindow`] keeps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowLimit {
+    /// Keep at most this many segments.
+    Segments(usize),
+
+    /// Evict the oldest segments for as long as the remaining segments still
+    /// last at least this many seconds.
+    Duration(f64),
+}
+
+/// A sliding window over the segments of a live [`MediaPlaylist`].
+///
+/// New segments are pushed to the end of the playlist, and the oldest
+/// segments are evicted once the window exceeds it's [`WindowLimit`]. The
+/// media sequence number and discontinuity sequence number of the playlist
+/// are kept up to date as segments are evicted, and date ranges are removed
+/// once they end before the first segment of the window.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveWindow {
+    playlist: MediaPlaylist,
+    limit: WindowLimit,
+
+    /// The ID of each removed date range, with the media sequence number of
+    /// the newest segment at the time it was removed.
+    removed_date_ranges: Vec<(String, u64)>,
+}
+
+impl LiveWindow {
+    /// Creates a window over the segments of `playlist`. Nothing is evicted
+    /// until the next segment is pushed.
+    #[must_use]
+    pub const fn new(playlist: MediaPlaylist, limit: WindowLimit) -> Self {
+        Self {
+            playlist,
+            limit,
+            removed_date_ranges: Vec::new(),
+        }
+    }
+
+    /// Returns the playlist containing the segments in the window.
+    #[must_use]
+    pub const fn playlist(&self) -> &MediaPlaylist {
+        &self.playlist
+    }
+
+    /// Returns the playlist mutably, e.g. to add a date range or update the
+    /// rendition reports.
+    ///
+    /// Segments should only be added with [`LiveWindow::push_segment`], so
+    /// that the window can keep the sequence numbers up to date.
+    pub const fn playlist_mut(&mut self) -> &mut MediaPlaylist {
+        &mut self.playlist
+    }
+
+    /// Consumes the window, returning it's playlist.
+    #[must_use]
+    pub fn into_playlist(self) -> MediaPlaylist {
+        self.playlist
+    }
+
+    /// Returns the parts pushed since the last segment, which are the parts
+    /// of the playlist's [`MediaPlaylist::in_progress_segment`].
+    #[must_use]
+    pub fn pending_parts(&self) -> &[PartialSegment] {
+        self.playlist
+            .in_progress_segment
+            .as_ref()
+            .map_or(&[], |segment| &segment.parts)
+    }
+
+    /// Returns the media sequence number the next pushed segment will have.
+    #[must_use]
+    pub const fn next_media_sequence_number(&self) -> u64 {
+        self.playlist
+            .first_media_sequence_number
+            .saturating_add(self.playlist.segments.len() as u64)
+    }
+
+    /// Returns the IDs of the date ranges which were recently removed from
+    /// the playlist, oldest first, for the `RECENTLY-REMOVED-DATERANGES` of
+    /// an EXT-X-SKIP tag.
+    ///
+    /// An ID is kept for as long as the segment which was the newest when the
+    /// date range was removed is still in the window.
+    pub fn removed_date_ranges(&self) -> impl Iterator<Item = &str> {
+        self.removed_date_ranges.iter().map(|(id, _)| id.as_str())
+    }
+
+    /// Adds a part to the playlist's [`MediaPlaylist::in_progress_segment`],
+    /// which inherits the encryption, media initialization section and bitrate
+    /// of the last segment when it is created.
+    ///
+    /// The parts are moved into the next segment pushed with
+    /// [`LiveWindow::push_segment`].
+    pub fn push_part(&mut self, part: PartialSegment) {
+        let last = self.playlist.segments.last();
+        self.playlist
+            .in_progress_segment
+            .get_or_insert_with(|| {
+                Box::new(InProgressSegment {
+                    encryption: last.and_then(|segment| segment.encryption.clone()),
+                    media_initialization_section: last
+                        .and_then(|segment| segment.media_initialization_section.clone()),
+                    bitrate: last.and_then(|segment| match segment.byte_range_or_bitrate {
+                        Some(ByteRangeOrBitrate::Bitrate(kbps)) => Some(kbps),
+                        _ => None,
+                    }),
+                    ..InProgressSegment::default()
+                })
+            })
+            .parts
+            .push(part);
+    }
+
+    /// Appends a complete segment to the end of the playlist. The parts pushed
+    /// since the last segment are moved in front of it's own parts.
+    ///
+    /// The oldest segments are then evicted until the window is within it's
+    /// limit, expired date ranges are removed, and parts more than three
+    /// target durations from the end of the playlist are removed.
+    pub fn push_segment(&mut self, mut segment: MediaSegment) {
+        if let Some(in_progress) = self.playlist.in_progress_segment.take() {
+            let mut parts = in_progress.parts;
+            parts.append(&mut segment.parts);
+            segment.parts = parts;
+        }
+        self.playlist.segments.push(segment);
+
+        self.evict_segments();
+        self.remove_expired_date_ranges();
+        self.remove_old_parts();
+    }
+
+    fn evict_segments(&mut self) {
+        let segments = &self.playlist.segments;
+        let count = match self.limit {
+            WindowLimit::Segments(max) => segments.len().saturating_sub(max),
+            WindowLimit::Duration(min_seconds) => {
+                let mut remaining: f64 = segments
+                    .iter()
+                    .map(|segment| segment.duration_seconds.as_f64())
+                    .sum();
+                segments
+                    .iter()
+                    .take_while(|segment| {
+                        remaining -= segment.duration_seconds.as_f64();
+                        remaining >= min_seconds
+                    })
+                    .count()
+            }
+        };
+
+        // The program date time of an evicted segment is carried forward, so
+        // the timeline of the window is not lost.
+        let mut time = None;
+        for segment in self.playlist.segments.drain(..count) {
+            self.playlist.first_media_sequence_number =
+                self.playlist.first_media_sequence_number.saturating_add(1);
+            if segment.is_discontinuity {
+                self.playlist.discontinuity_sequence_number = self
+                    .playlist
+                    .discontinuity_sequence_number
+                    .saturating_add(1);
+            }
+
+            time = segment
+                .absolute_time
+                .or(time)
+                .map(|time| time + seconds(segment.duration_seconds.as_f64()));
+        }
+        if let Some(first) = self.playlist.segments.first_mut() {
+            if first.absolute_time.is_none() {
+                first.absolute_time = time;
+            }
+        }
+
+        let first = self.playlist.first_media_sequence_number;
+        self.removed_date_ranges
+            .retain(|(_, sequence_number)| *sequence_number >= first);
+    }
+
+    fn remove_expired_date_ranges(&mut self) {
+        let Some(window_start) = self.playlist.start_time(0) else {
+            return;
+        };
+
+        let date_ranges = &self.playlist.metadata.date_ranges;
+        let expired: Vec<bool> = date_ranges
+            .iter()
+            .map(|date_range| {
+                date_range_end(date_range, date_ranges).is_some_and(|end| end < window_start)
+            })
+            .collect();
+
+        let newest = self.next_media_sequence_number().saturating_sub(1);
+        let date_ranges = mem::take(&mut self.playlist.metadata.date_ranges);
+        for (date_range, is_expired) in date_ranges.into_iter().zip(expired) {
+            if is_expired {
+                self.removed_date_ranges.push((date_range.id, newest));
+            } else {
+                self.playlist.metadata.date_ranges.push(date_range);
+            }
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn remove_old_parts(&mut self) {
+        let max_distance = self.playlist.target_duration as f64 * 3.0;
+        let mut distance = 0.0;
+        for segment in self.playlist.segments.iter_mut().rev() {
+            if distance > max_distance {
+                segment.parts.clear();
+            }
+            distance += segment.duration_seconds.as_f64();
+        }
+    }
+}
+
+impl MediaPlaylist {
+    /// Returns the time at which the segment at `index` begins, from the
+    /// program date time of the closest segment before it, or otherwise
+    /// after it.
+    pub(super) fn start_time(&self, index: usize) -> Option<DateTime<FixedOffset>> {
+        let (before, after) = self.segments.split_at(index.min(self.segments.len()));
+
+        let mut elapsed = 0.0;
+        for segment in before.iter().rev() {
+            elapsed += segment.duration_seconds.as_f64();
+            if let Some(time) = segment.absolute_time {
+                return Some(time + seconds(elapsed));
+            }
+        }
+
+        let mut elapsed = 0.0;
+        for segment in after {
+            if let Some(time) = segment.absolute_time {
+                return Some(time - seconds(elapsed));
+            }
+            elapsed += segment.duration_seconds.as_f64();
+        }
+
+        None
+    }
+}
+
+/// Returns the time at which `date_range` ends, if it is known.
+fn date_range_end(
+    date_range: &DateRange,
+    date_ranges: &[DateRange],
+) -> Option<DateTime<FixedOffset>> {
+    if let Some(end_date) = date_range.end_date {
+        return Some(end_date);
+    }
+    if let Some(duration) = date_range.duration_seconds {
+        return Some(date_range.start_date + seconds(duration));
+    }
+
+    // An END-ON-NEXT date range ends when the next one of the same class
+    // starts.
+    if date_range.end_on_next {
+        return date_ranges
+            .iter()
+            .filter(|other| {
+                other.class == date_range.class && other.start_date > date_range.start_date
+            })
+            .map(|other| other.start_date)
+            .min();
+    }
+
+    None
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn seconds(seconds: f64) -> chrono::Duration {
+    chrono::Duration::microseconds((seconds * 1e6).round() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FloatOrInteger;
+
+    fn segment(uri: &str) -> MediaSegment {
+        MediaSegment {
+            uri: uri.into(),
+            duration_seconds: FloatOrInteger::Integer(4),
+            title: String::new(),
+            byte_range_or_bitrate: None,
+            is_discontinuity: false,
+            encryption: None,
+            media_initialization_section: None,
+            absolute_time: None,
+            is_gap: false,
+            parts: vec![],
+        }
+    }
+
+    fn part(uri: &str) -> PartialSegment {
+        PartialSegment {
+            uri: uri.into(),
+            duration_in_seconds: 2.0,
+            is_independent: true,
+            byte_range: None,
+            is_gap: false,
+        }
+    }
+
+    #[test]
+    fn slide_window() {
+        let start = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z").unwrap();
+        let mut window = LiveWindow::new(
+            MediaPlaylist {
+                target_duration: 4,
+                ..MediaPlaylist::default()
+            },
+            WindowLimit::Segments(3),
+        );
+        window.playlist_mut().metadata.date_ranges = [("ad-1", 0.0), ("ad-2", 4.0), ("ad-3", 8.0)]
+            .into_iter()
+            .map(|(id, offset)| DateRange {
+                id: id.into(),
+                class: None,
+                start_date: start + seconds(offset),
+                cue: None,
+                end_date: None,
+                duration_seconds: (id == "ad-1").then_some(4.0),
+                planned_duration_seconds: None,
+                client_attributes: std::collections::HashMap::new(),
+                scte35_cmd: vec![],
+                scte35_in: vec![],
+                scte35_out: vec![],
+                end_on_next: id == "ad-2",
+            })
+            .collect();
+
+        for i in 0..6 {
+            let mut segment = segment(&format!("{i}.ts"));
+            segment.is_discontinuity = i == 1;
+            if i == 0 {
+                segment.absolute_time = Some(start);
+            }
+            window.push_segment(segment);
+        }
+        window.push_part(part("6.0.mp4"));
+
+        let playlist = window.playlist();
+        assert_eq!(
+            playlist
+                .segments
+                .iter()
+                .map(|segment| segment.uri.as_str())
+                .collect::<Vec<_>>(),
+            ["3.ts", "4.ts", "5.ts"]
+        );
+        assert_eq!(playlist.first_media_sequence_number, 3);
+        assert_eq!(playlist.discontinuity_sequence_number, 1);
+        assert_eq!(window.next_media_sequence_number(), 6);
+
+        assert_eq!(
+            playlist.segments[0].absolute_time,
+            Some(start + seconds(12.0))
+        );
+        assert_eq!(playlist.metadata.date_ranges.len(), 1);
+        assert_eq!(
+            window.removed_date_ranges().collect::<Vec<_>>(),
+            ["ad-1", "ad-2"]
+        );
+
+        assert_eq!(window.pending_parts(), [part("6.0.mp4")]);
+        assert!(window
+            .playlist()
+            .to_string()
+            .ends_with("5.ts\n#EXT-X-PART:URI=\"6.0.mp4\",DURATION=2,INDEPENDENT=YES\n"));
+
+        window.push_segment(segment("6.ts"));
+        assert_eq!(window.playlist().segments[2].parts, [part("6.0.mp4")]);
+        assert!(window.pending_parts().is_empty());
+    }
+
+    #[test]
+    fn next_media_sequence_number_saturates() {
+        let window = LiveWindow::new(
+            MediaPlaylist {
+                first_media_sequence_number: u64::MAX,
+                segments: vec![segment("1.ts"), segment("2.ts")],
+                ..MediaPlaylist::default()
+            },
+            WindowLimit::Segments(3),
+        );
+        assert_eq!(window.next_media_sequence_number(), u64::MAX);
+    }
+
+    #[test]
+    fn evict_segments_saturates() {
+        let mut discontinuity = segment("1.ts");
+        discontinuity.is_discontinuity = true;
+        let mut window = LiveWindow::new(
+            MediaPlaylist {
+                first_media_sequence_number: u64::MAX,
+                discontinuity_sequence_number: u64::MAX,
+                segments: vec![discontinuity],
+                ..MediaPlaylist::default()
+            },
+            WindowLimit::Segments(1),
+        );
+        window.push_segment(segment("2.ts"));
+
+        let playlist = window.playlist();
+        assert_eq!(playlist.segments.len(), 1);
+        assert_eq!(playlist.first_media_sequence_number, u64::MAX);
+        assert_eq!(playlist.discontinuity_sequence_number, u64::MAX);
+    }
+
+    #[test]
+    fn evict_by_duration() {
+        let mut window = LiveWindow::new(
+            MediaPlaylist {
+                target_duration: 4,
+                ..MediaPlaylist::default()
+            },
+            WindowLimit::Duration(10.0),
+        );
+        for i in 0..8 {
+            window.push_part(part(&format!("{i}.0.mp4")));
+            window.push_segment(segment(&format!("{i}.ts")));
+        }
+
+        let playlist = window.playlist();
+        assert_eq!(playlist.segments.len(), 3);
+        assert_eq!(playlist.first_media_sequence_number, 5);
+        assert!(playlist
+            .segments
+            .iter()
+            .all(|segment| !segment.parts.is_empty()));
+
+        // Segments which end more than three target durations from the end
+        // lose their parts, while the last one keeps them.
+        window.playlist_mut().target_duration = 1;
+        window.push_part(part("8.0.mp4"));
+        window.push_segment(segment("8.ts"));
+        assert_eq!(
+            window
+                .playlist()
+                .segments
+                .iter()
+                .map(|segment| segment.parts.len())
+                .collect::<Vec<_>>(),
+            [0, 0, 1]
+        );
+        assert_eq!(window.playlist().segments[2].parts, [part("8.0.mp4")]);
+    }
+}
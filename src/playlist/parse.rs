@@ -0,0 +1,1409 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{borrow::Cow, error::Error, fmt, io};
+
+use super::{
+    AudioRendition, ByteRangeOrBitrate, ClosedCaptionRendition, IFrameStream, InProgressSegment,
+    MediaInitializationSection, MediaPlaylist, MediaSegment, MultivariantPlaylist, PartInformation,
+    PartialSegment, Playlist, RenditionGroup, RenditionInfo, SkipInformation, StartOffset,
+    SubtitleRendition, VariantStream, VideoRendition,
+};
+use crate::tags::{MediaType, ParseTagError, Tag};
+use crate::variables::{VariableError, Variables};
+
+/// An error encountered while parsing a playlist.
+#[derive(Debug)]
+pub enum ParsePlaylistError {
+    /// An io error was encountered while reading the playlist.
+    Io(io::Error),
+
+    /// The playlist is not valid UTF-8.
+    InvalidUtf8,
+
+    /// The playlist does not begin with an EXTM3U tag.
+    MissingHeader,
+
+    /// A tag could not be parsed.
+    InvalidTag {
+        /// The line number of the tag, starting from 1.
+        line: usize,
+
+        /// Why the tag could not be parsed.
+        error: ParseTagError,
+    },
+
+    /// A tag appeared which is not allowed in this kind of playlist.
+    UnexpectedTag {
+        /// The line number of the tag, starting from 1.
+        line: usize,
+
+        /// The name of the tag, e.g. `#EXT-X-STREAM-INF`.
+        name: String,
+    },
+
+    /// The playlist contains both Media Playlist and Multivariant Playlist tags.
+    MixedPlaylist {
+        /// The line number of the first tag or URI which conflicts with an
+        /// earlier one, starting from 1.
+        line: usize,
+    },
+
+    /// A URI line appeared where one is not allowed.
+    UnexpectedUri {
+        /// The line number of the URI, starting from 1.
+        line: usize,
+    },
+
+    /// A Media Segment URI was not preceded by an EXTINF tag.
+    MissingSegmentDuration {
+        /// The line number of the URI, starting from 1.
+        line: usize,
+    },
+
+    /// The playlist ended with Media Segment tags which were not followed by a URI.
+    MissingSegmentUri,
+
+    /// The Media Playlist has no EXT-X-TARGETDURATION tag.
+    MissingTargetDuration,
+
+    /// The Media Playlist has an EXT-X-PART-INF tag, but no PART-HOLD-BACK
+    /// attribute in an EXT-X-SERVER-CONTROL tag.
+    MissingPartHoldBack,
+
+    /// A variable could not be defined or substituted.
+    Variable {
+        /// The line number of the tag or URI, starting from 1.
+        line: usize,
+
+        /// Why the variable could not be defined or substituted.
+        error: VariableError,
+    },
+}
+
+impl fmt::Display for ParsePlaylistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => write!(f, "io error while reading playlist: {error}"),
+            Self::InvalidUtf8 => write!(f, "playlist is not valid UTF-8"),
+            Self::MissingHeader => write!(f, "playlist does not begin with #EXTM3U"),
+            Self::InvalidTag { line, error } => write!(f, "line {line}: {error}"),
+            Self::UnexpectedTag { line, name } => {
+                write!(f, "line {line}: {name} is not allowed in this playlist")
+            }
+            Self::MixedPlaylist { line } => write!(
+                f,
+                "line {line}: media playlist and multivariant playlist tags cannot be mixed"
+            ),
+            Self::UnexpectedUri { line } => write!(f, "line {line}: unexpected URI"),
+            Self::MissingSegmentDuration { line } => {
+                write!(f, "line {line}: media segment has no EXTINF tag")
+            }
+            Self::MissingSegmentUri => write!(f, "last media segment has no URI"),
+            Self::MissingTargetDuration => write!(f, "playlist has no EXT-X-TARGETDURATION tag"),
+            Self::MissingPartHoldBack => write!(
+                f,
+                "playlist has an EXT-X-PART-INF tag but no PART-HOLD-BACK attribute"
+            ),
+            Self::Variable { line, error } => write!(f, "line {line}: {error}"),
+        }
+    }
+}
+
+impl Error for ParsePlaylistError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::InvalidTag { error, .. } => Some(error),
+            Self::Variable { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParsePlaylistError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl Playlist {
+    /// Parses either kind of playlist from an extended M3U playlist in `input`.
+    ///
+    /// The kind of playlist is decided by the tags present: EXT-X-MEDIA,
+    /// EXT-X-STREAM-INF, EXT-X-I-FRAME-STREAM-INF, EXT-X-SESSION-DATA,
+    /// EXT-X-SESSION-KEY, and EXT-X-CONTENT-STEERING make a Multivariant
+    /// Playlist, while Media Segment and Media Playlist tags (and bare URI
+    /// lines) make a Media Playlist. A playlist with neither is parsed as an
+    /// empty Multivariant Playlist.
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` if `input` is not a valid playlist, including when it
+    /// contains tags of both kinds.
+    pub fn parse(input: &[u8]) -> Result<Self, ParsePlaylistError> {
+        let input = std::str::from_utf8(input).map_err(|_| ParsePlaylistError::InvalidUtf8)?;
+        let lines = Lines::new(input)?.collect::<Result<Vec<_>, _>>()?;
+
+        let mut is_media_playlist = None;
+        for (line_number, line) in &lines {
+            let is_media_line = match line {
+                Line::Uri(_) => true,
+                Line::Tag(_, tag) => match is_media_playlist_tag(tag) {
+                    Some(is_media_tag) => is_media_tag,
+                    None => continue,
+                },
+            };
+
+            match is_media_playlist {
+                None => is_media_playlist = Some(is_media_line),
+                Some(is_media) if is_media != is_media_line => {
+                    return Err(ParsePlaylistError::MixedPlaylist { line: *line_number })
+                }
+                Some(_) => (),
+            }
+        }
+
+        let lines = lines.into_iter().map(Ok);
+        if is_media_playlist == Some(true) {
+            MediaPlaylist::parse_lines(lines).map(Self::Media)
+        } else {
+            MultivariantPlaylist::parse_lines(lines).map(Self::Multivariant)
+        }
+    }
+
+    /// Reads `reader` to the end and parses either kind of playlist from it.
+    /// See [`Playlist::parse`].
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` when encountering an io error on `reader`, or if the
+    /// playlist is not valid.
+    pub fn from_reader(mut reader: impl io::Read) -> Result<Self, ParsePlaylistError> {
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input)?;
+
+        Self::parse(&input)
+    }
+}
+
+impl MediaPlaylist {
+    /// Parses a `MediaPlaylist` from an extended M3U playlist in `input`.
+    ///
+    /// Media Segments inherit the EXT-X-KEY, EXT-X-MAP, and EXT-X-BITRATE tags
+    /// that precede them, and EXT-X-PART tags are attached to the `MediaSegment`
+    /// whose URI follows them. EXT-X-PART tags after the last URI line form the
+    /// [`MediaPlaylist::in_progress_segment`]. Comments, EXT-X-VERSION tags, and
+    /// unrecognized tags are ignored.
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` if `input` is not a valid Media Playlist.
+    pub fn parse(input: &[u8]) -> Result<Self, ParsePlaylistError> {
+        let input = std::str::from_utf8(input).map_err(|_| ParsePlaylistError::InvalidUtf8)?;
+        Self::parse_lines(Lines::new(input)?)
+    }
+
+    /// Reads `reader` to the end and parses a `MediaPlaylist` from it.
+    /// See [`MediaPlaylist::parse`].
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` when encountering an io error on `reader`, or if the
+    /// playlist is not a valid Media Playlist.
+    pub fn from_reader(mut reader: impl io::Read) -> Result<Self, ParsePlaylistError> {
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input)?;
+
+        Self::parse(&input)
+    }
+
+    /// Parses a `MediaPlaylist` like [`MediaPlaylist::parse`], substituting
+    /// variable references in URI lines, quoted-string attribute values, and
+    /// hexadecimal-sequence attribute values as they are parsed.
+    ///
+    /// `imports` are the variables of the Multivariant Playlist, and `query` is
+    /// the query component of the playlist's URI, without the leading `?`. See
+    /// [`Variables::resolve`].
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` if `input` is not a valid Media Playlist, or if a
+    /// variable can not be defined or is referenced without being defined.
+    pub fn parse_with_variables(
+        input: &[u8],
+        imports: &Variables,
+        query: Option<&str>,
+    ) -> Result<Self, ParsePlaylistError> {
+        let input = std::str::from_utf8(input).map_err(|_| ParsePlaylistError::InvalidUtf8)?;
+        Self::parse_lines(Lines::new(input)?.with_variables(imports, query))
+    }
+
+    fn parse_lines<'a>(
+        lines: impl Iterator<Item = Result<(usize, Line<'a>), ParsePlaylistError>>,
+    ) -> Result<Self, ParsePlaylistError> {
+        let mut playlist = Self::default();
+        let mut target_duration = None;
+        let mut part_target_duration = None;
+        let mut part_hold_back = None;
+
+        let mut segment = SegmentBuilder::default();
+        let mut encryption = None;
+        let mut media_initialization_section = None;
+        let mut bitrate = None;
+
+        for line in lines {
+            let (line_number, line) = line?;
+            let (raw_line, tag) = match line {
+                Line::Uri(uri) => {
+                    let segment = std::mem::take(&mut segment);
+                    let duration_seconds = segment
+                        .duration_seconds
+                        .ok_or(ParsePlaylistError::MissingSegmentDuration { line: line_number })?;
+
+                    playlist.segments.push(MediaSegment {
+                        uri: uri.into(),
+                        duration_seconds,
+                        title: segment.title,
+                        byte_range_or_bitrate: segment
+                            .byte_range
+                            .map(ByteRangeOrBitrate::ByteRange)
+                            .or_else(|| bitrate.map(ByteRangeOrBitrate::Bitrate)),
+                        is_discontinuity: segment.is_discontinuity,
+                        encryption: encryption.clone(),
+                        media_initialization_section: media_initialization_section.clone(),
+                        absolute_time: segment.absolute_time,
+                        is_gap: segment.is_gap,
+                        parts: segment.parts,
+                    });
+
+                    continue;
+                }
+                Line::Tag(raw_line, tag) => (raw_line, tag),
+            };
+
+            match tag {
+                Tag::XVersion { .. } => (),
+                Tag::XDefine(definition) => playlist.variables.push(definition),
+                Tag::XIndependentSegments => playlist.is_independent_segments = true,
+                Tag::XStart {
+                    offset_seconds,
+                    is_precise,
+                } => {
+                    playlist.start_offset = Some(StartOffset {
+                        offset_in_seconds: offset_seconds,
+                        is_precise,
+                    });
+                }
+                Tag::XTargetDuration {
+                    target_duration_seconds,
+                } => target_duration = Some(target_duration_seconds),
+                Tag::XMediaSequence { sequence_number } => {
+                    playlist.first_media_sequence_number = sequence_number;
+                }
+                Tag::XDiscontinuitySequence { sequence_number } => {
+                    playlist.discontinuity_sequence_number = sequence_number;
+                }
+                Tag::XEndList => playlist.finished = true,
+                Tag::XPlaylistType(playlist_type) => playlist.playlist_type = Some(playlist_type),
+                Tag::XIFramesOnly => playlist.iframes_only = true,
+                Tag::XPartInf {
+                    part_target_duration_seconds,
+                } => part_target_duration = Some(part_target_duration_seconds),
+                Tag::XServerControl {
+                    delta_update_info,
+                    hold_back,
+                    part_hold_back: server_part_hold_back,
+                    can_block_reload,
+                } => {
+                    playlist.playlist_delta_updates_information = delta_update_info;
+                    playlist.hold_back_seconds = hold_back;
+                    playlist.supports_blocking_playlist_reloads = can_block_reload;
+                    part_hold_back = server_part_hold_back;
+                }
+                Tag::XDateRange(date_range) => playlist.metadata.date_ranges.push(date_range),
+                Tag::XSkip {
+                    number_of_skipped_segments,
+                    recently_removed_dataranges,
+                } => {
+                    playlist.metadata.skip = Some(SkipInformation {
+                        number_of_skipped_segments,
+                        recently_removed_dataranges,
+                    });
+                }
+                Tag::XPreloadHint(hint) => playlist.metadata.preload_hints.push(hint),
+                Tag::XRenditionReport(report) => playlist.metadata.rendition_reports.push(report),
+
+                Tag::Inf {
+                    duration_seconds,
+                    title,
+                } => {
+                    segment.duration_seconds = Some(duration_seconds);
+                    segment.title = title;
+                }
+                Tag::XByterange(byte_range) => segment.byte_range = Some(byte_range),
+                Tag::XDiscontinuity => segment.is_discontinuity = true,
+                Tag::XKey(method) => encryption = method,
+                Tag::XMap { uri, range } => {
+                    media_initialization_section = Some(MediaInitializationSection { uri, range });
+                }
+                Tag::XProgramDateTime(time) => segment.absolute_time = Some(time),
+                Tag::XGap => segment.is_gap = true,
+                Tag::XBitrate { kbps } => bitrate = Some(kbps),
+                Tag::XPart {
+                    uri,
+                    duration_seconds,
+                    is_independent,
+                    byte_range,
+                    is_gap,
+                } => segment.parts.push(PartialSegment {
+                    uri,
+                    duration_in_seconds: duration_seconds,
+                    is_independent,
+                    byte_range,
+                    is_gap,
+                }),
+
+                Tag::M3u
+                | Tag::XMedia { .. }
+                | Tag::XStreamInf { .. }
+                | Tag::XIFrameStreamInf { .. }
+                | Tag::XSessionData(_)
+                | Tag::XSessionKey(_)
+                | Tag::XContentSteering(_) => {
+                    return Err(ParsePlaylistError::UnexpectedTag {
+                        line: line_number,
+                        name: tag_name(raw_line).into(),
+                    })
+                }
+            }
+        }
+
+        if segment.is_in_progress() {
+            playlist.in_progress_segment = Some(Box::new(InProgressSegment {
+                is_discontinuity: segment.is_discontinuity,
+                encryption,
+                media_initialization_section,
+                bitrate,
+                absolute_time: segment.absolute_time,
+                parts: segment.parts,
+            }));
+        } else if !segment.is_empty() {
+            return Err(ParsePlaylistError::MissingSegmentUri);
+        }
+
+        playlist.target_duration =
+            target_duration.ok_or(ParsePlaylistError::MissingTargetDuration)?;
+        if let Some(part_target_duration) = part_target_duration {
+            playlist.part_information = Some(PartInformation {
+                part_hold_back_seconds: part_hold_back
+                    .ok_or(ParsePlaylistError::MissingPartHoldBack)?,
+                part_target_duration,
+            });
+        }
+
+        Ok(playlist)
+    }
+}
+
+impl MultivariantPlaylist {
+    /// Parses a `MultivariantPlaylist` from an extended M3U playlist in `input`.
+    ///
+    /// EXT-X-MEDIA tags are collected into [`RenditionGroup`]s by their type and
+    /// GROUP-ID, in the order each group first appears. Comments, EXT-X-VERSION tags,
+    /// and unrecognized tags are ignored.
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` if `input` is not a valid Multivariant Playlist.
+    pub fn parse(input: &[u8]) -> Result<Self, ParsePlaylistError> {
+        let input = std::str::from_utf8(input).map_err(|_| ParsePlaylistError::InvalidUtf8)?;
+        Self::parse_lines(Lines::new(input)?)
+    }
+
+    /// Reads `reader` to the end and parses a `MultivariantPlaylist` from it.
+    /// See [`MultivariantPlaylist::parse`].
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` when encountering an io error on `reader`, or if the
+    /// playlist is not a valid Multivariant Playlist.
+    pub fn from_reader(mut reader: impl io::Read) -> Result<Self, ParsePlaylistError> {
+        let mut input = Vec::new();
+        reader.read_to_end(&mut input)?;
+
+        Self::parse(&input)
+    }
+
+    /// Parses a `MultivariantPlaylist` like [`MultivariantPlaylist::parse`],
+    /// substituting variable references in URI lines, quoted-string attribute
+    /// values, and hexadecimal-sequence attribute values as they are parsed.
+    ///
+    /// `query` is the query component of the playlist's URI, without the
+    /// leading `?`. See [`Variables::resolve`].
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` if `input` is not a valid Multivariant Playlist, or if
+    /// a variable can not be defined or is referenced without being defined.
+    pub fn parse_with_variables(
+        input: &[u8],
+        query: Option<&str>,
+    ) -> Result<Self, ParsePlaylistError> {
+        let input = std::str::from_utf8(input).map_err(|_| ParsePlaylistError::InvalidUtf8)?;
+        Self::parse_lines(Lines::new(input)?.with_variables(&Variables::new(), query))
+    }
+
+    fn parse_lines<'a>(
+        lines: impl Iterator<Item = Result<(usize, Line<'a>), ParsePlaylistError>>,
+    ) -> Result<Self, ParsePlaylistError> {
+        let mut playlist = Self::default();
+
+        for line in lines {
+            let (line_number, line) = line?;
+            let (raw_line, tag) = match line {
+                Line::Uri(_) => {
+                    return Err(ParsePlaylistError::UnexpectedUri { line: line_number })
+                }
+                Line::Tag(raw_line, tag) => (raw_line, tag),
+            };
+
+            match tag {
+                Tag::XVersion { .. } => (),
+                Tag::XDefine(definition) => playlist.variables.push(definition),
+                Tag::XIndependentSegments => playlist.is_independent_segments = true,
+                Tag::XStart {
+                    offset_seconds,
+                    is_precise,
+                } => {
+                    playlist.start_offset = Some(StartOffset {
+                        offset_in_seconds: offset_seconds,
+                        is_precise,
+                    });
+                }
+                Tag::XMedia {
+                    media_type,
+                    group_id,
+                    language,
+                    assoc_language,
+                    name,
+                    stable_rendition_id,
+                    playback_priority,
+                    characteristics,
+                } => {
+                    let info = RenditionInfo {
+                        language,
+                        assoc_language,
+                        name,
+                        priority: playback_priority,
+                        characteristics,
+                        stable_rendition_id,
+                    };
+
+                    playlist.push_rendition(group_id, media_type, info);
+                }
+                Tag::XStreamInf {
+                    stream_inf,
+                    frame_rate,
+                    audio_group_id,
+                    video_group_id,
+                    subtitles_group_id,
+                    closed_captions_group_id,
+                    uri,
+                } => playlist.variant_streams.push(VariantStream {
+                    stream_info: stream_inf,
+                    frame_rate,
+                    audio_group_id,
+                    video_group_id,
+                    subtitles_group_id,
+                    closed_captions_group_id,
+                    uri,
+                }),
+                Tag::XIFrameStreamInf {
+                    stream_inf,
+                    video_group_id,
+                    uri,
+                } => playlist.i_frame_streams.push(IFrameStream {
+                    stream_info: stream_inf,
+                    video_group_id,
+                    uri,
+                }),
+                Tag::XSessionData(data) => playlist.session_data.push(data),
+                Tag::XSessionKey(key) => playlist.session_key.push(key),
+                Tag::XContentSteering(steering) => playlist.content_steering.push(steering),
+
+                _ => {
+                    return Err(ParsePlaylistError::UnexpectedTag {
+                        line: line_number,
+                        name: tag_name(raw_line).into(),
+                    })
+                }
+            }
+        }
+
+        Ok(playlist)
+    }
+
+    /// Adds a rendition to the [`RenditionGroup`] of the same type with `group_id`,
+    /// creating the group if it does not yet exist.
+    fn push_rendition(&mut self, group_id: String, media_type: MediaType, info: RenditionInfo) {
+        let index = self.renditions_groups.iter().position(|group| {
+            matches!(
+                (group, &media_type),
+                (RenditionGroup::Video { group_id: id, .. }, MediaType::Video { .. })
+                    | (RenditionGroup::Audio { group_id: id, .. }, MediaType::Audio { .. })
+                    | (
+                        RenditionGroup::Subtitles { group_id: id, .. },
+                        MediaType::Subtitles { .. }
+                    )
+                    | (
+                        RenditionGroup::ClosedCaptions { group_id: id, .. },
+                        MediaType::ClosedCaptions { .. }
+                    ) if *id == group_id
+            )
+        });
+        let index = index.unwrap_or_else(|| {
+            self.renditions_groups.push(match media_type {
+                MediaType::Video { .. } => RenditionGroup::Video {
+                    group_id,
+                    renditions: vec![],
+                },
+                MediaType::Audio { .. } => RenditionGroup::Audio {
+                    group_id,
+                    renditions: vec![],
+                },
+                MediaType::Subtitles { .. } => RenditionGroup::Subtitles {
+                    group_id,
+                    renditions: vec![],
+                },
+                MediaType::ClosedCaptions { .. } => RenditionGroup::ClosedCaptions {
+                    group_id,
+                    renditions: vec![],
+                },
+            });
+            self.renditions_groups.len() - 1
+        });
+
+        match (&mut self.renditions_groups[index], media_type) {
+            (RenditionGroup::Video { renditions, .. }, MediaType::Video { uri }) => {
+                renditions.push(VideoRendition { info, uri });
+            }
+            (
+                RenditionGroup::Audio { renditions, .. },
+                MediaType::Audio {
+                    uri,
+                    channels,
+                    bit_depth,
+                    sample_rate,
+                },
+            ) => renditions.push(AudioRendition {
+                bit_depth,
+                sample_rate,
+                channels,
+                info,
+                uri,
+            }),
+            (
+                RenditionGroup::Subtitles { renditions, .. },
+                MediaType::Subtitles { uri, forced },
+            ) => renditions.push(SubtitleRendition { info, forced, uri }),
+            (
+                RenditionGroup::ClosedCaptions { renditions, .. },
+                MediaType::ClosedCaptions { in_stream_id },
+            ) => renditions.push(ClosedCaptionRendition { in_stream_id, info }),
+            _ => unreachable!("the group always has the same type as the rendition"),
+        }
+    }
+}
+
+/// The tags of a Media Segment which has not yet seen its URI.
+#[derive(Default)]
+struct SegmentBuilder {
+    duration_seconds: Option<crate::FloatOrInteger>,
+    title: String,
+    byte_range: Option<crate::ByteRange>,
+    is_discontinuity: bool,
+    absolute_time: Option<chrono::DateTime<chrono::FixedOffset>>,
+    is_gap: bool,
+    parts: Vec<PartialSegment>,
+}
+
+impl SegmentBuilder {
+    const fn is_empty(&self) -> bool {
+        self.duration_seconds.is_none()
+            && self.byte_range.is_none()
+            && !self.is_discontinuity
+            && self.absolute_time.is_none()
+            && !self.is_gap
+            && self.parts.is_empty()
+    }
+
+    /// Returns `true` if the segment only has the tags of a segment which is
+    /// still being produced.
+    const fn is_in_progress(&self) -> bool {
+        self.duration_seconds.is_none()
+            && self.byte_range.is_none()
+            && !self.is_gap
+            && !self.parts.is_empty()
+    }
+}
+
+/// A line of a playlist which is relevant to parsing.
+#[allow(clippy::large_enum_variant)]
+enum Line<'a> {
+    /// A recognized tag, along with the line it was parsed from.
+    Tag(&'a str, Tag),
+
+    /// A URI line, after any variables have been substituted.
+    Uri(Cow<'a, str>),
+}
+
+/// An iterator over the relevant lines of a playlist, after the EXTM3U tag.
+/// Blank lines, comments, and unrecognized tags are skipped.
+struct Lines<'a> {
+    lines: std::iter::Peekable<std::iter::Enumerate<std::str::Lines<'a>>>,
+    substitution: Option<Substitution<'a>>,
+}
+
+/// The state needed to define and substitute variables while parsing.
+struct Substitution<'a> {
+    variables: Variables,
+    imports: &'a Variables,
+    query: Option<&'a str>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(input: &'a str) -> Result<Self, ParsePlaylistError> {
+        let mut lines = input.lines().enumerate().peekable();
+        match lines.next() {
+            Some((_, line)) if line.trim_end() == "#EXTM3U" => Ok(Self {
+                lines,
+                substitution: None,
+            }),
+            _ => Err(ParsePlaylistError::MissingHeader),
+        }
+    }
+
+    fn with_variables(mut self, imports: &'a Variables, query: Option<&'a str>) -> Self {
+        self.substitution = Some(Substitution {
+            variables: Variables::new(),
+            imports,
+            query,
+        });
+
+        self
+    }
+
+    fn substitute_uri(
+        &self,
+        line_number: usize,
+        uri: &'a str,
+    ) -> Result<Cow<'a, str>, ParsePlaylistError> {
+        let Some(substitution) = &self.substitution else {
+            return Ok(Cow::Borrowed(uri));
+        };
+
+        substitution
+            .variables
+            .substitute(uri)
+            .map_err(|error| ParsePlaylistError::Variable {
+                line: line_number,
+                error,
+            })
+    }
+
+    fn substitute_tag(
+        &self,
+        line_number: usize,
+        line: &'a str,
+    ) -> Result<Cow<'a, str>, ParsePlaylistError> {
+        let Some(substitution) = &self.substitution else {
+            return Ok(Cow::Borrowed(line));
+        };
+        if line.starts_with("#EXT-X-DEFINE:") {
+            return Ok(Cow::Borrowed(line));
+        }
+
+        substitution
+            .variables
+            .substitute_tag(line)
+            .map_err(|error| ParsePlaylistError::Variable {
+                line: line_number,
+                error,
+            })
+    }
+
+    fn define(&mut self, line_number: usize, tag: &Tag) -> Result<(), ParsePlaylistError> {
+        if let (Some(substitution), Tag::XDefine(definition)) = (&mut self.substitution, tag) {
+            substitution
+                .variables
+                .define(definition, substitution.imports, substitution.query)
+                .map_err(|error| ParsePlaylistError::Variable {
+                    line: line_number,
+                    error,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    fn next_relevant(&mut self) -> Option<(usize, &'a str)> {
+        for (index, line) in self.lines.by_ref() {
+            let line = line.trim_end();
+            if line.is_empty() || (line.starts_with('#') && !line.starts_with("#EXT")) {
+                continue;
+            }
+
+            return Some((index + 1, line));
+        }
+
+        None
+    }
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = Result<(usize, Line<'a>), ParsePlaylistError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (line_number, line) = self.next_relevant()?;
+            if !line.starts_with('#') {
+                return Some(
+                    self.substitute_uri(line_number, line)
+                        .map(|uri| (line_number, Line::Uri(uri))),
+                );
+            }
+
+            let uri = if Tag::is_followed_by_uri(line) {
+                match self.next_relevant() {
+                    Some((uri_line_number, uri)) if !uri.starts_with('#') => {
+                        match self.substitute_uri(uri_line_number, uri) {
+                            Ok(uri) => Some(uri),
+                            Err(error) => return Some(Err(error)),
+                        }
+                    }
+                    _ => {
+                        return Some(Err(ParsePlaylistError::InvalidTag {
+                            line: line_number,
+                            error: ParseTagError::MissingUri,
+                        }))
+                    }
+                }
+            } else {
+                None
+            };
+
+            let substituted_line = match self.substitute_tag(line_number, line) {
+                Ok(substituted_line) => substituted_line,
+                Err(error) => match Tag::parse_line(line, uri.as_deref()) {
+                    Err(ParseTagError::UnknownTag(_)) => continue,
+                    _ => return Some(Err(error)),
+                },
+            };
+
+            return match Tag::parse_line(&substituted_line, uri.as_deref()) {
+                Ok(tag) => Some(
+                    self.define(line_number, &tag)
+                        .map(|()| (line_number, Line::Tag(line, tag))),
+                ),
+                Err(ParseTagError::UnknownTag(_)) => continue,
+                Err(error) => Some(Err(ParsePlaylistError::InvalidTag {
+                    line: line_number,
+                    error,
+                })),
+            };
+        }
+    }
+}
+
+/// Returns `Some(true)` if `tag` may only appear in a Media Playlist,
+/// `Some(false)` if it may only appear in a Multivariant Playlist, and `None`
+/// if it may appear in either.
+const fn is_media_playlist_tag(tag: &Tag) -> Option<bool> {
+    match tag {
+        Tag::M3u
+        | Tag::XVersion { .. }
+        | Tag::XDefine(_)
+        | Tag::XStart { .. }
+        | Tag::XIndependentSegments => None,
+        Tag::XMedia { .. }
+        | Tag::XStreamInf { .. }
+        | Tag::XIFrameStreamInf { .. }
+        | Tag::XSessionData(_)
+        | Tag::XSessionKey(_)
+        | Tag::XContentSteering(_) => Some(false),
+        _ => Some(true),
+    }
+}
+
+fn tag_name(line: &str) -> &str {
+    line.split_once(':').map_or(line, |(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{EncryptionMethod, FloatOrInteger, KeyFormat};
+
+    use super::*;
+
+    #[test]
+    fn parse_media_playlist() {
+        let playlist = MediaPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-VERSION:6
+#EXT-X-TARGETDURATION:6
+# A comment which should be ignored.
+#EXT-X-MEDIA-SEQUENCE:42
+#EXT-X-PART-INF:PART-TARGET=2
+#EXT-X-SERVER-CONTROL:HOLD-BACK=18,PART-HOLD-BACK=6,CAN-BLOCK-RELOAD=YES
+#EXT-X-SOMETHING-NEW:FOO=BAR
+#EXT-X-KEY:METHOD=AES-128,URI=\"key1.key\"
+#EXT-X-MAP:URI=\"init.mp4\"
+#EXT-X-BITRATE:8000
+#EXTINF:6,first
+1.mp4
+
+#EXT-X-BYTERANGE:400@0
+#EXTINF:5.5
+2.mp4
+#EXT-X-DISCONTINUITY
+#EXT-X-KEY:METHOD=NONE
+#EXT-X-PART:URI=\"3.0.mp4\",DURATION=2,INDEPENDENT=YES
+#EXT-X-PART:URI=\"3.1.mp4\",DURATION=2
+#EXTINF:4
+3.mp4
+#EXT-X-PROGRAM-DATE-TIME:2024-01-01T00:00:00Z
+#EXT-X-PART:URI=\"4.0.mp4\",DURATION=2,INDEPENDENT=YES
+#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"4.1.mp4\"
+",
+        )
+        .unwrap();
+
+        assert_eq!(playlist.target_duration, 6);
+        assert_eq!(playlist.first_media_sequence_number, 42);
+        assert_eq!(
+            playlist.part_information,
+            Some(PartInformation {
+                part_hold_back_seconds: 6.0,
+                part_target_duration: 2.0,
+            })
+        );
+        assert_eq!(playlist.hold_back_seconds, Some(18.0));
+        assert!(playlist.supports_blocking_playlist_reloads);
+        assert_eq!(playlist.metadata.preload_hints.len(), 1);
+        assert_eq!(playlist.segments.len(), 3);
+
+        let key = Some(EncryptionMethod::Aes128 {
+            uri: "key1.key".into(),
+            iv: None,
+            key_format: KeyFormat::Identity,
+            key_format_versions: vec![],
+        });
+        let map = Some(MediaInitializationSection {
+            uri: "init.mp4".into(),
+            range: None,
+        });
+
+        let [first, second, third] = &playlist.segments[..] else {
+            unreachable!();
+        };
+        assert_eq!(first.uri, "1.mp4");
+        assert_eq!(first.title, "first");
+        assert_eq!(first.duration_seconds, FloatOrInteger::Integer(6));
+        assert_eq!(
+            first.byte_range_or_bitrate,
+            Some(ByteRangeOrBitrate::Bitrate(8000))
+        );
+        assert_eq!(first.encryption, key);
+        assert_eq!(first.media_initialization_section, map);
+
+        assert_eq!(second.duration_seconds, FloatOrInteger::Float(5.5));
+        assert_eq!(
+            second.byte_range_or_bitrate,
+            Some(ByteRangeOrBitrate::ByteRange(crate::ByteRange {
+                length_bytes: 400,
+                start_offset_bytes: Some(0),
+            }))
+        );
+        assert_eq!(second.encryption, key);
+        assert!(!second.is_discontinuity);
+
+        assert!(third.is_discontinuity);
+        assert_eq!(third.encryption, None);
+        assert_eq!(third.media_initialization_section, map);
+        assert_eq!(
+            third.byte_range_or_bitrate,
+            Some(ByteRangeOrBitrate::Bitrate(8000))
+        );
+        assert_eq!(third.parts.len(), 2);
+        assert!(third.parts[0].is_independent);
+        assert_eq!(third.parts[1].uri, "3.1.mp4");
+
+        let in_progress = playlist.in_progress_segment.unwrap();
+        assert!(!in_progress.is_discontinuity);
+        assert_eq!(in_progress.encryption, None);
+        assert_eq!(in_progress.media_initialization_section, map);
+        assert!(in_progress.absolute_time.is_some());
+        assert_eq!(in_progress.parts.len(), 1);
+        assert_eq!(in_progress.parts[0].uri, "4.0.mp4");
+    }
+
+    #[test]
+    fn round_trip_media_playlist() {
+        let playlist = MediaPlaylist {
+            segments: vec![
+                MediaSegment {
+                    uri: "https://example.com/1.mp4".into(),
+                    duration_seconds: FloatOrInteger::Float(5.045),
+                    title: "This is the first thingy!".into(),
+                    byte_range_or_bitrate: Some(ByteRangeOrBitrate::Bitrate(8000)),
+                    is_discontinuity: false,
+                    encryption: Some(EncryptionMethod::Aes128 {
+                        uri: "https://example.com/key.key".into(),
+                        iv: Some(0x0F91_DC05),
+                        key_format: KeyFormat::Identity,
+                        key_format_versions: vec![1, 7, 6],
+                    }),
+                    media_initialization_section: Some(MediaInitializationSection {
+                        uri: "https://example.com/1.mp4".into(),
+                        range: Some(crate::ByteRangeWithOffset {
+                            length_bytes: 400,
+                            start_offset_bytes: 0,
+                        }),
+                    }),
+                    absolute_time: Some(
+                        chrono::DateTime::parse_from_rfc3339("2010-02-19T14:54:23.031+08:00")
+                            .unwrap(),
+                    ),
+                    is_gap: false,
+                    parts: vec![PartialSegment {
+                        uri: "https://example.com/1.mp4".into(),
+                        duration_in_seconds: 5.045 / 2.0,
+                        is_independent: true,
+                        byte_range: Some(crate::ByteRange {
+                            length_bytes: 400,
+                            start_offset_bytes: None,
+                        }),
+                        is_gap: false,
+                    }],
+                },
+                MediaSegment {
+                    uri: "https://example.com/2.mp4".into(),
+                    duration_seconds: FloatOrInteger::Float(5.045),
+                    title: String::new(),
+                    byte_range_or_bitrate: Some(ByteRangeOrBitrate::Bitrate(5000)),
+                    is_discontinuity: true,
+                    encryption: None,
+                    media_initialization_section: Some(MediaInitializationSection {
+                        uri: "https://example.com/1.mp4".into(),
+                        range: Some(crate::ByteRangeWithOffset {
+                            length_bytes: 400,
+                            start_offset_bytes: 0,
+                        }),
+                    }),
+                    absolute_time: None,
+                    is_gap: true,
+                    parts: vec![],
+                },
+            ],
+            variables: vec![crate::DefinitionType::Inline {
+                name: "cool".into(),
+                value: "foo".into(),
+            }],
+            target_duration: 5,
+            first_media_sequence_number: 3,
+            discontinuity_sequence_number: 12,
+            playlist_type: Some(crate::PlaylistType::Event),
+            part_information: Some(PartInformation {
+                part_hold_back_seconds: 9.0,
+                part_target_duration: 3.0,
+            }),
+            playlist_delta_updates_information: Some(crate::DeltaUpdateInfo {
+                skip_boundary_seconds: 18.0,
+                can_skip_dateranges: true,
+            }),
+            supports_blocking_playlist_reloads: true,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        playlist.serialize(&mut output).unwrap();
+        assert_eq!(MediaPlaylist::parse(&output).unwrap(), playlist);
+        assert_eq!(
+            MediaPlaylist::from_reader(output.as_slice()).unwrap(),
+            playlist
+        );
+    }
+
+    #[test]
+    fn round_trip_in_progress_segment_bitrate() {
+        let playlist = MediaPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-TARGETDURATION:2
+#EXTINF:2
+a.ts
+#EXT-X-BITRATE:2000
+#EXT-X-PART:DURATION=1,URI=\"b.0.ts\"
+",
+        )
+        .unwrap();
+        assert_eq!(
+            playlist.in_progress_segment.as_ref().unwrap().bitrate,
+            Some(2000)
+        );
+
+        let mut output = Vec::new();
+        playlist.serialize(&mut output).unwrap();
+        assert!(String::from_utf8_lossy(&output).contains("#EXT-X-BITRATE:2000\n#EXT-X-PART:"));
+        assert_eq!(MediaPlaylist::parse(&output).unwrap(), playlist);
+    }
+
+    #[test]
+    fn parse_media_playlist_errors() {
+        assert!(matches!(
+            MediaPlaylist::parse(b"#EXT-X-TARGETDURATION:6\n"),
+            Err(ParsePlaylistError::MissingHeader)
+        ));
+        assert!(matches!(
+            MediaPlaylist::parse(b"#EXTM3U\n#EXTINF:6\n1.mp4\n"),
+            Err(ParsePlaylistError::MissingTargetDuration)
+        ));
+        assert!(matches!(
+            MediaPlaylist::parse(b"#EXTM3U\n#EXT-X-TARGETDURATION:6\n1.mp4\n"),
+            Err(ParsePlaylistError::MissingSegmentDuration { line: 3 })
+        ));
+        assert!(matches!(
+            MediaPlaylist::parse(b"#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6\n"),
+            Err(ParsePlaylistError::MissingSegmentUri)
+        ));
+        assert!(matches!(
+            MediaPlaylist::parse(
+                b"#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6\n#EXT-X-PART:DURATION=2,URI=\"1.0.mp4\"\n"
+            ),
+            Err(ParsePlaylistError::MissingSegmentUri)
+        ));
+        assert!(matches!(
+            MediaPlaylist::parse(
+                b"#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXT-X-SERVER-CONTROL:CAN-BLOCK-RELOAD=YES\n#EXT-X-PART-INF:PART-TARGET=2\n"
+            ),
+            Err(ParsePlaylistError::MissingPartHoldBack)
+        ));
+        assert!(matches!(
+            MediaPlaylist::parse(b"#EXTM3U\n#EXT-X-TARGETDURATION:six\n"),
+            Err(ParsePlaylistError::InvalidTag {
+                line: 2,
+                error: ParseTagError::InvalidValue(_)
+            })
+        ));
+        assert!(matches!(
+            MediaPlaylist::parse(b"#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXT-X-STREAM-INF:BANDWIDTH=1\n1.m3u8\n"),
+            Err(ParsePlaylistError::UnexpectedTag { line: 3, name }) if name == "#EXT-X-STREAM-INF"
+        ));
+    }
+
+    #[test]
+    fn parse_multivariant_playlist() {
+        let playlist = MultivariantPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-INDEPENDENT-SEGMENTS
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",DEFAULT=YES,URI=\"en.m3u8\"
+#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"English\",URI=\"subs.m3u8\"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"Deutsch\",AUTOSELECT=YES,URI=\"de.m3u8\"
+#EXT-X-MEDIA:TYPE=VIDEO,GROUP-ID=\"aac\",NAME=\"Angle\"
+
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,AUDIO=\"aac\",SUBTITLES=\"subs\"
+# Comments may appear between a tag and its URI.
+low.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=2560000,AUDIO=\"aac\"
+high.m3u8
+#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=86000,URI=\"low-iframe.m3u8\"
+#EXT-X-SESSION-DATA:DATA-ID=\"com.example.title\",VALUE=\"Example\"
+#EXT-X-CONTENT-STEERING:SERVER-URI=\"/steering\",PATHWAY-ID=\"CDN-A\"
+",
+        )
+        .unwrap();
+
+        assert!(playlist.is_independent_segments);
+        assert_eq!(playlist.renditions_groups.len(), 3);
+        let RenditionGroup::Audio {
+            group_id,
+            renditions,
+        } = &playlist.renditions_groups[0]
+        else {
+            panic!("expected an audio group first");
+        };
+        assert_eq!(group_id, "aac");
+        assert_eq!(renditions.len(), 2);
+        assert_eq!(renditions[1].info.name, "Deutsch");
+        assert_eq!(
+            renditions[1].info.priority,
+            crate::RenditionPlaybackPriority::AutoSelect
+        );
+        assert!(matches!(
+            &playlist.renditions_groups[1],
+            RenditionGroup::Subtitles { renditions, .. } if renditions.len() == 1
+        ));
+        assert!(matches!(
+            &playlist.renditions_groups[2],
+            RenditionGroup::Video { group_id, .. } if group_id == "aac"
+        ));
+
+        assert_eq!(playlist.variant_streams.len(), 2);
+        assert_eq!(playlist.variant_streams[0].uri, "low.m3u8");
+        assert_eq!(
+            playlist.variant_streams[0].subtitles_group_id.as_deref(),
+            Some("subs")
+        );
+        assert_eq!(playlist.variant_streams[1].uri, "high.m3u8");
+        assert_eq!(playlist.i_frame_streams[0].uri, "low-iframe.m3u8");
+        assert_eq!(playlist.session_data[0].data_id, "com.example.title");
+        assert_eq!(
+            playlist.content_steering[0].pathway_id.as_deref(),
+            Some("CDN-A")
+        );
+    }
+
+    #[test]
+    fn round_trip_multivariant_playlist() {
+        let playlist = MultivariantPlaylist {
+            is_independent_segments: true,
+            start_offset: Some(StartOffset {
+                offset_in_seconds: 2.0,
+                is_precise: true,
+            }),
+            variables: vec![crate::DefinitionType::QueryParameter {
+                name: "token".into(),
+            }],
+            renditions_groups: vec![
+                RenditionGroup::Video {
+                    group_id: "cool_video".into(),
+                    renditions: vec![VideoRendition {
+                        info: RenditionInfo {
+                            language: Some("en_US".into()),
+                            assoc_language: Some("de".into()),
+                            name: "English".into(),
+                            priority: crate::RenditionPlaybackPriority::Default,
+                            characteristics: vec!["private.cool.example".into()],
+                            stable_rendition_id: Some("very_stable".into()),
+                        },
+                        uri: Some("https://example.com/video.m3u8".into()),
+                    }],
+                },
+                RenditionGroup::ClosedCaptions {
+                    group_id: "cool_captions".into(),
+                    renditions: vec![ClosedCaptionRendition {
+                        info: RenditionInfo {
+                            language: None,
+                            assoc_language: None,
+                            name: "somethin".into(),
+                            priority: crate::RenditionPlaybackPriority::None,
+                            characteristics: vec![],
+                            stable_rendition_id: None,
+                        },
+                        in_stream_id: crate::InStreamId::Cc1,
+                    }],
+                },
+            ],
+            variant_streams: vec![VariantStream {
+                stream_info: crate::StreamInf {
+                    bandwidth_bits_per_second: 8024,
+                    average_bandwidth_bits_per_second: Some(8000),
+                    score: Some(2.0),
+                    codecs: vec!["mp4a.40.2".into(), "avc1.4d401e".into()],
+                    supplemental_codecs: vec![],
+                    resolution: Some(crate::Resolution {
+                        width: 1080,
+                        height: 1920,
+                    }),
+                    hdcp_level: Some(crate::HdcpLevel::Type1),
+                    allowed_cpc: vec![],
+                    video_range: crate::VideoRange::Pq,
+                    required_video_layout: vec![crate::VideoChannelSpecifier::Stereo],
+                    stable_variant_id: Some("azBY09+/=.-_".into()),
+                    pathway_id: Some("cool-pathway".into()),
+                },
+                frame_rate: Some(60.0),
+                audio_group_id: None,
+                video_group_id: Some("cool_video".into()),
+                subtitles_group_id: None,
+                closed_captions_group_id: Some("cool_captions".into()),
+                uri: "https://example.com/stuffs.m3u8".into(),
+            }],
+            session_key: vec![EncryptionMethod::SampleAes {
+                uri: "https://example.com/key.key".into(),
+                iv: None,
+                key_format_versions: vec![],
+            }],
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        playlist.serialize(&mut output).unwrap();
+        assert_eq!(MultivariantPlaylist::parse(&output).unwrap(), playlist);
+    }
+
+    #[test]
+    fn parse_multivariant_playlist_errors() {
+        assert!(matches!(
+            MultivariantPlaylist::parse(b"#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\n"),
+            Err(ParsePlaylistError::InvalidTag {
+                line: 2,
+                error: ParseTagError::MissingUri
+            })
+        ));
+        assert!(matches!(
+            MultivariantPlaylist::parse(b"#EXTM3U\nlow.m3u8\n"),
+            Err(ParsePlaylistError::UnexpectedUri { line: 2 })
+        ));
+        assert!(matches!(
+            MultivariantPlaylist::parse(b"#EXTM3U\n#EXTINF:6\n1.mp4\n"),
+            Err(ParsePlaylistError::UnexpectedTag { line: 2, name }) if name == "#EXTINF"
+        ));
+    }
+
+    #[test]
+    fn parse_playlist() {
+        assert!(matches!(
+            Playlist::parse(b"#EXTM3U\n#EXT-X-STREAM-INF:BANDWIDTH=1\nlow.m3u8\n"),
+            Ok(Playlist::Multivariant(playlist)) if playlist.variant_streams.len() == 1
+        ));
+        assert!(matches!(
+            Playlist::parse(b"#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:6\n#EXTINF:6,\n1.ts\n"),
+            Ok(Playlist::Media(playlist)) if playlist.segments.len() == 1
+        ));
+        assert!(matches!(
+            Playlist::parse(b"#EXTM3U\n#EXT-X-INDEPENDENT-SEGMENTS\n"),
+            Ok(Playlist::Multivariant(_))
+        ));
+        assert!(matches!(
+            Playlist::parse(
+                b"#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXT-X-STREAM-INF:BANDWIDTH=1\nlow.m3u8\n"
+            ),
+            Err(ParsePlaylistError::MixedPlaylist { line: 3 })
+        ));
+        assert!(matches!(
+            Playlist::parse(b"#EXTM3U\n#EXT-X-SESSION-KEY:METHOD=AES-128,URI=\"k\"\n1.ts\n"),
+            Err(ParsePlaylistError::MixedPlaylist { line: 3 })
+        ));
+
+        let playlist = Playlist::Media(MediaPlaylist {
+            target_duration: 6,
+            segments: vec![MediaSegment {
+                uri: "1.ts".into(),
+                duration_seconds: FloatOrInteger::Integer(6),
+                title: String::new(),
+                byte_range_or_bitrate: None,
+                is_discontinuity: false,
+                encryption: None,
+                media_initialization_section: None,
+                absolute_time: None,
+                is_gap: false,
+                parts: vec![],
+            }],
+            ..Default::default()
+        });
+        let mut output = Vec::new();
+        playlist.serialize(&mut output).unwrap();
+        assert_eq!(Playlist::parse(&output).unwrap(), playlist);
+    }
+
+    #[test]
+    fn parse_with_variables() {
+        let multivariant = MultivariantPlaylist::parse_with_variables(
+            br#"#EXTM3U
+#EXT-X-DEFINE:QUERYPARAM="token"
+#EXT-X-DEFINE:NAME="cdn",VALUE="https://cdn.example.com"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,PATHWAY-ID="{$token}"
+{$cdn}/low.m3u8?token={$token}
+"#,
+            Some("token=abc"),
+        )
+        .unwrap();
+        assert_eq!(
+            multivariant.variant_streams[0].uri,
+            "https://cdn.example.com/low.m3u8?token=abc"
+        );
+        assert_eq!(
+            multivariant.variant_streams[0].stream_info.pathway_id,
+            Some("abc".into())
+        );
+
+        let imports = Variables::resolve(
+            &multivariant.variables,
+            &Variables::new(),
+            Some("token=abc"),
+        )
+        .unwrap();
+        let playlist = MediaPlaylist::parse_with_variables(
+            br#"#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXT-X-DEFINE:IMPORT="token"
+#EXT-X-DEFINE:NAME="cmd",VALUE="FC30"
+#EXT-X-DATERANGE:ID="ad-{$token}",START-DATE="2010-02-19T14:54:23.031+08:00",SCTE35-CMD=0x{$cmd}
+#EXTINF:6,{$token}
+1.ts?token={$token}
+"#,
+            &imports,
+            None,
+        )
+        .unwrap();
+        assert_eq!(playlist.segments[0].uri, "1.ts?token=abc");
+        assert_eq!(playlist.segments[0].title, "{$token}");
+        assert_eq!(playlist.metadata.date_ranges[0].id, "ad-abc");
+        assert_eq!(
+            playlist.metadata.date_ranges[0].scte35_cmd,
+            vec![0xFC, 0x30]
+        );
+
+        assert!(matches!(
+            MediaPlaylist::parse_with_variables(
+                b"#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6,\n{$missing}.ts\n",
+                &imports,
+                None,
+            ),
+            Err(ParsePlaylistError::Variable {
+                line: 4,
+                error: VariableError::Undefined(_)
+            })
+        ));
+        assert!(matches!(
+            MediaPlaylist::parse_with_variables(
+                b"#EXTM3U\n#EXT-X-DEFINE:IMPORT=\"other\"\n",
+                &imports,
+                None,
+            ),
+            Err(ParsePlaylistError::Variable {
+                line: 2,
+                error: VariableError::ImportNotFound(_)
+            })
+        ));
+
+        assert!(matches!(
+            MediaPlaylist::parse_with_variables(
+                b"#EXTM3U\n#EXT-X-DEFINE:QUERYPARAM=\"t\"\n#EXT-X-MAP:URI=\"{$t}\"\n",
+                &Variables::new(),
+                Some("t=init.mp4%22,BYTERANGE=%22100@0"),
+            ),
+            Err(ParsePlaylistError::Variable {
+                line: 3,
+                error: VariableError::InvalidValue(_)
+            })
+        ));
+
+        let playlist =
+            MediaPlaylist::parse(b"#EXTM3U\n#EXT-X-TARGETDURATION:6\n#EXTINF:6,\n{$missing}.ts\n")
+                .unwrap();
+        assert_eq!(playlist.segments[0].uri, "{$missing}.ts");
+    }
+}
@@ -13,15 +13,176 @@
 // limitations under the License.
 
 use super::{
-    ByteRangeOrBitrate, IFrameStream, MediaMetadata, MediaPlaylist, MediaSegment,
-    MultivariantPlaylist, RenditionGroup, VariantStream,
+    ByteRangeOrBitrate, IFrameStream, InProgressSegment, MediaInitializationSection, MediaMetadata,
+    MediaPlaylist, MediaSegment, MultivariantPlaylist, PartialSegment, Playlist, RenditionGroup,
+    VariantStream,
 };
-use crate::tags::Tag;
-use std::{cmp::max, io};
+use super::{MediaPlaylistViolation, MultivariantPlaylistViolation, VersionFeature};
+use crate::tags::{validate_uri_line, MediaTypeRef, TagRef, TagViolation};
+use crate::{variables, SerializeError};
+use std::{fmt, io};
+
+/// Something the tags and URI lines of a playlist can be written to.
+trait PlaylistWriter {
+    fn write_tag(&mut self, tag: TagRef<'_>) -> fmt::Result;
+    fn write_uri(&mut self, uri: &str) -> fmt::Result;
+}
+
+impl<W: fmt::Write> PlaylistWriter for W {
+    fn write_tag(&mut self, tag: TagRef<'_>) -> fmt::Result {
+        tag.write_to(self)
+    }
+
+    fn write_uri(&mut self, uri: &str) -> fmt::Result {
+        writeln!(self, "{uri}")
+    }
+}
+
+/// Writes the EXTM3U tag, and the EXT-X-VERSION tag unless `version` is 1.
+fn write_header(version: u8, output: &mut impl PlaylistWriter) -> fmt::Result {
+    output.write_tag(TagRef::M3u)?;
+    if version != 1 {
+        output.write_tag(TagRef::XVersion { version })?;
+    }
+
+    Ok(())
+}
+
+/// Stands in for the segment before the first one, so that the first segment
+/// writes every EXT-X-KEY, EXT-X-MAP and EXT-X-BITRATE tag it has.
+const NO_MEDIA_SEGMENT: MediaSegment = MediaSegment {
+    uri: String::new(),
+    duration_seconds: crate::FloatOrInteger::Integer(0),
+    title: String::new(),
+    byte_range_or_bitrate: None,
+    is_discontinuity: false,
+    encryption: None,
+    media_initialization_section: None,
+    absolute_time: None,
+    is_gap: false,
+    parts: vec![],
+};
+
+/// Collects the violations in every tag and URI line instead of writing them.
+#[derive(Default)]
+struct TagValidator(Vec<TagViolation>);
+
+impl PlaylistWriter for TagValidator {
+    fn write_tag(&mut self, tag: TagRef<'_>) -> fmt::Result {
+        tag.validate_into(&mut self.0);
+
+        Ok(())
+    }
+
+    fn write_uri(&mut self, uri: &str) -> fmt::Result {
+        validate_uri_line(uri, &mut self.0);
+
+        Ok(())
+    }
+}
+
+/// Records whether any tag or URI line references a variable.
+#[derive(Default)]
+struct ReferenceScanner {
+    found: bool,
+
+    /// Reused to format each tag which might contain a reference.
+    line: String,
+}
+
+impl PlaylistWriter for ReferenceScanner {
+    fn write_tag(&mut self, tag: TagRef<'_>) -> fmt::Result {
+        if self.found || matches!(tag, TagRef::XDefine(_)) {
+            return Ok(());
+        }
+
+        // Most tags contain no reference at all, so they are only buffered
+        // if they might.
+        let mut opening = ReferenceOpening::default();
+        tag.write_to(&mut opening)?;
+        if !opening.found {
+            return Ok(());
+        }
+
+        self.line.clear();
+        tag.write_to(&mut self.line)?;
+        let mut lines = self.line.lines();
+        // Only EXT-X-STREAM-INF spans more than one line, and the rest is
+        // its URI.
+        self.found = lines.next().is_some_and(variables::tag_has_reference)
+            || lines.any(variables::has_reference);
+
+        Ok(())
+    }
+
+    fn write_uri(&mut self, uri: &str) -> fmt::Result {
+        self.found |= variables::has_reference(uri);
+
+        Ok(())
+    }
+}
+
+/// Records whether anything written contains `{$`, which begins every
+/// variable reference.
+#[derive(Default)]
+struct ReferenceOpening {
+    found: bool,
+    ends_with_brace: bool,
+}
+
+impl fmt::Write for ReferenceOpening {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.found |= (self.ends_with_brace && s.starts_with('$')) || s.contains("{$");
+        self.ends_with_brace = s.ends_with('{');
+
+        Ok(())
+    }
+}
+
+/// Formats the playlist exactly as [`Playlist::serialize`] writes it.
+impl fmt::Display for Playlist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Multivariant(playlist) => playlist.write(f),
+            Self::Media(playlist) => playlist.write(f),
+        }
+    }
+}
+
+/// Formats the playlist exactly as [`MediaPlaylist::serialize`] writes it.
+impl fmt::Display for MediaPlaylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f)
+    }
+}
+
+/// Formats the playlist exactly as [`MultivariantPlaylist::serialize`] writes
+/// it.
+impl fmt::Display for MultivariantPlaylist {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f)
+    }
+}
+
+impl Playlist {
+    /// Serializes the `Playlist` as a extended M3U playlist into `output`.
+    /// See [`MultivariantPlaylist::serialize`] and [`MediaPlaylist::serialize`].
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` when encountering an io error on `output`.
+    pub fn serialize(&self, output: impl io::Write) -> io::Result<()> {
+        match self {
+            Self::Multivariant(playlist) => playlist.serialize(output),
+            Self::Media(playlist) => playlist.serialize(output),
+        }
+    }
+}
 
 impl MediaPlaylist {
     /// Serializes the `MediaPlaylist` as a extended M3U playlist into `output`.
-    /// Guaranteed to write valid UTF-8 only.
+    /// Guaranteed to write valid UTF-8 only. To serialize into a `String`,
+    /// use the [`fmt::Display`] implementation instead.
     ///
     /// This method makes lots of small calls to write on `output`. If the implementation
     /// of write on `output` makes a syscall, like with a `TcpStream`, you should wrap it
@@ -30,210 +191,232 @@ impl MediaPlaylist {
     /// # Note
     ///
     /// This method is not guaranteed to write a valid M3U playlist. It's your job to create
-    /// valid input.
+    /// valid input, which can be checked with [`MediaPlaylist::validate`].
     ///
     /// # Errors
     ///
     /// May return `Err` when encountering an io error on `output`.
-    pub fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
-        Tag::M3u.serialize(&mut output)?;
-        let version = self.get_version();
-        if version != 1 {
-            Tag::XVersion {
-                version: self.get_version(),
-            }
-            .serialize(&mut output)?;
+    pub fn serialize(&self, output: impl io::Write) -> io::Result<()> {
+        crate::write_io(output, |output| self.write(output))
+    }
+
+    /// Serializes the `MediaPlaylist` like [`MediaPlaylist::serialize`], but
+    /// into an async `output`.
+    ///
+    /// The playlist is formatted one segment at a time into a small buffer,
+    /// which is reused, so `output` receives writes of a few KiB each.
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` when encountering an io error on `output`.
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+    pub async fn serialize_async(
+        &self,
+        output: impl tokio::io::AsyncWrite + Unpin,
+    ) -> io::Result<()> {
+        let mut output = crate::AsyncWriter::new(output);
+        let version = self.required_version();
+        output
+            .write(|buffer| {
+                write_header(version, buffer)?;
+                self.write_head(buffer)
+            })
+            .await?;
+
+        let mut last_media_segment = &NO_MEDIA_SEGMENT;
+        for segment in &self.segments {
+            output
+                .write(|buffer| segment.write(last_media_segment, buffer))
+                .await?;
+            last_media_segment = segment;
+        }
+        if let Some(segment) = &self.in_progress_segment {
+            output
+                .write(|buffer| segment.write(last_media_segment, buffer))
+                .await?;
+        }
+
+        output.finish().await
+    }
+
+    /// Serializes the `MediaPlaylist` like [`MediaPlaylist::serialize`], but
+    /// only if it passes [`MediaPlaylist::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializeError::Invalid`] without writing anything if the
+    /// playlist is invalid, and [`SerializeError::Io`] when encountering an io
+    /// error on `output`.
+    pub fn serialize_strict(
+        &self,
+        output: impl io::Write,
+    ) -> Result<(), SerializeError<MediaPlaylistViolation>> {
+        self.validate().map_err(SerializeError::Invalid)?;
+        crate::write_io(output, |output| self.write(output))?;
+
+        Ok(())
+    }
+
+    /// Serializes the `MediaPlaylist` like [`MediaPlaylist::serialize`], but only if
+    /// [`MediaPlaylist::required_version`] is at most `max_version`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializeError::Invalid`] with every feature which requires a
+    /// version greater than `max_version` without writing anything, and
+    /// [`SerializeError::Io`] when encountering an io error on `output`.
+    pub fn serialize_with_max_version(
+        &self,
+        max_version: u8,
+        output: impl io::Write,
+    ) -> Result<(), SerializeError<VersionFeature>> {
+        let features = self.version_features();
+        let version = features
+            .last()
+            .map_or(1, |feature| feature.required_version());
+        if version > max_version {
+            return Err(SerializeError::Invalid(
+                features
+                    .into_iter()
+                    .filter(|feature| feature.required_version() > max_version)
+                    .collect(),
+            ));
+        }
+        crate::write_io(output, |output| self.write_with_version(version, output))?;
+
+        Ok(())
+    }
+
+    /// Returns every [`TagViolation`] in the tags and URI lines which would be
+    /// written for this playlist.
+    pub(super) fn tag_violations(&self) -> Vec<TagViolation> {
+        // EXTM3U and EXT-X-VERSION are always valid, so only the body is
+        // checked, which spares scanning the playlist for its version.
+        let mut validator = TagValidator::default();
+        self.write_body(&mut validator)
+            .expect("validating tags should never fail");
+
+        validator.0
+    }
+
+    fn write(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
+        self.write_with_version(self.required_version(), output)
+    }
+
+    /// Writes the playlist with its already computed
+    /// [`required_version`](Self::required_version).
+    fn write_with_version(&self, version: u8, output: &mut impl PlaylistWriter) -> fmt::Result {
+        write_header(version, output)?;
+        self.write_body(output)
+    }
+
+    /// Writes every tag and URI line after the EXT-X-VERSION tag.
+    fn write_body(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
+        self.write_head(output)?;
+
+        let mut last_media_segment = &NO_MEDIA_SEGMENT;
+        for segment in &self.segments {
+            segment.write(last_media_segment, output)?;
+            last_media_segment = segment;
+        }
+        if let Some(segment) = &self.in_progress_segment {
+            segment.write(last_media_segment, output)?;
         }
 
+        Ok(())
+    }
+
+    /// Writes every tag between the EXT-X-VERSION tag and the first segment.
+    fn write_head(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
         for variable in &self.variables {
-            Tag::XDefine(variable.clone()).serialize(&mut output)?;
+            output.write_tag(TagRef::XDefine(variable))?;
         }
         if self.is_independent_segments {
-            Tag::XIndependentSegments.serialize(&mut output)?;
+            output.write_tag(TagRef::XIndependentSegments)?;
         }
         if let Some(offset) = &self.start_offset {
-            Tag::XStart {
+            output.write_tag(TagRef::XStart {
                 offset_seconds: offset.offset_in_seconds,
                 is_precise: offset.is_precise,
-            }
-            .serialize(&mut output)?;
+            })?;
         }
 
-        Tag::XTargetDuration {
+        output.write_tag(TagRef::XTargetDuration {
             target_duration_seconds: self.target_duration,
-        }
-        .serialize(&mut output)?;
+        })?;
         if self.first_media_sequence_number != 0 {
-            Tag::XMediaSequence {
+            output.write_tag(TagRef::XMediaSequence {
                 sequence_number: self.first_media_sequence_number,
-            }
-            .serialize(&mut output)?;
+            })?;
         }
         if self.discontinuity_sequence_number != 0 {
-            Tag::XDiscontinuitySequence {
+            output.write_tag(TagRef::XDiscontinuitySequence {
                 sequence_number: self.discontinuity_sequence_number,
-            }
-            .serialize(&mut output)?;
+            })?;
         }
         if self.finished {
-            Tag::XEndList.serialize(&mut output)?;
+            output.write_tag(TagRef::XEndList)?;
         }
         if let Some(playlist_type) = &self.playlist_type {
-            Tag::XPlaylistType(playlist_type.clone()).serialize(&mut output)?;
+            output.write_tag(TagRef::XPlaylistType(playlist_type))?;
         }
         if self.iframes_only {
-            Tag::XIFramesOnly.serialize(&mut output)?;
+            output.write_tag(TagRef::XIFramesOnly)?;
         }
         if let Some(part_information) = &self.part_information {
-            Tag::XPartInf {
+            output.write_tag(TagRef::XPartInf {
                 part_target_duration_seconds: part_information.part_target_duration,
-            }
-            .serialize(&mut output)?;
+            })?;
         }
         if self.playlist_delta_updates_information.is_some()
             || self.hold_back_seconds.is_some()
             || self.part_information.is_some()
             || self.supports_blocking_playlist_reloads
         {
-            Tag::XServerControl {
-                delta_update_info: self.playlist_delta_updates_information.clone(),
+            output.write_tag(TagRef::XServerControl {
+                delta_update_info: self.playlist_delta_updates_information.as_ref(),
                 hold_back: self.hold_back_seconds,
                 part_hold_back: self
                     .part_information
-                    .clone()
+                    .as_ref()
                     .map(|info| info.part_hold_back_seconds),
                 can_block_reload: self.supports_blocking_playlist_reloads,
-            }
-            .serialize(&mut output)?;
-        }
-
-        self.metadata.serialize(&mut output)?;
-
-        let mut last_media_segment = &MediaSegment {
-            uri: String::new(),
-            duration_seconds: crate::FloatOrInteger::Integer(0),
-            title: String::new(),
-            byte_range_or_bitrate: None,
-            is_discontinuity: false,
-            encryption: None,
-            media_initialization_section: None,
-            absolute_time: None,
-            is_gap: false,
-            parts: vec![],
-        };
-        for segment in &self.segments {
-            segment.serialize(last_media_segment, &mut output)?;
-            last_media_segment = segment;
+            })?;
         }
 
-        Ok(())
+        self.metadata.write(output)
     }
 
-    fn get_version(&self) -> u8 {
-        let mut version = 1;
-
-        let mut has_map = false;
-        for segment in &self.segments {
-            if let Some(method) = &segment.encryption {
-                if let crate::EncryptionMethod::Aes128 { iv, key_format, .. } = method {
-                    if iv.is_some() {
-                        version = max(version, 2);
-                    }
-
-                    if let crate::KeyFormat::Other(_) = key_format {
-                        version = 5;
-                    }
-                } else if let crate::EncryptionMethod::SampleAes { .. } = method {
-                    version = 5;
-                }
-
-                let (crate::EncryptionMethod::Aes128 {
-                    key_format_versions,
-                    ..
-                }
-                | crate::EncryptionMethod::SampleAes {
-                    key_format_versions,
-                    ..
-                }
-                | crate::EncryptionMethod::SampleAesCtr {
-                    key_format_versions,
-                    ..
-                }) = method;
-                for key_version in key_format_versions {
-                    if *key_version != 1 {
-                        version = 5;
-                        break;
-                    }
-                }
-            }
-
-            if let crate::FloatOrInteger::Float(_) = segment.duration_seconds {
-                version = max(version, 3);
-            }
-
-            if let Some(ByteRangeOrBitrate::ByteRange(_)) = segment.byte_range_or_bitrate {
-                version = max(version, 4);
-            }
-
-            if segment.media_initialization_section.is_some() {
-                has_map = true;
-                version = 5;
-                break;
-            }
-        }
-
-        if self.iframes_only {
-            version = max(version, 4);
-        } else if has_map {
-            version = 6;
-        }
-
-        // NOTE: Might be wrong? This is just checking whether we define any
-        // variables, not if we use variable substitution. And what if we use
-        // variable substitution, but define no variables? Should be a parse
-        // error anyways right? But maybe not in the lower versions?
-        if !self.variables.is_empty() {
-            version = 8;
-        }
-
-        if let Some(skip_information) = &self.metadata.skip {
-            if skip_information.recently_removed_dataranges.is_empty() {
-                version = 9;
-            } else {
-                version = 10;
-            }
-        }
-
-        for variable in &self.variables {
-            if let crate::DefinitionType::QueryParameter { .. } = variable {
-                version = 11;
-            }
-        }
+    /// Returns `true` if any tag or URI line references a variable.
+    pub(super) fn references_variables(&self) -> bool {
+        let mut scanner = ReferenceScanner::default();
+        self.write_body(&mut scanner)
+            .expect("scanning for variable references should never fail");
 
-        version
+        scanner.found
     }
 }
 
 impl MediaMetadata {
-    fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
+    fn write(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
         for date_range in &self.date_ranges {
-            Tag::XDateRange(date_range.clone()).serialize(&mut output)?;
+            output.write_tag(TagRef::XDateRange(date_range))?;
         }
 
         if let Some(skip) = &self.skip {
-            Tag::XSkip {
+            output.write_tag(TagRef::XSkip {
                 number_of_skipped_segments: skip.number_of_skipped_segments,
-                recently_removed_dataranges: skip.recently_removed_dataranges.clone(),
-            }
-            .serialize(&mut output)?;
+                recently_removed_dataranges: &skip.recently_removed_dataranges,
+            })?;
         }
 
         for hint in &self.preload_hints {
-            Tag::XPreloadHint(hint.clone()).serialize(&mut output)?;
+            output.write_tag(TagRef::XPreloadHint(hint))?;
         }
 
         for report in &self.rendition_reports {
-            Tag::XRenditionReport(report.clone()).serialize(&mut output)?;
+            output.write_tag(TagRef::XRenditionReport(report))?;
         }
 
         Ok(())
@@ -241,73 +424,126 @@ impl MediaMetadata {
 }
 
 impl MediaSegment {
-    fn serialize(&self, last_media_segment: &Self, mut output: impl io::Write) -> io::Result<()> {
+    fn write(&self, last_media_segment: &Self, output: &mut impl PlaylistWriter) -> fmt::Result {
         if self.is_discontinuity {
-            Tag::XDiscontinuity.serialize(&mut output)?;
+            output.write_tag(TagRef::XDiscontinuity)?;
         }
 
-        Tag::Inf {
-            duration_seconds: self.duration_seconds.clone(),
-            title: self.title.clone(),
-        }
-        .serialize(&mut output)?;
+        output.write_tag(TagRef::Inf {
+            duration_seconds: &self.duration_seconds,
+            title: &self.title,
+        })?;
 
         if let Some(byte_range_or_bitrate) = &self.byte_range_or_bitrate {
             match byte_range_or_bitrate {
                 ByteRangeOrBitrate::ByteRange(byte_range) => {
-                    Tag::XByterange(byte_range.clone()).serialize(&mut output)?;
+                    output.write_tag(TagRef::XByterange(byte_range))?;
                 }
                 ByteRangeOrBitrate::Bitrate(kbps) => {
                     if self.byte_range_or_bitrate != last_media_segment.byte_range_or_bitrate {
-                        Tag::XBitrate { kbps: *kbps }.serialize(&mut output)?;
+                        output.write_tag(TagRef::XBitrate { kbps: *kbps })?;
                     }
                 }
             }
         }
 
-        if self.encryption != last_media_segment.encryption {
-            Tag::XKey(self.encryption.clone()).serialize(&mut output)?;
-        }
+        write_key_and_map(
+            self.encryption.as_ref(),
+            self.media_initialization_section.as_ref(),
+            last_media_segment,
+            output,
+        )?;
 
-        if let Some(map) = &self.media_initialization_section {
-            if self.media_initialization_section != last_media_segment.media_initialization_section
-            {
-                Tag::XMap {
-                    uri: map.uri.clone(),
-                    range: map.range.clone(),
-                }
-                .serialize(&mut output)?;
-            }
+        if let Some(time) = &self.absolute_time {
+            output.write_tag(TagRef::XProgramDateTime(time))?;
         }
 
-        if let Some(time) = self.absolute_time {
-            Tag::XProgramDateTime(time).serialize(&mut output)?;
+        if self.is_gap {
+            output.write_tag(TagRef::XGap)?;
         }
 
-        if self.is_gap {
-            Tag::XGap.serialize(&mut output)?;
+        write_parts(&self.parts, output)?;
+
+        output.write_uri(&self.uri)?;
+
+        Ok(())
+    }
+}
+
+impl InProgressSegment {
+    fn write(
+        &self,
+        last_media_segment: &MediaSegment,
+        output: &mut impl PlaylistWriter,
+    ) -> fmt::Result {
+        if self.is_discontinuity {
+            output.write_tag(TagRef::XDiscontinuity)?;
         }
 
-        for part in &self.parts {
-            Tag::XPart {
-                uri: part.uri.clone(),
-                duration_seconds: part.duration_in_seconds,
-                is_independent: part.is_independent,
-                byte_range: part.byte_range.clone(),
-                is_gap: part.is_gap,
+        write_key_and_map(
+            self.encryption.as_ref(),
+            self.media_initialization_section.as_ref(),
+            last_media_segment,
+            output,
+        )?;
+
+        if let Some(kbps) = self.bitrate {
+            if last_media_segment.byte_range_or_bitrate != Some(ByteRangeOrBitrate::Bitrate(kbps)) {
+                output.write_tag(TagRef::XBitrate { kbps })?;
             }
-            .serialize(&mut output)?;
         }
 
-        writeln!(output, "{}", self.uri)?;
+        if let Some(time) = &self.absolute_time {
+            output.write_tag(TagRef::XProgramDateTime(time))?;
+        }
 
-        Ok(())
+        write_parts(&self.parts, output)
+    }
+}
+
+/// Writes the EXT-X-KEY and EXT-X-MAP tags of a segment, if they differ from
+/// those of the segment before it.
+fn write_key_and_map(
+    encryption: Option<&crate::EncryptionMethod>,
+    media_initialization_section: Option<&MediaInitializationSection>,
+    last_media_segment: &MediaSegment,
+    output: &mut impl PlaylistWriter,
+) -> fmt::Result {
+    if encryption != last_media_segment.encryption.as_ref() {
+        output.write_tag(TagRef::XKey(encryption))?;
+    }
+
+    if let Some(map) = media_initialization_section {
+        if media_initialization_section != last_media_segment.media_initialization_section.as_ref()
+        {
+            output.write_tag(TagRef::XMap {
+                uri: &map.uri,
+                range: map.range.as_ref(),
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_parts(parts: &[PartialSegment], output: &mut impl PlaylistWriter) -> fmt::Result {
+    for part in parts {
+        output.write_tag(TagRef::XPart {
+            uri: &part.uri,
+            duration_seconds: part.duration_in_seconds,
+            is_independent: part.is_independent,
+            byte_range: part.byte_range.as_ref(),
+            is_gap: part.is_gap,
+        })?;
     }
+
+    Ok(())
 }
 
 impl MultivariantPlaylist {
     /// Serializes the `MultivariantPlaylist` as a extended M3U playlist into `output`.
-    /// Guaranteed to write valid UTF-8 only.
+    /// Guaranteed to write valid UTF-8 only. To serialize into a `String`,
+    /// use the [`fmt::Display`] implementation instead.
     ///
     /// This method makes lots of small calls to write on `output`. If the implementation
     /// of write on `output` makes a syscall, like with a `TcpStream`, you should wrap it
@@ -316,159 +552,236 @@ impl MultivariantPlaylist {
     /// # Note
     ///
     /// This method is not guaranteed to write a valid M3U playlist. It's your job to create
-    /// valid input.
+    /// valid input, which can be checked with [`MultivariantPlaylist::validate`].
     ///
     /// # Errors
     ///
     /// May return `Err` when encountering an io error on `output`.
-    pub fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
-        Tag::M3u.serialize(&mut output)?;
-        let version = self.get_version();
-        if version != 1 {
-            Tag::XVersion {
-                version: self.get_version(),
-            }
-            .serialize(&mut output)?;
-        }
+    pub fn serialize(&self, output: impl io::Write) -> io::Result<()> {
+        crate::write_io(output, |output| self.write(output))
+    }
 
-        for variable in &self.variables {
-            Tag::XDefine(variable.clone()).serialize(&mut output)?;
+    /// Serializes the `MultivariantPlaylist` like [`MultivariantPlaylist::serialize`], but
+    /// into an async `output`.
+    ///
+    /// The playlist is formatted one rendition group or stream at a time into
+    /// a small buffer, which is reused, so `output` receives writes of a few
+    /// KiB each.
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` when encountering an io error on `output`.
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+    pub async fn serialize_async(
+        &self,
+        output: impl tokio::io::AsyncWrite + Unpin,
+    ) -> io::Result<()> {
+        let mut output = crate::AsyncWriter::new(output);
+        let version = self.required_version();
+        output
+            .write(|buffer| {
+                write_header(version, buffer)?;
+                self.write_head(buffer)
+            })
+            .await?;
+
+        for rendition_group in &self.renditions_groups {
+            output.write(|buffer| rendition_group.write(buffer)).await?;
         }
-        if self.is_independent_segments {
-            Tag::XIndependentSegments.serialize(&mut output)?;
+        for variant_stream in &self.variant_streams {
+            output.write(|buffer| variant_stream.write(buffer)).await?;
         }
-        if let Some(offset) = &self.start_offset {
-            Tag::XStart {
-                offset_seconds: offset.offset_in_seconds,
-                is_precise: offset.is_precise,
-            }
-            .serialize(&mut output)?;
+        for i_frame_stream in &self.i_frame_streams {
+            output.write(|buffer| i_frame_stream.write(buffer)).await?;
+        }
+        output.write(|buffer| self.write_tail(buffer)).await?;
+
+        output.finish().await
+    }
+
+    /// Serializes the `MultivariantPlaylist` like [`MultivariantPlaylist::serialize`], but
+    /// only if it passes [`MultivariantPlaylist::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializeError::Invalid`] without writing anything if the
+    /// playlist is invalid, and [`SerializeError::Io`] when encountering an io
+    /// error on `output`.
+    pub fn serialize_strict(
+        &self,
+        output: impl io::Write,
+    ) -> Result<(), SerializeError<MultivariantPlaylistViolation>> {
+        self.validate().map_err(SerializeError::Invalid)?;
+        crate::write_io(output, |output| self.write(output))?;
+
+        Ok(())
+    }
+
+    /// Serializes the `MultivariantPlaylist` like [`MultivariantPlaylist::serialize`], but only if
+    /// [`MultivariantPlaylist::required_version`] is at most `max_version`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializeError::Invalid`] with every feature which requires a
+    /// version greater than `max_version` without writing anything, and
+    /// [`SerializeError::Io`] when encountering an io error on `output`.
+    pub fn serialize_with_max_version(
+        &self,
+        max_version: u8,
+        output: impl io::Write,
+    ) -> Result<(), SerializeError<VersionFeature>> {
+        let features = self.version_features();
+        let version = features
+            .last()
+            .map_or(1, |feature| feature.required_version());
+        if version > max_version {
+            return Err(SerializeError::Invalid(
+                features
+                    .into_iter()
+                    .filter(|feature| feature.required_version() > max_version)
+                    .collect(),
+            ));
         }
+        crate::write_io(output, |output| self.write_with_version(version, output))?;
+
+        Ok(())
+    }
+
+    /// Returns every [`TagViolation`] in the tags and URI lines which would be
+    /// written for this playlist.
+    pub(super) fn tag_violations(&self) -> Vec<TagViolation> {
+        let mut validator = TagValidator::default();
+        self.write_body(&mut validator)
+            .expect("validating tags should never fail");
+
+        validator.0
+    }
+
+    fn write(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
+        self.write_with_version(self.required_version(), output)
+    }
+
+    /// Writes the playlist with its already computed
+    /// [`required_version`](Self::required_version).
+    fn write_with_version(&self, version: u8, output: &mut impl PlaylistWriter) -> fmt::Result {
+        write_header(version, output)?;
+        self.write_body(output)
+    }
+
+    /// Writes every tag and URI line after the EXT-X-VERSION tag.
+    fn write_body(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
+        self.write_head(output)?;
 
         for rendition_group in &self.renditions_groups {
-            rendition_group.serialize(&mut output)?;
+            rendition_group.write(output)?;
         }
 
         for variant_stream in &self.variant_streams {
-            variant_stream.clone().serialize(&mut output)?;
+            variant_stream.write(output)?;
         }
 
         for i_frame_stream in &self.i_frame_streams {
-            i_frame_stream.clone().serialize(&mut output)?;
+            i_frame_stream.write(output)?;
         }
 
-        for data in &self.session_data {
-            data.clone().serialize(&mut output)?;
-        }
+        self.write_tail(output)
+    }
 
-        for key in &self.session_key {
-            Tag::XSessionKey(key.clone()).serialize(&mut output)?;
+    /// Writes every tag between the EXT-X-VERSION tag and the first rendition
+    /// group.
+    fn write_head(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
+        for variable in &self.variables {
+            output.write_tag(TagRef::XDefine(variable))?;
         }
-
-        for content_steering in &self.content_steering {
-            Tag::XContentSteering(content_steering.clone()).serialize(&mut output)?;
+        if self.is_independent_segments {
+            output.write_tag(TagRef::XIndependentSegments)?;
+        }
+        if let Some(offset) = &self.start_offset {
+            output.write_tag(TagRef::XStart {
+                offset_seconds: offset.offset_in_seconds,
+                is_precise: offset.is_precise,
+            })?;
         }
 
         Ok(())
     }
 
-    fn get_version(&self) -> u8 {
-        let mut version = 1;
-
-        'outer: for rendition_group in &self.renditions_groups {
-            if let RenditionGroup::ClosedCaptions { renditions, .. } = rendition_group {
-                for rendition in renditions {
-                    if let crate::InStreamId::Service(_) = rendition.in_stream_id {
-                        version = 7;
-                        break 'outer;
-                    }
-                }
-            }
-        }
-
-        // NOTE: Might be wrong? This is just checking whether we define any
-        // variables, not if we use variable substitution. And what if we use
-        // variable substitution, but define no variables? Should be a parse
-        // error anyways right? But maybe not in the lower versions?
-        if !self.variables.is_empty() {
-            version = 8;
+    /// Writes every tag after the last I-frame stream.
+    fn write_tail(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
+        for data in &self.session_data {
+            output.write_tag(TagRef::XSessionData(data))?;
         }
 
-        for variable in &self.variables {
-            if let crate::DefinitionType::QueryParameter { .. } = variable {
-                version = 11;
-            }
+        for key in &self.session_key {
+            output.write_tag(TagRef::XSessionKey(key))?;
         }
 
-        for stream in &self.variant_streams {
-            if !stream.stream_info.required_video_layout.is_empty() {
-                version = 12;
-            }
+        for content_steering in &self.content_steering {
+            output.write_tag(TagRef::XContentSteering(content_steering))?;
         }
 
-        version
+        Ok(())
     }
-}
 
-impl crate::SessionData {
-    fn serialize(self, mut output: impl io::Write) -> io::Result<()> {
-        Tag::XSessionData(self).serialize(&mut output)?;
+    /// Returns `true` if any tag or URI line references a variable.
+    pub(super) fn references_variables(&self) -> bool {
+        let mut scanner = ReferenceScanner::default();
+        self.write_body(&mut scanner)
+            .expect("scanning for variable references should never fail");
 
-        Ok(())
+        scanner.found
     }
 }
 
 impl IFrameStream {
-    fn serialize(self, mut output: impl io::Write) -> io::Result<()> {
-        Tag::XIFrameStreamInf {
-            stream_inf: self.stream_info,
-            video_group_id: self.video_group_id,
-            uri: self.uri,
-        }
-        .serialize(&mut output)?;
+    fn write(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
+        output.write_tag(TagRef::XIFrameStreamInf {
+            stream_inf: &self.stream_info,
+            video_group_id: self.video_group_id.as_deref(),
+            uri: &self.uri,
+        })?;
 
         Ok(())
     }
 }
 
 impl VariantStream {
-    fn serialize(self, mut output: impl io::Write) -> io::Result<()> {
-        Tag::XStreamInf {
-            stream_inf: self.stream_info,
+    fn write(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
+        output.write_tag(TagRef::XStreamInf {
+            stream_inf: &self.stream_info,
             frame_rate: self.frame_rate,
-            audio_group_id: self.audio_group_id,
-            video_group_id: self.video_group_id,
-            subtitles_group_id: self.subtitles_group_id,
-            closed_captions_group_id: self.closed_captions_group_id,
-            uri: self.uri,
-        }
-        .serialize(&mut output)?;
+            audio_group_id: self.audio_group_id.as_deref(),
+            video_group_id: self.video_group_id.as_deref(),
+            subtitles_group_id: self.subtitles_group_id.as_deref(),
+            closed_captions_group_id: self.closed_captions_group_id.as_deref(),
+            uri: &self.uri,
+        })?;
 
         Ok(())
     }
 }
 
 impl RenditionGroup {
-    fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
+    fn write(&self, output: &mut impl PlaylistWriter) -> fmt::Result {
         match self {
             Self::Video {
                 group_id,
                 renditions,
             } => {
                 for rendition in renditions {
-                    Tag::XMedia {
-                        media_type: crate::tags::MediaType::Video {
-                            uri: rendition.uri.clone(),
+                    output.write_tag(TagRef::XMedia {
+                        media_type: MediaTypeRef::Video {
+                            uri: rendition.uri.as_deref(),
                         },
-                        group_id: group_id.clone(),
-                        language: rendition.info.language.clone(),
-                        assoc_language: rendition.info.assoc_language.clone(),
-                        name: rendition.info.name.clone(),
-                        stable_rendition_id: rendition.info.stable_rendition_id.clone(),
-                        playback_priority: rendition.info.priority.clone(),
-                        characteristics: rendition.info.characteristics.clone(),
-                    }
-                    .serialize(&mut output)?;
+                        group_id,
+                        language: rendition.info.language.as_deref(),
+                        assoc_language: rendition.info.assoc_language.as_deref(),
+                        name: &rendition.info.name,
+                        stable_rendition_id: rendition.info.stable_rendition_id.as_deref(),
+                        playback_priority: &rendition.info.priority,
+                        characteristics: &rendition.info.characteristics,
+                    })?;
                 }
             }
             Self::Audio {
@@ -476,22 +789,21 @@ impl RenditionGroup {
                 renditions,
             } => {
                 for rendition in renditions {
-                    Tag::XMedia {
-                        media_type: crate::tags::MediaType::Audio {
-                            uri: rendition.uri.clone(),
-                            channels: rendition.channels.clone(),
+                    output.write_tag(TagRef::XMedia {
+                        media_type: MediaTypeRef::Audio {
+                            uri: rendition.uri.as_deref(),
+                            channels: rendition.channels.as_ref(),
                             bit_depth: rendition.bit_depth,
                             sample_rate: rendition.sample_rate,
                         },
-                        group_id: group_id.clone(),
-                        language: rendition.info.language.clone(),
-                        assoc_language: rendition.info.assoc_language.clone(),
-                        name: rendition.info.name.clone(),
-                        stable_rendition_id: rendition.info.stable_rendition_id.clone(),
-                        playback_priority: rendition.info.priority.clone(),
-                        characteristics: rendition.info.characteristics.clone(),
-                    }
-                    .serialize(&mut output)?;
+                        group_id,
+                        language: rendition.info.language.as_deref(),
+                        assoc_language: rendition.info.assoc_language.as_deref(),
+                        name: &rendition.info.name,
+                        stable_rendition_id: rendition.info.stable_rendition_id.as_deref(),
+                        playback_priority: &rendition.info.priority,
+                        characteristics: &rendition.info.characteristics,
+                    })?;
                 }
             }
             Self::Subtitles {
@@ -499,20 +811,19 @@ impl RenditionGroup {
                 renditions,
             } => {
                 for rendition in renditions {
-                    Tag::XMedia {
-                        media_type: crate::tags::MediaType::Subtitles {
-                            uri: rendition.uri.clone(),
+                    output.write_tag(TagRef::XMedia {
+                        media_type: MediaTypeRef::Subtitles {
+                            uri: &rendition.uri,
                             forced: rendition.forced,
                         },
-                        group_id: group_id.clone(),
-                        language: rendition.info.language.clone(),
-                        assoc_language: rendition.info.assoc_language.clone(),
-                        name: rendition.info.name.clone(),
-                        stable_rendition_id: rendition.info.stable_rendition_id.clone(),
-                        playback_priority: rendition.info.priority.clone(),
-                        characteristics: rendition.info.characteristics.clone(),
-                    }
-                    .serialize(&mut output)?;
+                        group_id,
+                        language: rendition.info.language.as_deref(),
+                        assoc_language: rendition.info.assoc_language.as_deref(),
+                        name: &rendition.info.name,
+                        stable_rendition_id: rendition.info.stable_rendition_id.as_deref(),
+                        playback_priority: &rendition.info.priority,
+                        characteristics: &rendition.info.characteristics,
+                    })?;
                 }
             }
             Self::ClosedCaptions {
@@ -520,19 +831,18 @@ impl RenditionGroup {
                 renditions,
             } => {
                 for rendition in renditions {
-                    Tag::XMedia {
-                        media_type: crate::tags::MediaType::ClosedCaptions {
-                            in_stream_id: rendition.in_stream_id.clone(),
+                    output.write_tag(TagRef::XMedia {
+                        media_type: MediaTypeRef::ClosedCaptions {
+                            in_stream_id: &rendition.in_stream_id,
                         },
-                        group_id: group_id.clone(),
-                        language: rendition.info.language.clone(),
-                        assoc_language: rendition.info.assoc_language.clone(),
-                        name: rendition.info.name.clone(),
-                        stable_rendition_id: rendition.info.stable_rendition_id.clone(),
-                        playback_priority: rendition.info.priority.clone(),
-                        characteristics: rendition.info.characteristics.clone(),
-                    }
-                    .serialize(&mut output)?;
+                        group_id,
+                        language: rendition.info.language.as_deref(),
+                        assoc_language: rendition.info.assoc_language.as_deref(),
+                        name: &rendition.info.name,
+                        stable_rendition_id: rendition.info.stable_rendition_id.as_deref(),
+                        playback_priority: &rendition.info.priority,
+                        characteristics: &rendition.info.characteristics,
+                    })?;
                 }
             }
         };
@@ -672,6 +982,7 @@ mod tests {
         };
 
         playlist.serialize(&mut output).unwrap();
+        assert_eq!(playlist.to_string().into_bytes(), output);
 
         assert_eq!(
             String::from_utf8(output).unwrap(),
@@ -857,6 +1168,23 @@ https://example.com/stuffs.m3u8
                 can_skip_dateranges: true,
             }),
             supports_blocking_playlist_reloads: true,
+            in_progress_segment: Some(Box::new(InProgressSegment {
+                is_discontinuity: true,
+                encryption: None,
+                media_initialization_section: Some(MediaInitializationSection {
+                    uri: "https://example.com/init.mp4".into(),
+                    range: None,
+                }),
+                bitrate: None,
+                absolute_time: None,
+                parts: vec![PartialSegment {
+                    uri: "https://example.com/4.0.mp4".into(),
+                    duration_in_seconds: 2.5,
+                    is_independent: true,
+                    byte_range: None,
+                    is_gap: false,
+                }],
+            })),
             metadata: MediaMetadata {
                 date_ranges: vec![],
                 skip: None,
@@ -875,6 +1203,7 @@ https://example.com/stuffs.m3u8
         };
 
         playlist.serialize(&mut output).unwrap();
+        assert_eq!(playlist.to_string().into_bytes(), output);
 
         assert_eq!(
             String::from_utf8(output).unwrap(),
@@ -909,7 +1238,141 @@ https://example.com/2.mp4
 #EXT-X-PART:URI=\"https://example.com/3.mp4\",DURATION=2.5225,INDEPENDENT=YES,BYTERANGE=\"400\"
 #EXT-X-PART:URI=\"https://example.com/3.mp4\",DURATION=2.5225,BYTERANGE=\"400@400\"
 https://example.com/3.mp4
+#EXT-X-DISCONTINUITY
+#EXT-X-MAP:URI=\"https://example.com/init.mp4\"
+#EXT-X-PART:URI=\"https://example.com/4.0.mp4\",DURATION=2.5,INDEPENDENT=YES
 "
         );
     }
+
+    #[test]
+    fn serialize_strict() {
+        let mut playlist = MediaPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXTINF:6,
+first.ts
+#EXTINF:6,
+second.ts
+#EXT-X-ENDLIST
+",
+        )
+        .unwrap();
+
+        let mut output = Vec::new();
+        playlist.serialize_strict(&mut output).unwrap();
+        assert!(!output.is_empty());
+
+        playlist.segments[1].uri = "#second.ts".into();
+        playlist.segments[1].title = "title\n#EXT-X-ENDLIST".into();
+        let mut output = Vec::new();
+        let error = playlist.serialize_strict(&mut output).unwrap_err();
+        assert!(output.is_empty());
+        assert!(matches!(
+            error,
+            SerializeError::Invalid(violations) if violations == vec![
+                MediaPlaylistViolation::InvalidTag(TagViolation::InvalidTitle(
+                    "title\n#EXT-X-ENDLIST".into()
+                )),
+                MediaPlaylistViolation::InvalidTag(TagViolation::InvalidUriLine(
+                    "#second.ts".into()
+                )),
+            ]
+        ));
+
+        let mut playlist = MultivariantPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-STREAM-INF:BANDWIDTH=1280000
+low.m3u8
+",
+        )
+        .unwrap();
+        playlist.variant_streams[0].uri = String::new();
+        assert_eq!(
+            playlist.validate(),
+            Err(vec![MultivariantPlaylistViolation::InvalidTag(
+                TagViolation::InvalidUriLine(String::new())
+            )])
+        );
+    }
+
+    #[test]
+    fn required_version() {
+        let mut playlist = MultivariantPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-DEFINE:NAME="cdn",VALUE="https://cdn.example.com"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000
+low.m3u8
+"#,
+        )
+        .unwrap();
+        assert_eq!(playlist.required_version(), 1);
+
+        playlist.variant_streams[0].uri = "{$cdn}/low.m3u8".into();
+        assert_eq!(playlist.required_version(), 8);
+
+        let mut playlist = MediaPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXT-X-DEFINE:NAME="cdn",VALUE="https://cdn.example.com"
+#EXTINF:6,{$cdn}
+1.ts
+"#,
+        )
+        .unwrap();
+        assert_eq!(playlist.required_version(), 1);
+
+        playlist.segments[0].media_initialization_section = Some(MediaInitializationSection {
+            uri: "{$cdn}/init.mp4".into(),
+            range: None,
+        });
+        assert_eq!(playlist.required_version(), 8);
+
+        let mut output = Vec::new();
+        playlist.serialize(&mut output).unwrap();
+        assert!(output.starts_with(b"#EXTM3U\n#EXT-X-VERSION:8\n"));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn serialize_async() {
+        let playlist = MediaPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-TARGETDURATION:5
+#EXTINF:5,
+1.ts
+#EXT-X-ENDLIST",
+        )
+        .unwrap();
+
+        let mut output = Vec::new();
+        playlist.serialize_async(&mut output).await.unwrap();
+        assert_eq!(output, playlist.to_string().into_bytes());
+
+        // Large enough that the buffer is written out several times.
+        let mut playlist = playlist;
+        playlist.segments = (0..1000)
+            .map(|i| MediaSegment {
+                uri: format!("https://example.com/{i}.ts"),
+                ..playlist.segments[0].clone()
+            })
+            .collect();
+        let mut output = Vec::new();
+        playlist.serialize_async(&mut output).await.unwrap();
+        assert_eq!(output, playlist.to_string().into_bytes());
+
+        let playlist = MultivariantPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"aac\",NAME=\"English\",URI=\"en.m3u8\"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,AUDIO=\"aac\"
+low.m3u8
+#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=86000,URI=\"low-iframe.m3u8\"
+#EXT-X-SESSION-DATA:DATA-ID=\"com.example.title\",VALUE=\"Example\"
+",
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        playlist.serialize_async(&mut output).await.unwrap();
+        assert_eq!(output, playlist.to_string().into_bytes());
+    }
 }
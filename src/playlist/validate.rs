@@ -0,0 +1,939 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{error::Error, fmt};
+
+use super::{
+    ByteRangeOrBitrate, MediaPlaylist, MultivariantPlaylist, PartialSegment, RenditionGroup,
+    RenditionInfo,
+};
+use crate::{
+    pathway_id, tags::TagViolation, FloatOrInteger, PlaylistType, PreloadHintType,
+    RenditionPlaybackPriority,
+};
+
+/// A requirement of the HLS spec which a [`MediaPlaylist`] does not meet.
+///
+/// Segments are identified by their index in [`MediaPlaylist::segments`],
+/// and partial segments by their index in [`super::MediaSegment::parts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaPlaylistViolation {
+    /// The duration of a segment, rounded to the nearest integer, is greater
+    /// than [`MediaPlaylist::target_duration`].
+    SegmentDurationExceedsTarget {
+        /// The index of the segment.
+        segment: usize,
+    },
+
+    /// A segment has a byte range without an offset, but the segment before it
+    /// is not a byte range of the same resource.
+    MissingByteRangeOffset {
+        /// The index of the segment.
+        segment: usize,
+    },
+
+    /// A segment has partial segments, but [`MediaPlaylist::part_information`]
+    /// is `None`.
+    MissingPartInformation,
+
+    /// The duration of a partial segment is greater than
+    /// [`super::PartInformation::part_target_duration`].
+    PartDurationExceedsTarget {
+        /// The index of the segment containing the partial segment, or the
+        /// number of segments for the in-progress segment.
+        segment: usize,
+
+        /// The index of the partial segment.
+        part: usize,
+    },
+
+    /// A partial segment has a byte range without an offset, but the partial
+    /// segment before it is not a byte range of the same resource.
+    MissingPartByteRangeOffset {
+        /// The index of the segment containing the partial segment, or the
+        /// number of segments for the in-progress segment.
+        segment: usize,
+
+        /// The index of the partial segment.
+        part: usize,
+    },
+
+    /// [`super::PartInformation::part_hold_back_seconds`] is less than twice
+    /// the part target duration.
+    PartHoldBackTooShort,
+
+    /// The playlist has partial segments but does not support blocking
+    /// playlist reloads.
+    PartsWithoutBlockingReloads,
+
+    /// [`MediaPlaylist::hold_back_seconds`] is less than three times the
+    /// target duration.
+    HoldBackTooShort,
+
+    /// The skip boundary of [`MediaPlaylist::playlist_delta_updates_information`]
+    /// is less than six times the target duration.
+    SkipBoundaryTooShort,
+
+    /// [`super::MediaMetadata::skip`] is `Some`, but the playlist does not
+    /// support delta updates.
+    SkipWithoutDeltaUpdates,
+
+    /// [`MediaPlaylist::playlist_type`] is VOD, but [`MediaPlaylist::finished`]
+    /// is false.
+    UnfinishedVod,
+
+    /// The playlist is I-frames only, and some but not all of it's segments
+    /// have a Media Initialization Section.
+    MissingMediaInitializationSection {
+        /// The index of the segment without one.
+        segment: usize,
+    },
+
+    /// The playlist has date ranges, but no segment has an absolute time.
+    DateRangesWithoutProgramDateTime,
+
+    /// The end date of a date range is before it's start date.
+    DateRangeEndsBeforeStart {
+        /// The ID of the date range.
+        id: String,
+    },
+
+    /// A date range has both a duration and an end date, and they disagree.
+    DateRangeDurationMismatch {
+        /// The ID of the date range.
+        id: String,
+    },
+
+    /// A date range ends on the next date range of it's class, but also has
+    /// a duration or end date.
+    DateRangeEndOnNextWithEnd {
+        /// The ID of the date range.
+        id: String,
+    },
+
+    /// A date range ends on the next date range of it's class, but has no class.
+    DateRangeEndOnNextWithoutClass {
+        /// The ID of the date range.
+        id: String,
+    },
+
+    /// More than one preload hint has the same type.
+    DuplicatePreloadHint(PreloadHintType),
+
+    /// A tag or URI line of the playlist can not be serialized.
+    InvalidTag(TagViolation),
+}
+
+impl fmt::Display for MediaPlaylistViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SegmentDurationExceedsTarget { segment } => {
+                write!(f, "segment {segment} is longer than the target duration")
+            }
+            Self::MissingByteRangeOffset { segment } => write!(
+                f,
+                "segment {segment} has a byte range without an offset, but does not follow a \
+                 byte range of the same resource"
+            ),
+            Self::MissingPartInformation => {
+                write!(f, "playlist has partial segments but no EXT-X-PART-INF tag")
+            }
+            Self::PartDurationExceedsTarget { segment, part } => write!(
+                f,
+                "partial segment {part} of segment {segment} is longer than the part target \
+                 duration"
+            ),
+            Self::MissingPartByteRangeOffset { segment, part } => write!(
+                f,
+                "partial segment {part} of segment {segment} has a byte range without an \
+                 offset, but does not follow a byte range of the same resource"
+            ),
+            Self::PartHoldBackTooShort => write!(
+                f,
+                "PART-HOLD-BACK is less than twice the part target duration"
+            ),
+            Self::PartsWithoutBlockingReloads => write!(
+                f,
+                "playlist has partial segments but does not support blocking reloads"
+            ),
+            Self::HoldBackTooShort => {
+                write!(f, "HOLD-BACK is less than three times the target duration")
+            }
+            Self::SkipBoundaryTooShort => write!(
+                f,
+                "CAN-SKIP-UNTIL is less than six times the target duration"
+            ),
+            Self::SkipWithoutDeltaUpdates => write!(
+                f,
+                "playlist has an EXT-X-SKIP tag but does not support delta updates"
+            ),
+            Self::UnfinishedVod => write!(f, "VOD playlist has no EXT-X-ENDLIST tag"),
+            Self::MissingMediaInitializationSection { segment } => write!(
+                f,
+                "segment {segment} of an I-frames only playlist has no EXT-X-MAP tag"
+            ),
+            Self::DateRangesWithoutProgramDateTime => write!(
+                f,
+                "playlist has date ranges but no EXT-X-PROGRAM-DATE-TIME tag"
+            ),
+            Self::DateRangeEndsBeforeStart { id } => {
+                write!(f, "date range {id:?} ends before it starts")
+            }
+            Self::DateRangeDurationMismatch { id } => write!(
+                f,
+                "date range {id:?} has a duration which disagrees with it's end date"
+            ),
+            Self::DateRangeEndOnNextWithEnd { id } => write!(
+                f,
+                "date range {id:?} has END-ON-NEXT along with a duration or end date"
+            ),
+            Self::DateRangeEndOnNextWithoutClass { id } => {
+                write!(f, "date range {id:?} has END-ON-NEXT but no class")
+            }
+            Self::DuplicatePreloadHint(hint_type) => {
+                write!(f, "more than one preload hint has type {hint_type:?}")
+            }
+            Self::InvalidTag(violation) => violation.fmt(f),
+        }
+    }
+}
+
+impl Error for MediaPlaylistViolation {}
+
+/// The type of a [`RenditionGroup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenditionGroupType {
+    /// A [`RenditionGroup::Video`].
+    Video,
+
+    /// A [`RenditionGroup::Audio`].
+    Audio,
+
+    /// A [`RenditionGroup::Subtitles`].
+    Subtitles,
+
+    /// A [`RenditionGroup::ClosedCaptions`].
+    ClosedCaptions,
+}
+
+impl fmt::Display for RenditionGroupType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Video => write!(f, "VIDEO"),
+            Self::Audio => write!(f, "AUDIO"),
+            Self::Subtitles => write!(f, "SUBTITLES"),
+            Self::ClosedCaptions => write!(f, "CLOSED-CAPTIONS"),
+        }
+    }
+}
+
+/// A requirement of the HLS spec which a [`MultivariantPlaylist`] does not meet.
+///
+/// Variant Streams are identified by their index in
+/// [`MultivariantPlaylist::variant_streams`], and I-frame streams by their
+/// index in [`MultivariantPlaylist::i_frame_streams`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultivariantPlaylistViolation {
+    /// A Variant Stream refers to a rendition group which does not exist.
+    UnknownRenditionGroup {
+        /// The index of the Variant Stream.
+        variant_stream: usize,
+
+        /// The type of the missing group.
+        group_type: RenditionGroupType,
+
+        /// The ID of the missing group.
+        group_id: String,
+    },
+
+    /// An I-frame stream refers to a video rendition group which does not exist.
+    UnknownIFrameRenditionGroup {
+        /// The index of the I-frame stream.
+        i_frame_stream: usize,
+
+        /// The ID of the missing group.
+        group_id: String,
+    },
+
+    /// More than one rendition group has the same type and ID.
+    DuplicateRenditionGroup {
+        /// The type of the groups.
+        group_type: RenditionGroupType,
+
+        /// The ID of the groups.
+        group_id: String,
+    },
+
+    /// More than one rendition in a group has a playback priority of
+    /// [`RenditionPlaybackPriority::Default`].
+    MultipleDefaultRenditions {
+        /// The type of the group.
+        group_type: RenditionGroupType,
+
+        /// The ID of the group.
+        group_id: String,
+    },
+
+    /// More than one rendition in a group has the same name.
+    DuplicateRenditionName {
+        /// The type of the group.
+        group_type: RenditionGroupType,
+
+        /// The ID of the group.
+        group_id: String,
+
+        /// The duplicated name.
+        name: String,
+    },
+
+    /// More than one rendition in a group has the same stable rendition ID.
+    DuplicateStableRenditionId {
+        /// The type of the group.
+        group_type: RenditionGroupType,
+
+        /// The ID of the group.
+        group_id: String,
+
+        /// The duplicated stable rendition ID.
+        stable_rendition_id: String,
+    },
+
+    /// More than one Variant Stream on the same pathway has the same stable
+    /// variant ID.
+    DuplicateStableVariantId(String),
+
+    /// More than one I-frame stream on the same pathway has the same stable
+    /// variant ID.
+    DuplicateIFrameStableVariantId(String),
+
+    /// A tag or URI line of the playlist can not be serialized.
+    InvalidTag(TagViolation),
+}
+
+impl fmt::Display for MultivariantPlaylistViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownRenditionGroup {
+                variant_stream,
+                group_type,
+                group_id,
+            } => write!(
+                f,
+                "variant stream {variant_stream} refers to unknown {group_type} group {group_id:?}"
+            ),
+            Self::UnknownIFrameRenditionGroup {
+                i_frame_stream,
+                group_id,
+            } => write!(
+                f,
+                "I-frame stream {i_frame_stream} refers to unknown VIDEO group {group_id:?}"
+            ),
+            Self::DuplicateRenditionGroup {
+                group_type,
+                group_id,
+            } => write!(f, "{group_type} group {group_id:?} appears more than once"),
+            Self::MultipleDefaultRenditions {
+                group_type,
+                group_id,
+            } => write!(
+                f,
+                "{group_type} group {group_id:?} has more than one default rendition"
+            ),
+            Self::DuplicateRenditionName {
+                group_type,
+                group_id,
+                name,
+            } => write!(
+                f,
+                "{group_type} group {group_id:?} has more than one rendition named {name:?}"
+            ),
+            Self::DuplicateStableRenditionId {
+                group_type,
+                group_id,
+                stable_rendition_id,
+            } => write!(
+                f,
+                "{group_type} group {group_id:?} has more than one rendition with stable \
+                 rendition ID {stable_rendition_id:?}"
+            ),
+            Self::DuplicateStableVariantId(id) => write!(
+                f,
+                "more than one variant stream on a pathway has stable variant ID {id:?}"
+            ),
+            Self::DuplicateIFrameStableVariantId(id) => write!(
+                f,
+                "more than one I-frame stream on a pathway has stable variant ID {id:?}"
+            ),
+            Self::InvalidTag(violation) => violation.fmt(f),
+        }
+    }
+}
+
+impl Error for MultivariantPlaylistViolation {}
+
+impl MultivariantPlaylist {
+    /// Checks the playlist against the requirements of the HLS spec,
+    /// returning every requirement which is not met.
+    ///
+    /// Stable variant IDs must be unique among the streams of each pathway,
+    /// since cloned pathways share them, and stable rendition IDs must be
+    /// unique within each rendition group. Every tag and URI line must also
+    /// be serializable, see [`crate::tags::Tag::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with at least one violation if the playlist is invalid.
+    pub fn validate(&self) -> Result<(), Vec<MultivariantPlaylistViolation>> {
+        let mut violations: Vec<_> = self
+            .tag_violations()
+            .into_iter()
+            .map(MultivariantPlaylistViolation::InvalidTag)
+            .collect();
+
+        for (i, group) in self.renditions_groups.iter().enumerate() {
+            let (group_type, group_id) = group_type_and_id(group);
+            if self.renditions_groups[..i]
+                .iter()
+                .any(|other| group_type_and_id(other) == (group_type, group_id))
+            {
+                violations.push(MultivariantPlaylistViolation::DuplicateRenditionGroup {
+                    group_type,
+                    group_id: group_id.into(),
+                });
+            }
+
+            let infos = rendition_infos(group);
+            if infos
+                .iter()
+                .filter(|info| info.priority == RenditionPlaybackPriority::Default)
+                .count()
+                > 1
+            {
+                violations.push(MultivariantPlaylistViolation::MultipleDefaultRenditions {
+                    group_type,
+                    group_id: group_id.into(),
+                });
+            }
+
+            for (j, info) in infos.iter().enumerate() {
+                let earlier = &infos[..j];
+                if earlier.iter().any(|other| other.name == info.name) {
+                    violations.push(MultivariantPlaylistViolation::DuplicateRenditionName {
+                        group_type,
+                        group_id: group_id.into(),
+                        name: info.name.clone(),
+                    });
+                }
+
+                if let Some(id) = &info.stable_rendition_id {
+                    if earlier
+                        .iter()
+                        .any(|other| other.stable_rendition_id.as_ref() == Some(id))
+                    {
+                        violations.push(
+                            MultivariantPlaylistViolation::DuplicateStableRenditionId {
+                                group_type,
+                                group_id: group_id.into(),
+                                stable_rendition_id: id.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        let has_group = |group_type, group_id: &str| {
+            self.renditions_groups
+                .iter()
+                .any(|group| group_type_and_id(group) == (group_type, group_id))
+        };
+
+        for (i, variant) in self.variant_streams.iter().enumerate() {
+            for (group_type, group_id) in [
+                (RenditionGroupType::Video, &variant.video_group_id),
+                (RenditionGroupType::Audio, &variant.audio_group_id),
+                (RenditionGroupType::Subtitles, &variant.subtitles_group_id),
+                (
+                    RenditionGroupType::ClosedCaptions,
+                    &variant.closed_captions_group_id,
+                ),
+            ] {
+                if let Some(group_id) = group_id {
+                    if !has_group(group_type, group_id) {
+                        violations.push(MultivariantPlaylistViolation::UnknownRenditionGroup {
+                            variant_stream: i,
+                            group_type,
+                            group_id: group_id.clone(),
+                        });
+                    }
+                }
+            }
+
+            if let Some(id) = &variant.stream_info.stable_variant_id {
+                if self.variant_streams[..i].iter().any(|other| {
+                    other.stream_info.stable_variant_id.as_ref() == Some(id)
+                        && pathway_id(other.stream_info.pathway_id.as_deref())
+                            == pathway_id(variant.stream_info.pathway_id.as_deref())
+                }) {
+                    violations.push(MultivariantPlaylistViolation::DuplicateStableVariantId(
+                        id.clone(),
+                    ));
+                }
+            }
+        }
+
+        for (i, stream) in self.i_frame_streams.iter().enumerate() {
+            if let Some(group_id) = &stream.video_group_id {
+                if !has_group(RenditionGroupType::Video, group_id) {
+                    violations.push(MultivariantPlaylistViolation::UnknownIFrameRenditionGroup {
+                        i_frame_stream: i,
+                        group_id: group_id.clone(),
+                    });
+                }
+            }
+
+            if let Some(id) = &stream.stream_info.stable_variant_id {
+                if self.i_frame_streams[..i].iter().any(|other| {
+                    other.stream_info.stable_variant_id.as_ref() == Some(id)
+                        && pathway_id(other.stream_info.pathway_id.as_deref())
+                            == pathway_id(stream.stream_info.pathway_id.as_deref())
+                }) {
+                    violations.push(
+                        MultivariantPlaylistViolation::DuplicateIFrameStableVariantId(id.clone()),
+                    );
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+fn group_type_and_id(group: &RenditionGroup) -> (RenditionGroupType, &str) {
+    match group {
+        RenditionGroup::Video { group_id, .. } => (RenditionGroupType::Video, group_id),
+        RenditionGroup::Audio { group_id, .. } => (RenditionGroupType::Audio, group_id),
+        RenditionGroup::Subtitles { group_id, .. } => (RenditionGroupType::Subtitles, group_id),
+        RenditionGroup::ClosedCaptions { group_id, .. } => {
+            (RenditionGroupType::ClosedCaptions, group_id)
+        }
+    }
+}
+
+fn rendition_infos(group: &RenditionGroup) -> Vec<&RenditionInfo> {
+    match group {
+        RenditionGroup::Video { renditions, .. } => renditions.iter().map(|r| &r.info).collect(),
+        RenditionGroup::Audio { renditions, .. } => renditions.iter().map(|r| &r.info).collect(),
+        RenditionGroup::Subtitles { renditions, .. } => {
+            renditions.iter().map(|r| &r.info).collect()
+        }
+        RenditionGroup::ClosedCaptions { renditions, .. } => {
+            renditions.iter().map(|r| &r.info).collect()
+        }
+    }
+}
+
+impl MediaPlaylist {
+    /// Checks the playlist against the requirements of the HLS spec,
+    /// returning every requirement which is not met.
+    ///
+    /// This checks both the rules that apply to the playlist as a whole and
+    /// that every tag and URI line can be serialized, see
+    /// [`crate::tags::Tag::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with at least one violation if the playlist is invalid.
+    pub fn validate(&self) -> Result<(), Vec<MediaPlaylistViolation>> {
+        let mut violations: Vec<_> = self
+            .tag_violations()
+            .into_iter()
+            .map(MediaPlaylistViolation::InvalidTag)
+            .collect();
+        self.validate_segments(&mut violations);
+        self.validate_server_control(&mut violations);
+        self.validate_metadata(&mut violations);
+
+        if self.playlist_type == Some(PlaylistType::Vod) && !self.finished {
+            violations.push(MediaPlaylistViolation::UnfinishedVod);
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    fn validate_segments(&self, violations: &mut Vec<MediaPlaylistViolation>) {
+        let uses_media_initialization_sections = self
+            .segments
+            .iter()
+            .any(|segment| segment.media_initialization_section.is_some());
+
+        for (i, segment) in self.segments.iter().enumerate() {
+            let duration = match segment.duration_seconds {
+                FloatOrInteger::Float(duration) => duration.round(),
+                #[allow(clippy::cast_precision_loss)]
+                FloatOrInteger::Integer(duration) => duration as f64,
+            };
+            #[allow(clippy::cast_precision_loss)]
+            if duration > self.target_duration as f64 {
+                violations
+                    .push(MediaPlaylistViolation::SegmentDurationExceedsTarget { segment: i });
+            }
+
+            if let Some(ByteRangeOrBitrate::ByteRange(range)) = &segment.byte_range_or_bitrate {
+                let follows_same_resource = i.checked_sub(1).is_some_and(|previous| {
+                    let previous = &self.segments[previous];
+                    previous.uri == segment.uri
+                        && matches!(
+                            previous.byte_range_or_bitrate,
+                            Some(ByteRangeOrBitrate::ByteRange(_))
+                        )
+                });
+                if range.start_offset_bytes.is_none() && !follows_same_resource {
+                    violations.push(MediaPlaylistViolation::MissingByteRangeOffset { segment: i });
+                }
+            }
+
+            if self.iframes_only
+                && uses_media_initialization_sections
+                && segment.media_initialization_section.is_none()
+            {
+                violations
+                    .push(MediaPlaylistViolation::MissingMediaInitializationSection { segment: i });
+            }
+
+            self.validate_parts(i, &segment.parts, violations);
+        }
+
+        if let Some(segment) = &self.in_progress_segment {
+            self.validate_parts(self.segments.len(), &segment.parts, violations);
+        }
+    }
+
+    fn validate_parts(
+        &self,
+        segment: usize,
+        parts: &[PartialSegment],
+        violations: &mut Vec<MediaPlaylistViolation>,
+    ) {
+        for (j, part) in parts.iter().enumerate() {
+            if let Some(information) = &self.part_information {
+                if part.duration_in_seconds > information.part_target_duration {
+                    violations.push(MediaPlaylistViolation::PartDurationExceedsTarget {
+                        segment,
+                        part: j,
+                    });
+                }
+            }
+
+            let follows_same_resource = j.checked_sub(1).is_some_and(|previous| {
+                let previous = &parts[previous];
+                previous.uri == part.uri && previous.byte_range.is_some()
+            });
+            if part
+                .byte_range
+                .as_ref()
+                .is_some_and(|range| range.start_offset_bytes.is_none())
+                && !follows_same_resource
+            {
+                violations
+                    .push(MediaPlaylistViolation::MissingPartByteRangeOffset { segment, part: j });
+            }
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn validate_server_control(&self, violations: &mut Vec<MediaPlaylistViolation>) {
+        let target_duration = self.target_duration as f64;
+
+        if self
+            .hold_back_seconds
+            .is_some_and(|hold_back| hold_back < target_duration * 3.0)
+        {
+            violations.push(MediaPlaylistViolation::HoldBackTooShort);
+        }
+
+        if self
+            .playlist_delta_updates_information
+            .as_ref()
+            .is_some_and(|information| information.skip_boundary_seconds < target_duration * 6.0)
+        {
+            violations.push(MediaPlaylistViolation::SkipBoundaryTooShort);
+        }
+
+        let has_parts = self
+            .segments
+            .iter()
+            .map(|segment| &segment.parts)
+            .chain(
+                self.in_progress_segment
+                    .iter()
+                    .map(|segment| &segment.parts),
+            )
+            .any(|parts| !parts.is_empty());
+        match &self.part_information {
+            Some(information)
+                if information.part_hold_back_seconds < information.part_target_duration * 2.0 =>
+            {
+                violations.push(MediaPlaylistViolation::PartHoldBackTooShort);
+            }
+            None if has_parts => violations.push(MediaPlaylistViolation::MissingPartInformation),
+            _ => (),
+        }
+
+        if has_parts && !self.supports_blocking_playlist_reloads {
+            violations.push(MediaPlaylistViolation::PartsWithoutBlockingReloads);
+        }
+    }
+
+    fn validate_metadata(&self, violations: &mut Vec<MediaPlaylistViolation>) {
+        if self.metadata.skip.is_some() && self.playlist_delta_updates_information.is_none() {
+            violations.push(MediaPlaylistViolation::SkipWithoutDeltaUpdates);
+        }
+
+        if !self.metadata.date_ranges.is_empty()
+            && self
+                .segments
+                .iter()
+                .all(|segment| segment.absolute_time.is_none())
+        {
+            violations.push(MediaPlaylistViolation::DateRangesWithoutProgramDateTime);
+        }
+
+        for date_range in &self.metadata.date_ranges {
+            let id = || date_range.id.clone();
+
+            if let Some(end_date) = date_range.end_date {
+                if end_date < date_range.start_date {
+                    violations.push(MediaPlaylistViolation::DateRangeEndsBeforeStart { id: id() });
+                }
+
+                if let Some(duration) = date_range.duration_seconds {
+                    #[allow(clippy::cast_precision_loss)]
+                    let actual_duration = (end_date - date_range.start_date)
+                        .num_microseconds()
+                        .map_or(f64::INFINITY, |microseconds| microseconds as f64 / 1e6);
+                    if (actual_duration - duration).abs() > 0.001 {
+                        violations
+                            .push(MediaPlaylistViolation::DateRangeDurationMismatch { id: id() });
+                    }
+                }
+            }
+
+            if date_range.end_on_next {
+                if date_range.end_date.is_some() || date_range.duration_seconds.is_some() {
+                    violations.push(MediaPlaylistViolation::DateRangeEndOnNextWithEnd { id: id() });
+                }
+                if date_range.class.is_none() {
+                    violations
+                        .push(MediaPlaylistViolation::DateRangeEndOnNextWithoutClass { id: id() });
+                }
+            }
+        }
+
+        let hints = &self.metadata.preload_hints;
+        for hint_type in [PreloadHintType::Part, PreloadHintType::Map] {
+            if hints
+                .iter()
+                .filter(|hint| hint.hint_type == hint_type)
+                .count()
+                > 1
+            {
+                violations.push(MediaPlaylistViolation::DuplicatePreloadHint(hint_type));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_media_playlist() {
+        let playlist = MediaPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-TARGETDURATION:4
+#EXT-X-PLAYLIST-TYPE:EVENT
+#EXT-X-SERVER-CONTROL:HOLD-BACK=12,CAN-BLOCK-RELOAD=YES,PART-HOLD-BACK=3
+#EXT-X-PART-INF:PART-TARGET=1
+#EXT-X-PROGRAM-DATE-TIME:2024-01-01T00:00:00Z
+#EXT-X-DATERANGE:ID=\"ad\",START-DATE=\"2024-01-01T00:00:00Z\",DURATION=4.0
+#EXT-X-BYTERANGE:1000@0
+#EXTINF:4.4,
+main.mp4
+#EXT-X-BYTERANGE:1000
+#EXTINF:4,
+main.mp4
+#EXT-X-PART:DURATION=1,URI=\"3.0.mp4\"
+#EXT-X-PART:DURATION=1,URI=\"3.1.mp4\"
+#EXTINF:2,
+3.mp4
+",
+        )
+        .unwrap();
+        assert_eq!(playlist.validate(), Ok(()));
+
+        let playlist = MediaPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-TARGETDURATION:4
+#EXT-X-PLAYLIST-TYPE:VOD
+#EXT-X-SERVER-CONTROL:HOLD-BACK=8,PART-HOLD-BACK=1
+#EXT-X-PART-INF:PART-TARGET=1
+#EXT-X-DATERANGE:ID=\"ad\",CLASS=\"a\",START-DATE=\"2024-01-01T00:00:10Z\",\
+END-DATE=\"2024-01-01T00:00:00Z\",END-ON-NEXT=YES
+#EXT-X-DATERANGE:ID=\"b\",START-DATE=\"2024-01-01T00:00:00Z\",END-ON-NEXT=YES
+#EXTINF:4.5,
+1.mp4
+#EXT-X-BYTERANGE:1000
+#EXTINF:4,
+2.mp4
+#EXT-X-PART:DURATION=1.5,URI=\"3.0.mp4\"
+#EXTINF:2,
+3.mp4
+#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"4.0.mp4\"
+#EXT-X-PRELOAD-HINT:TYPE=PART,URI=\"4.1.mp4\"
+",
+        )
+        .unwrap();
+        assert_eq!(
+            playlist.validate(),
+            Err(vec![
+                MediaPlaylistViolation::SegmentDurationExceedsTarget { segment: 0 },
+                MediaPlaylistViolation::MissingByteRangeOffset { segment: 1 },
+                MediaPlaylistViolation::PartDurationExceedsTarget {
+                    segment: 2,
+                    part: 0
+                },
+                MediaPlaylistViolation::HoldBackTooShort,
+                MediaPlaylistViolation::PartHoldBackTooShort,
+                MediaPlaylistViolation::PartsWithoutBlockingReloads,
+                MediaPlaylistViolation::DateRangesWithoutProgramDateTime,
+                MediaPlaylistViolation::DateRangeEndsBeforeStart { id: "ad".into() },
+                MediaPlaylistViolation::DateRangeEndOnNextWithEnd { id: "ad".into() },
+                MediaPlaylistViolation::DateRangeEndOnNextWithoutClass { id: "b".into() },
+                MediaPlaylistViolation::DuplicatePreloadHint(PreloadHintType::Part),
+                MediaPlaylistViolation::UnfinishedVod,
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_iframes_only_media_playlist() {
+        let playlist = MediaPlaylist::parse(
+            b"#EXTM3U
+#EXT-X-TARGETDURATION:4
+#EXT-X-I-FRAMES-ONLY
+#EXTINF:4,
+#EXT-X-BYTERANGE:1000@0
+1.mp4
+#EXT-X-MAP:URI=\"init.mp4\"
+#EXTINF:4,
+#EXT-X-BYTERANGE:1000@0
+2.mp4
+",
+        )
+        .unwrap();
+        assert_eq!(
+            playlist.validate(),
+            Err(vec![
+                MediaPlaylistViolation::MissingMediaInitializationSection { segment: 0 }
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_multivariant_playlist() {
+        let playlist = MultivariantPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac",NAME="English",DEFAULT=YES,STABLE-RENDITION-ID="en",URI="en.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac",NAME="Deutsch",STABLE-RENDITION-ID="de",URI="de.m3u8"
+#EXT-X-MEDIA:TYPE=CLOSED-CAPTIONS,GROUP-ID="cc",NAME="English",INSTREAM-ID="CC1"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,AUDIO="aac",CLOSED-CAPTIONS="cc",STABLE-VARIANT-ID="lo",PATHWAY-ID="A"
+a/low.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,AUDIO="aac",CLOSED-CAPTIONS="cc",STABLE-VARIANT-ID="lo",PATHWAY-ID="B"
+b/low.m3u8
+"#,
+        )
+        .unwrap();
+        assert_eq!(playlist.validate(), Ok(()));
+
+        let playlist = MultivariantPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac",NAME="English",DEFAULT=YES,STABLE-RENDITION-ID="en",URI="en.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac",NAME="English",DEFAULT=YES,STABLE-RENDITION-ID="en",URI="en2.m3u8"
+#EXT-X-MEDIA:TYPE=VIDEO,GROUP-ID="aac",NAME="Angle"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,AUDIO="aac",VIDEO="vid",STABLE-VARIANT-ID="lo"
+low.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,SUBTITLES="aac",STABLE-VARIANT-ID="lo",PATHWAY-ID="."
+low2.m3u8
+#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=86000,VIDEO="vid",URI="iframe.m3u8"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            playlist.validate(),
+            Err(vec![
+                MultivariantPlaylistViolation::MultipleDefaultRenditions {
+                    group_type: RenditionGroupType::Audio,
+                    group_id: "aac".into()
+                },
+                MultivariantPlaylistViolation::DuplicateRenditionName {
+                    group_type: RenditionGroupType::Audio,
+                    group_id: "aac".into(),
+                    name: "English".into()
+                },
+                MultivariantPlaylistViolation::DuplicateStableRenditionId {
+                    group_type: RenditionGroupType::Audio,
+                    group_id: "aac".into(),
+                    stable_rendition_id: "en".into()
+                },
+                MultivariantPlaylistViolation::UnknownRenditionGroup {
+                    variant_stream: 0,
+                    group_type: RenditionGroupType::Video,
+                    group_id: "vid".into()
+                },
+                MultivariantPlaylistViolation::UnknownRenditionGroup {
+                    variant_stream: 1,
+                    group_type: RenditionGroupType::Subtitles,
+                    group_id: "aac".into()
+                },
+                MultivariantPlaylistViolation::DuplicateStableVariantId("lo".into()),
+                MultivariantPlaylistViolation::UnknownIFrameRenditionGroup {
+                    i_frame_stream: 0,
+                    group_id: "vid".into()
+                },
+            ])
+        );
+
+        let mut playlist = playlist;
+        playlist.renditions_groups.push(RenditionGroup::Video {
+            group_id: "aac".into(),
+            renditions: vec![],
+        });
+        assert!(playlist.validate().unwrap_err().contains(
+            &MultivariantPlaylistViolation::DuplicateRenditionGroup {
+                group_type: RenditionGroupType::Video,
+                group_id: "aac".into()
+            }
+        ));
+    }
+}
@@ -0,0 +1,417 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use super::{ByteRangeOrBitrate, MediaPlaylist, MultivariantPlaylist, Playlist, RenditionGroup};
+use crate::{
+    DefinitionType, EncryptionMethod, FloatOrInteger, InStreamId, KeyFormat, VideoChannelSpecifier,
+};
+
+/// A feature of a playlist which requires an EXT-X-VERSION greater than 1.
+///
+/// Features are ordered by the version they require.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum VersionFeature {
+    /// The IV attribute of an EXT-X-KEY or EXT-X-SESSION-KEY tag. Requires
+    /// version 2.
+    EncryptionIv,
+
+    /// A floating-point EXTINF duration. Requires version 3.
+    FloatDuration,
+
+    /// An EXT-X-BYTERANGE tag. Requires version 4.
+    ByteRange,
+
+    /// An EXT-X-I-FRAMES-ONLY tag. Requires version 4.
+    IFramesOnly,
+
+    /// The KEYFORMAT attribute of an EXT-X-KEY or EXT-X-SESSION-KEY tag.
+    /// Requires version 5.
+    KeyFormat,
+
+    /// The KEYFORMATVERSIONS attribute of an EXT-X-KEY or EXT-X-SESSION-KEY
+    /// tag. Requires version 5.
+    KeyFormatVersions,
+
+    /// The SAMPLE-AES encryption method. Requires version 5.
+    SampleAes,
+
+    /// An EXT-X-MAP tag in an I-frames only playlist. Requires version 5.
+    IFramesOnlyMap,
+
+    /// An EXT-X-MAP tag in a playlist which is not I-frames only. Requires
+    /// version 6.
+    Map,
+
+    /// A SERVICE value of the INSTREAM-ID attribute. Requires version 7.
+    ServiceInStreamId,
+
+    /// A variable reference in a URI line or attribute value. Requires
+    /// version 8.
+    VariableSubstitution,
+
+    /// An EXT-X-SKIP tag. Requires version 9.
+    Skip,
+
+    /// The RECENTLY-REMOVED-DATERANGES attribute of an EXT-X-SKIP tag.
+    /// Requires version 10.
+    SkipDateRanges,
+
+    /// The QUERYPARAM attribute of an EXT-X-DEFINE tag. Requires version 11.
+    QueryParameterDefinition,
+
+    /// The REQ-VIDEO-LAYOUT attribute. Requires version 12.
+    RequiredVideoLayout,
+}
+
+impl VersionFeature {
+    /// Every feature, in order.
+    const ALL: [Self; 15] = [
+        Self::EncryptionIv,
+        Self::FloatDuration,
+        Self::ByteRange,
+        Self::IFramesOnly,
+        Self::KeyFormat,
+        Self::KeyFormatVersions,
+        Self::SampleAes,
+        Self::IFramesOnlyMap,
+        Self::Map,
+        Self::ServiceInStreamId,
+        Self::VariableSubstitution,
+        Self::Skip,
+        Self::SkipDateRanges,
+        Self::QueryParameterDefinition,
+        Self::RequiredVideoLayout,
+    ];
+
+    /// Returns the lowest EXT-X-VERSION which supports the feature.
+    #[must_use]
+    pub const fn required_version(self) -> u8 {
+        match self {
+            Self::EncryptionIv => 2,
+            Self::FloatDuration => 3,
+            Self::ByteRange | Self::IFramesOnly => 4,
+            Self::KeyFormat | Self::KeyFormatVersions | Self::SampleAes | Self::IFramesOnlyMap => 5,
+            Self::Map => 6,
+            Self::ServiceInStreamId => 7,
+            Self::VariableSubstitution => 8,
+            Self::Skip => 9,
+            Self::SkipDateRanges => 10,
+            Self::QueryParameterDefinition => 11,
+            Self::RequiredVideoLayout => 12,
+        }
+    }
+}
+
+impl fmt::Display for VersionFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let feature = match self {
+            Self::EncryptionIv => "IV attribute",
+            Self::FloatDuration => "floating-point EXTINF duration",
+            Self::ByteRange => "EXT-X-BYTERANGE",
+            Self::IFramesOnly => "EXT-X-I-FRAMES-ONLY",
+            Self::KeyFormat => "KEYFORMAT attribute",
+            Self::KeyFormatVersions => "KEYFORMATVERSIONS attribute",
+            Self::SampleAes => "SAMPLE-AES encryption",
+            Self::IFramesOnlyMap => "EXT-X-MAP in an I-frames only playlist",
+            Self::Map => "EXT-X-MAP",
+            Self::ServiceInStreamId => "INSTREAM-ID SERVICE value",
+            Self::VariableSubstitution => "variable substitution",
+            Self::Skip => "EXT-X-SKIP",
+            Self::SkipDateRanges => "EXT-X-SKIP RECENTLY-REMOVED-DATERANGES attribute",
+            Self::QueryParameterDefinition => "EXT-X-DEFINE QUERYPARAM attribute",
+            Self::RequiredVideoLayout => "REQ-VIDEO-LAYOUT attribute",
+        };
+
+        write!(f, "{feature} requires version {}", self.required_version())
+    }
+}
+
+impl std::error::Error for VersionFeature {}
+
+impl Playlist {
+    /// Returns the lowest EXT-X-VERSION a client must support to play the
+    /// playlist. See [`MultivariantPlaylist::required_version`] and
+    /// [`MediaPlaylist::required_version`].
+    #[must_use]
+    pub fn required_version(&self) -> u8 {
+        match self {
+            Self::Multivariant(playlist) => playlist.required_version(),
+            Self::Media(playlist) => playlist.required_version(),
+        }
+    }
+}
+
+impl MediaPlaylist {
+    /// Returns the lowest EXT-X-VERSION a client must support to play the
+    /// playlist, which is the version written by [`MediaPlaylist::serialize`].
+    ///
+    /// Version 8 is only required if a URI line, quoted-string attribute
+    /// value, or hexadecimal-sequence attribute value references a variable,
+    /// not just because variables are defined.
+    #[must_use]
+    pub fn required_version(&self) -> u8 {
+        self.features().required_version()
+    }
+
+    /// Returns every feature of the playlist which requires an EXT-X-VERSION
+    /// greater than 1, ordered by the version they require.
+    #[must_use]
+    pub fn version_features(&self) -> Vec<VersionFeature> {
+        self.features().iter().collect()
+    }
+
+    fn features(&self) -> Features {
+        let mut features = Features::default();
+
+        for segment in &self.segments {
+            if let FloatOrInteger::Float(_) = segment.duration_seconds {
+                features.push(VersionFeature::FloatDuration);
+            }
+
+            if let Some(ByteRangeOrBitrate::ByteRange(_)) = segment.byte_range_or_bitrate {
+                features.push(VersionFeature::ByteRange);
+            }
+        }
+
+        let keys_and_maps = self
+            .segments
+            .iter()
+            .map(|segment| (&segment.encryption, &segment.media_initialization_section))
+            .chain(
+                self.in_progress_segment
+                    .iter()
+                    .map(|segment| (&segment.encryption, &segment.media_initialization_section)),
+            );
+        for (encryption, media_initialization_section) in keys_and_maps {
+            if let Some(method) = encryption {
+                encryption_features(method, &mut features);
+            }
+
+            if media_initialization_section.is_some() {
+                features.push(if self.iframes_only {
+                    VersionFeature::IFramesOnlyMap
+                } else {
+                    VersionFeature::Map
+                });
+            }
+        }
+
+        if self.iframes_only {
+            features.push(VersionFeature::IFramesOnly);
+        }
+
+        if self.references_variables() {
+            features.push(VersionFeature::VariableSubstitution);
+        }
+
+        if let Some(skip_information) = &self.metadata.skip {
+            features.push(VersionFeature::Skip);
+            if !skip_information.recently_removed_dataranges.is_empty() {
+                features.push(VersionFeature::SkipDateRanges);
+            }
+        }
+
+        definition_features(&self.variables, &mut features);
+
+        features
+    }
+}
+
+impl MultivariantPlaylist {
+    /// Returns the lowest EXT-X-VERSION a client must support to play the
+    /// playlist, which is the version written by
+    /// [`MultivariantPlaylist::serialize`].
+    ///
+    /// Version 8 is only required if a URI line, quoted-string attribute
+    /// value, or hexadecimal-sequence attribute value references a variable,
+    /// not just because variables are defined.
+    #[must_use]
+    pub fn required_version(&self) -> u8 {
+        self.features().required_version()
+    }
+
+    /// Returns every feature of the playlist which requires an EXT-X-VERSION
+    /// greater than 1, ordered by the version they require.
+    #[must_use]
+    pub fn version_features(&self) -> Vec<VersionFeature> {
+        self.features().iter().collect()
+    }
+
+    fn features(&self) -> Features {
+        let mut features = Features::default();
+
+        for key in &self.session_key {
+            encryption_features(key, &mut features);
+        }
+
+        for rendition_group in &self.renditions_groups {
+            if let RenditionGroup::ClosedCaptions { renditions, .. } = rendition_group {
+                if renditions
+                    .iter()
+                    .any(|rendition| matches!(rendition.in_stream_id, InStreamId::Service(_)))
+                {
+                    features.push(VersionFeature::ServiceInStreamId);
+                }
+            }
+        }
+
+        if self.references_variables() {
+            features.push(VersionFeature::VariableSubstitution);
+        }
+
+        definition_features(&self.variables, &mut features);
+
+        // A lone CH-MONO is the default, and is not written.
+        if self.variant_streams.iter().any(|stream| {
+            !matches!(
+                stream.stream_info.required_video_layout.as_slice(),
+                [] | [VideoChannelSpecifier::Mono]
+            )
+        }) {
+            features.push(VersionFeature::RequiredVideoLayout);
+        }
+
+        features
+    }
+}
+
+/// A set of features, which unlike a `Vec` can be built without allocating.
+#[derive(Default)]
+struct Features(u16);
+
+impl Features {
+    const fn push(&mut self, feature: VersionFeature) {
+        self.0 |= 1 << feature as u16;
+    }
+
+    /// Iterates over the features in the set, in order.
+    fn iter(&self) -> impl Iterator<Item = VersionFeature> + '_ {
+        VersionFeature::ALL
+            .into_iter()
+            .filter(|feature| self.0 & (1 << *feature as u16) != 0)
+    }
+
+    fn required_version(&self) -> u8 {
+        self.iter()
+            .last()
+            .map_or(1, VersionFeature::required_version)
+    }
+}
+
+fn encryption_features(method: &EncryptionMethod, features: &mut Features) {
+    if let EncryptionMethod::Aes128 { iv: Some(_), .. }
+    | EncryptionMethod::SampleAes { iv: Some(_), .. } = method
+    {
+        features.push(VersionFeature::EncryptionIv);
+    }
+    if let EncryptionMethod::Aes128 {
+        key_format: KeyFormat::Other(_),
+        ..
+    } = method
+    {
+        features.push(VersionFeature::KeyFormat);
+    }
+    if let EncryptionMethod::SampleAes { .. } = method {
+        features.push(VersionFeature::SampleAes);
+    }
+
+    let (EncryptionMethod::Aes128 {
+        key_format_versions,
+        ..
+    }
+    | EncryptionMethod::SampleAes {
+        key_format_versions,
+        ..
+    }
+    | EncryptionMethod::SampleAesCtr {
+        key_format_versions,
+        ..
+    }) = method;
+    if key_format_versions.iter().any(|version| *version != 1) {
+        features.push(VersionFeature::KeyFormatVersions);
+    }
+}
+
+fn definition_features(definitions: &[DefinitionType], features: &mut Features) {
+    if definitions
+        .iter()
+        .any(|definition| matches!(definition, DefinitionType::QueryParameter { .. }))
+    {
+        features.push(VersionFeature::QueryParameterDefinition);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SerializeError;
+
+    #[test]
+    fn version_features() {
+        let playlist = MediaPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-TARGETDURATION:6
+#EXT-X-DEFINE:QUERYPARAM="token"
+#EXT-X-SKIP:SKIPPED-SEGMENTS=3
+#EXT-X-KEY:METHOD=AES-128,URI="key.bin",IV=0x1,KEYFORMAT="com.example"
+#EXT-X-MAP:URI="init.mp4"
+#EXTINF:5.5,
+1.mp4
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            playlist.version_features(),
+            vec![
+                VersionFeature::EncryptionIv,
+                VersionFeature::FloatDuration,
+                VersionFeature::KeyFormat,
+                VersionFeature::Map,
+                VersionFeature::Skip,
+                VersionFeature::QueryParameterDefinition,
+            ]
+        );
+        assert_eq!(playlist.required_version(), 11);
+
+        let mut output = Vec::new();
+        let error = playlist
+            .serialize_with_max_version(6, &mut output)
+            .unwrap_err();
+        assert!(output.is_empty());
+        assert!(matches!(
+            error,
+            SerializeError::Invalid(features) if features == vec![
+                VersionFeature::Skip,
+                VersionFeature::QueryParameterDefinition,
+            ]
+        ));
+        playlist
+            .serialize_with_max_version(11, &mut output)
+            .unwrap();
+        assert!(output.starts_with(b"#EXTM3U\n#EXT-X-VERSION:11\n"));
+
+        let playlist = MultivariantPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-SESSION-KEY:METHOD=SAMPLE-AES,URI="key.bin"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,REQ-VIDEO-LAYOUT="CH-MONO"
+low.m3u8
+"#,
+        )
+        .unwrap();
+        assert_eq!(playlist.version_features(), vec![VersionFeature::SampleAes]);
+        assert_eq!(playlist.required_version(), 5);
+    }
+}
@@ -18,16 +18,34 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod pathway_clone;
+mod pathway_selection;
+mod query;
+
 use std::collections::HashMap;
-use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
 use std::io;
 
-use serde::ser::SerializeStruct;
-use serde::ser::Serializer;
-use serde::Serialize;
+use crate::pathway_id;
+
+use serde::de::{self, Deserializer, IgnoredAny, MapAccess, Unexpected, Visitor};
+use serde::ser::{self, SerializeStruct, Serializer};
+use serde::{Deserialize, Serialize};
+
+pub use pathway_clone::ApplyPathwayCloneError;
+pub use pathway_selection::PathwaySelection;
+pub use query::SteeringQuery;
+
+/// The ID of the pathway of every Variant Stream which has no pathway ID.
+pub const DEFAULT_PATHWAY_ID: &str = crate::DEFAULT_PATHWAY_ID;
 
 /// A steering manifest which identifies the available pathways
 /// and their priority order.
+///
+/// Unlike the playlist types, `SteeringManifest` deliberately does not
+/// implement [`std::fmt::Display`], because serializing an invalid manifest
+/// fails. Use [`SteeringManifest::to_json_string`] instead.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SteeringManifest {
     /// Specifies how many seconds the client must wait before
@@ -39,12 +57,233 @@ pub struct SteeringManifest {
     pub reload_uri: Option<String>,
 
     /// A list of pathway IDs order to most preferred to least preferred.
-    pub pathway_priority: HashSet<String>,
+    pub pathway_priority: PathwayPriority,
 
     /// A list of novel pathways made by cloning existing ones.
     pub pathway_clones: Vec<PathwayClone>,
 }
 
+/// A list of unique pathway IDs ordered from most preferred to least preferred.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PathwayPriority(Vec<String>);
+
+impl PathwayPriority {
+    /// Creates an empty `PathwayPriority`.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Appends a pathway ID as the least preferred pathway.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if `pathway_id` is already in the list.
+    pub fn push(&mut self, pathway_id: impl Into<String>) -> Result<(), DuplicatePathwayId> {
+        let pathway_id = pathway_id.into();
+        if self.contains(&pathway_id) {
+            return Err(DuplicatePathwayId(pathway_id));
+        }
+
+        self.0.push(pathway_id);
+        Ok(())
+    }
+
+    /// Returns true if `pathway_id` is in the list.
+    #[must_use]
+    pub fn contains(&self, pathway_id: &str) -> bool {
+        self.0.iter().any(|id| id == pathway_id)
+    }
+
+    /// Returns the number of pathway IDs in the list.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true if the list contains no pathway IDs.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the pathway IDs, from most preferred to
+    /// least preferred.
+    pub fn iter(&self) -> std::slice::Iter<'_, String> {
+        self.0.iter()
+    }
+
+    /// Returns the pathway IDs, from most preferred to least preferred.
+    #[must_use]
+    pub fn as_slice(&self) -> &[String] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<String>> for PathwayPriority {
+    type Error = DuplicatePathwayId;
+
+    fn try_from(pathway_ids: Vec<String>) -> Result<Self, Self::Error> {
+        for (i, id) in pathway_ids.iter().enumerate() {
+            if pathway_ids[..i].contains(id) {
+                return Err(DuplicatePathwayId(id.clone()));
+            }
+        }
+
+        Ok(Self(pathway_ids))
+    }
+}
+
+impl From<PathwayPriority> for Vec<String> {
+    fn from(priority: PathwayPriority) -> Self {
+        priority.0
+    }
+}
+
+impl<'a> IntoIterator for &'a PathwayPriority {
+    type Item = &'a String;
+    type IntoIter = std::slice::Iter<'a, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for PathwayPriority {
+    type Item = String;
+    type IntoIter = std::vec::IntoIter<String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// A pathway ID appeared more than once in a [`PathwayPriority`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicatePathwayId(pub String);
+
+impl fmt::Display for DuplicatePathwayId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pathway ID {:?} appears more than once", self.0)
+    }
+}
+
+impl Error for DuplicatePathwayId {}
+
+/// A requirement of the HLS spec which a [`SteeringManifest`] does not meet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SteeringManifestViolation {
+    /// [`SteeringManifest::ttl_seconds`] is zero.
+    ZeroTtl,
+
+    /// [`SteeringManifest::pathway_priority`] is empty.
+    EmptyPathwayPriority,
+
+    /// A pathway ID contains characters other than `a-z`, `A-Z`, `0-9`,
+    /// `.`, `-`, and `_`, or is empty.
+    InvalidPathwayId(String),
+
+    /// More than one [`PathwayClone`] has this ID.
+    DuplicateCloneId(String),
+
+    /// The `base_id` of a [`PathwayClone`] does not refer to a known pathway.
+    UnknownBaseId {
+        /// The ID of the clone.
+        clone_id: String,
+
+        /// The unknown base ID.
+        base_id: String,
+    },
+
+    /// The [`UriReplacement::host`] of a [`PathwayClone`] is an empty string.
+    EmptyHost {
+        /// The ID of the clone.
+        clone_id: String,
+    },
+
+    /// The [`UriReplacement::query_parameters`] of a [`PathwayClone`]
+    /// contain an empty key.
+    EmptyQueryParameterKey {
+        /// The ID of the clone.
+        clone_id: String,
+    },
+}
+
+impl fmt::Display for SteeringManifestViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroTtl => write!(f, "TTL must be greater than zero"),
+            Self::EmptyPathwayPriority => write!(f, "PATHWAY-PRIORITY must not be empty"),
+            Self::InvalidPathwayId(id) => write!(f, "{id:?} is not a valid pathway ID"),
+            Self::DuplicateCloneId(id) => {
+                write!(f, "more than one pathway clone has the ID {id:?}")
+            }
+            Self::UnknownBaseId { clone_id, base_id } => write!(
+                f,
+                "pathway clone {clone_id:?} has unknown BASE-ID {base_id:?}"
+            ),
+            Self::EmptyHost { clone_id } => {
+                write!(f, "pathway clone {clone_id:?} has an empty HOST")
+            }
+            Self::EmptyQueryParameterKey { clone_id } => {
+                write!(f, "pathway clone {clone_id:?} has an empty PARAMS key")
+            }
+        }
+    }
+}
+
+impl Error for SteeringManifestViolation {}
+
+/// An error encountered while serializing a [`SteeringManifest`].
+#[derive(Debug)]
+pub enum SerializeSteeringManifestError {
+    /// The manifest could not be written as json.
+    Json(serde_json::Error),
+
+    /// The manifest failed [`SteeringManifest::validate`].
+    Invalid(Vec<SteeringManifestViolation>),
+}
+
+impl fmt::Display for SerializeSteeringManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json(error) => write!(f, "failed to write steering manifest: {error}"),
+            Self::Invalid(violations) => {
+                write!(f, "invalid steering manifest")?;
+                for (i, violation) in violations.iter().enumerate() {
+                    let separator = if i == 0 { ": " } else { "; " };
+                    write!(f, "{separator}{violation}")?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for SerializeSteeringManifestError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Json(error) => Some(error),
+            Self::Invalid(_) => None,
+        }
+    }
+}
+
+fn has_pathway(playlist: &crate::playlist::MultivariantPlaylist, id: &str) -> bool {
+    playlist
+        .variant_streams
+        .iter()
+        .any(|variant| pathway_id(variant.stream_info.pathway_id.as_deref()) == id)
+}
+
+fn is_valid_pathway_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
 /// A way to introduce novel Pathways by cloning existing ones.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PathwayClone {
@@ -81,27 +320,169 @@ pub struct UriReplacement {
 
 impl SteeringManifest {
     /// Serializes the manifest into it's json representation.
-    /// Guaranteed to write valid UTF-8 only.
+    /// Guaranteed to write valid UTF-8 only. To serialize into a `String`,
+    /// use [`SteeringManifest::to_json_string`] instead.
     ///
     /// This does not percent encode [`UriReplacement::query_parameters`].
     ///
     /// # Errors
     ///
-    /// May return `Err` when encountering an io error on `output`.
+    /// Returns [`SerializeSteeringManifestError::Invalid`] without writing
+    /// anything if the manifest fails [`SteeringManifest::validate`], and
+    /// may return [`SerializeSteeringManifestError::Json`] when encountering
+    /// an io error on `output`.
+    pub fn serialize(&self, output: impl io::Write) -> Result<(), SerializeSteeringManifestError> {
+        serde_json::to_writer(output, self).map_err(|error| self.serialize_error(error))
+    }
+
+    /// Serializes the manifest like [`SteeringManifest::serialize`], but into
+    /// a `String`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializeSteeringManifestError::Invalid`] if the manifest
+    /// fails [`SteeringManifest::validate`].
+    pub fn to_json_string(&self) -> Result<String, SerializeSteeringManifestError> {
+        serde_json::to_string(self).map_err(|error| self.serialize_error(error))
+    }
+
+    /// Serializes the manifest like [`SteeringManifest::serialize`], but into
+    /// an async `output`.
+    ///
+    /// The whole manifest is encoded into a buffer first, so `output` only
+    /// receives a single large write.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializeSteeringManifestError::Invalid`] without writing
+    /// anything if the manifest fails [`SteeringManifest::validate`], and
+    /// may return [`SerializeSteeringManifestError::Json`] when encountering
+    /// an io error on `output`.
+    #[cfg(feature = "tokio")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+    pub async fn serialize_async(
+        &self,
+        mut output: impl tokio::io::AsyncWrite + Unpin,
+    ) -> Result<(), SerializeSteeringManifestError> {
+        use tokio::io::AsyncWriteExt;
+
+        let json = serde_json::to_vec(self).map_err(|error| self.serialize_error(error))?;
+
+        output
+            .write_all(&json)
+            .await
+            .map_err(|error| SerializeSteeringManifestError::Json(serde_json::Error::io(error)))
+    }
+
+    /// Turns an error from serializing the manifest into the violations which
+    /// caused it, since the [`Serialize`] impl only reports them as a message.
+    fn serialize_error(&self, error: serde_json::Error) -> SerializeSteeringManifestError {
+        match self.validate() {
+            Err(violations) if error.is_data() => {
+                SerializeSteeringManifestError::Invalid(violations)
+            }
+            _ => SerializeSteeringManifestError::Json(error),
+        }
+    }
+
+    /// Checks the manifest against the requirements of the HLS spec,
+    /// returning every requirement which is not met.
+    ///
+    /// The `BASE-ID` of a [`PathwayClone`] is only known if it appears in
+    /// [`SteeringManifest::pathway_priority`] or is the ID of an earlier clone,
+    /// since the manifest alone cannot see the pathways of the Multivariant
+    /// Playlist.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with at least one violation if the manifest is invalid.
+    pub fn validate(&self) -> Result<(), Vec<SteeringManifestViolation>> {
+        let mut violations = Vec::new();
+
+        if self.ttl_seconds == 0 {
+            violations.push(SteeringManifestViolation::ZeroTtl);
+        }
+
+        if self.pathway_priority.is_empty() {
+            violations.push(SteeringManifestViolation::EmptyPathwayPriority);
+        }
+        for id in &self.pathway_priority {
+            if !is_valid_pathway_id(id) {
+                violations.push(SteeringManifestViolation::InvalidPathwayId(id.clone()));
+            }
+        }
+
+        for (i, clone) in self.pathway_clones.iter().enumerate() {
+            if !is_valid_pathway_id(&clone.id) {
+                violations.push(SteeringManifestViolation::InvalidPathwayId(
+                    clone.id.clone(),
+                ));
+            }
+            if self.pathway_clones[..i]
+                .iter()
+                .any(|other| other.id == clone.id)
+            {
+                violations.push(SteeringManifestViolation::DuplicateCloneId(
+                    clone.id.clone(),
+                ));
+            }
+
+            let is_known_base_id = self.pathway_priority.contains(&clone.base_id)
+                || self.pathway_clones[..i]
+                    .iter()
+                    .any(|other| other.id == clone.base_id);
+            if !is_known_base_id {
+                violations.push(SteeringManifestViolation::UnknownBaseId {
+                    clone_id: clone.id.clone(),
+                    base_id: clone.base_id.clone(),
+                });
+            }
+
+            let replacement = &clone.uri_replacement;
+            if replacement.host.as_ref().is_some_and(String::is_empty) {
+                violations.push(SteeringManifestViolation::EmptyHost {
+                    clone_id: clone.id.clone(),
+                });
+            }
+            if replacement
+                .query_parameters
+                .as_ref()
+                .is_some_and(|params| params.contains_key(""))
+            {
+                violations.push(SteeringManifestViolation::EmptyQueryParameterKey {
+                    clone_id: clone.id.clone(),
+                });
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Parses a manifest from it's json representation in `input`.
     ///
-    /// # Panics
+    /// Unrecognized keys are ignored.
     ///
-    /// A number of invariants mirroring the requirements of the HLS spec
-    /// must be upheld when making a call to this method, or else it may
-    /// panic. They are listed below.
+    /// # Errors
     ///
-    /// * [`SteeringManifest::pathway_priority`] must be non-empty.
+    /// May return `Err` if `input` is not a valid steering manifest, including
+    /// when it's `VERSION` is not 1.
+    pub fn parse(input: &[u8]) -> Result<Self, serde_json::Error> {
+        serde_json::from_slice(input)
+    }
+
+    /// Reads a manifest from it's json representation in `reader`.
+    /// See [`SteeringManifest::parse`].
     ///
-    /// * [`UriReplacement::host`], if `Some`, must be non-empty.
+    /// # Errors
     ///
-    /// * [`UriReplacement::query_parameters`] must not contain a key which is empty.
-    pub fn serialize(&self, output: impl io::Write) -> Result<(), serde_json::Error> {
-        serde_json::to_writer(output, self)
+    /// May return `Err` when encountering an io error on `reader`, or if the
+    /// manifest is not valid.
+    pub fn from_reader(reader: impl io::Read) -> Result<Self, serde_json::Error> {
+        serde_json::from_reader(reader)
     }
 }
 
@@ -110,6 +491,12 @@ impl Serialize for SteeringManifest {
     where
         S: Serializer,
     {
+        if let Err(violations) = self.validate() {
+            return Err(ser::Error::custom(SerializeSteeringManifestError::Invalid(
+                violations,
+            )));
+        }
+
         let mut len_of_fields = 3;
         if self.reload_uri.is_some() {
             len_of_fields += 1;
@@ -124,12 +511,7 @@ impl Serialize for SteeringManifest {
         if let Some(reload_uri) = &self.reload_uri {
             manifest.serialize_field("RELOAD-URI", reload_uri)?;
         }
-
-        assert!(
-            !self.pathway_priority.is_empty(),
-            "Found an empty pathway priority list while serializing."
-        );
-        manifest.serialize_field("PATHWAY-PRIORITY", &self.pathway_priority)?;
+        manifest.serialize_field("PATHWAY-PRIORITY", self.pathway_priority.as_slice())?;
 
         if self.pathway_clones.is_empty() {
             return manifest.end();
@@ -175,20 +557,10 @@ impl Serialize for UriReplacement {
         let mut replacement = serializer.serialize_struct("UriReplacement", len_of_fields)?;
 
         if let Some(host) = &self.host {
-            assert!(
-                !host.is_empty(),
-                "Found an empty host string while serializing."
-            );
-
             replacement.serialize_field("HOST", host)?;
         }
 
         if let Some(params) = &self.query_parameters {
-            assert!(
-                !params.contains_key(""),
-                "Found an empty query parameter key while serializing."
-            );
-
             replacement.serialize_field("PARAMS", params)?;
         }
 
@@ -203,3 +575,439 @@ impl Serialize for UriReplacement {
         replacement.end()
     }
 }
+
+/// Deserializes the next value of `map` into `field`, failing if `field`
+/// has already been set by an earlier occurrence of the same key.
+fn next_value_once<'de, A, T>(
+    map: &mut A,
+    field: &mut Option<T>,
+    name: &'static str,
+) -> Result<(), A::Error>
+where
+    A: MapAccess<'de>,
+    T: Deserialize<'de>,
+{
+    if field.is_some() {
+        return Err(de::Error::duplicate_field(name));
+    }
+
+    *field = Some(map.next_value()?);
+    Ok(())
+}
+
+impl<'de> Deserialize<'de> for SteeringManifest {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SteeringManifestVisitor;
+
+        impl<'de> Visitor<'de> for SteeringManifestVisitor {
+            type Value = SteeringManifest;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a steering manifest object")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut version = None;
+                let mut ttl_seconds = None;
+                let mut reload_uri = None;
+                let mut pathway_priority = None;
+                let mut pathway_clones = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "VERSION" => next_value_once(&mut map, &mut version, "VERSION")?,
+                        "TTL" => next_value_once(&mut map, &mut ttl_seconds, "TTL")?,
+                        "RELOAD-URI" => next_value_once(&mut map, &mut reload_uri, "RELOAD-URI")?,
+                        "PATHWAY-PRIORITY" => {
+                            next_value_once(&mut map, &mut pathway_priority, "PATHWAY-PRIORITY")?;
+                        }
+                        "PATHWAY-CLONES" => {
+                            next_value_once(&mut map, &mut pathway_clones, "PATHWAY-CLONES")?;
+                        }
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                let version: u64 = version.ok_or_else(|| de::Error::missing_field("VERSION"))?;
+                if version != 1 {
+                    return Err(de::Error::invalid_value(
+                        Unexpected::Unsigned(version),
+                        &"steering manifest VERSION 1",
+                    ));
+                }
+
+                Ok(SteeringManifest {
+                    ttl_seconds: ttl_seconds.ok_or_else(|| de::Error::missing_field("TTL"))?,
+                    reload_uri,
+                    pathway_priority: pathway_priority
+                        .ok_or_else(|| de::Error::missing_field("PATHWAY-PRIORITY"))?,
+                    pathway_clones: pathway_clones.unwrap_or_default(),
+                })
+            }
+        }
+
+        deserializer.deserialize_struct(
+            "SteeringManifest",
+            &[
+                "VERSION",
+                "TTL",
+                "RELOAD-URI",
+                "PATHWAY-PRIORITY",
+                "PATHWAY-CLONES",
+            ],
+            SteeringManifestVisitor,
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for PathwayPriority {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<String>::deserialize(deserializer)?
+            .try_into()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for PathwayClone {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct PathwayCloneVisitor;
+
+        impl<'de> Visitor<'de> for PathwayCloneVisitor {
+            type Value = PathwayClone;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a pathway clone object")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut base_id = None;
+                let mut id = None;
+                let mut uri_replacement = None;
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "BASE-ID" => next_value_once(&mut map, &mut base_id, "BASE-ID")?,
+                        "ID" => next_value_once(&mut map, &mut id, "ID")?,
+                        "URI-REPLACEMENT" => {
+                            next_value_once(&mut map, &mut uri_replacement, "URI-REPLACEMENT")?;
+                        }
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                Ok(PathwayClone {
+                    base_id: base_id.ok_or_else(|| de::Error::missing_field("BASE-ID"))?,
+                    id: id.ok_or_else(|| de::Error::missing_field("ID"))?,
+                    uri_replacement: uri_replacement
+                        .ok_or_else(|| de::Error::missing_field("URI-REPLACEMENT"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct(
+            "PathwayClone",
+            &["BASE-ID", "ID", "URI-REPLACEMENT"],
+            PathwayCloneVisitor,
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for UriReplacement {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct UriReplacementVisitor;
+
+        impl<'de> Visitor<'de> for UriReplacementVisitor {
+            type Value = UriReplacement;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a URI replacement object")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut replacement = UriReplacement {
+                    host: None,
+                    query_parameters: None,
+                    per_variant_uris: None,
+                    per_rendition_uris: None,
+                };
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "HOST" => next_value_once(&mut map, &mut replacement.host, "HOST")?,
+                        "PARAMS" => {
+                            next_value_once(&mut map, &mut replacement.query_parameters, "PARAMS")?;
+                        }
+                        "PER-VARIANT-URIS" => next_value_once(
+                            &mut map,
+                            &mut replacement.per_variant_uris,
+                            "PER-VARIANT-URIS",
+                        )?,
+                        "PER-RENDITION-URIS" => next_value_once(
+                            &mut map,
+                            &mut replacement.per_rendition_uris,
+                            "PER-RENDITION-URIS",
+                        )?,
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                Ok(replacement)
+            }
+        }
+
+        deserializer.deserialize_struct(
+            "UriReplacement",
+            &["HOST", "PARAMS", "PER-VARIANT-URIS", "PER-RENDITION-URIS"],
+            UriReplacementVisitor,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_steering_manifest() {
+        let manifest = SteeringManifest::parse(
+            br#"{
+                "VERSION": 1,
+                "TTL": 300,
+                "RELOAD-URI": "https://example.com/steering?video=00012&session=123",
+                "PATHWAY-PRIORITY": ["CDN-A", "CDN-B"],
+                "PATHWAY-CLONES": [
+                    {
+                        "BASE-ID": "CDN-A",
+                        "ID": "CDN-C",
+                        "URI-REPLACEMENT": {
+                            "HOST": "backup.example.com",
+                            "PARAMS": { "token": "abc" },
+                            "PER-VARIANT-URIS": { "hi": "https://example.com/hi.m3u8" }
+                        }
+                    }
+                ],
+                "SOMETHING-NEW": { "IGNORED": true }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            manifest,
+            SteeringManifest {
+                ttl_seconds: 300,
+                reload_uri: Some("https://example.com/steering?video=00012&session=123".into()),
+                pathway_priority: vec!["CDN-A".into(), "CDN-B".into()].try_into().unwrap(),
+                pathway_clones: vec![PathwayClone {
+                    base_id: "CDN-A".into(),
+                    id: "CDN-C".into(),
+                    uri_replacement: UriReplacement {
+                        host: Some("backup.example.com".into()),
+                        query_parameters: Some(HashMap::from([("token".into(), "abc".into())])),
+                        per_variant_uris: Some(HashMap::from([(
+                            "hi".into(),
+                            "https://example.com/hi.m3u8".into()
+                        )])),
+                        per_rendition_uris: None,
+                    },
+                }],
+            }
+        );
+
+        let mut output = Vec::new();
+        manifest.serialize(&mut output).unwrap();
+        assert_eq!(SteeringManifest::parse(&output).unwrap(), manifest);
+        assert_eq!(
+            manifest.to_json_string().unwrap().as_bytes(),
+            output.as_slice()
+        );
+    }
+
+    #[test]
+    fn pathway_priority_order() {
+        let mut pathway_priority = PathwayPriority::new();
+        for id in ["CDN-Z", "CDN-A", "CDN-M"] {
+            pathway_priority.push(id).unwrap();
+        }
+        assert_eq!(
+            pathway_priority.push("CDN-A"),
+            Err(DuplicatePathwayId("CDN-A".into()))
+        );
+        assert_eq!(
+            PathwayPriority::try_from(vec!["CDN-A".into(), "CDN-B".into(), "CDN-A".into()]),
+            Err(DuplicatePathwayId("CDN-A".into()))
+        );
+
+        let manifest = SteeringManifest {
+            ttl_seconds: 300,
+            reload_uri: None,
+            pathway_priority,
+            pathway_clones: vec![],
+        };
+        let mut output = Vec::new();
+        manifest.serialize(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"{"VERSION":1,"TTL":300,"PATHWAY-PRIORITY":["CDN-Z","CDN-A","CDN-M"]}"#
+        );
+
+        assert!(SteeringManifest::parse(
+            br#"{"VERSION": 1, "TTL": 300, "PATHWAY-PRIORITY": ["CDN-A", "CDN-A"]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn validate_steering_manifest() {
+        let mut manifest = SteeringManifest {
+            ttl_seconds: 0,
+            reload_uri: None,
+            pathway_priority: PathwayPriority::new(),
+            pathway_clones: vec![],
+        };
+        assert_eq!(
+            manifest.validate(),
+            Err(vec![
+                SteeringManifestViolation::ZeroTtl,
+                SteeringManifestViolation::EmptyPathwayPriority
+            ])
+        );
+        let mut output = Vec::new();
+        assert!(matches!(
+            manifest.serialize(&mut output),
+            Err(SerializeSteeringManifestError::Invalid(violations)) if violations.len() == 2
+        ));
+        assert!(output.is_empty());
+        assert!(matches!(
+            manifest.to_json_string(),
+            Err(SerializeSteeringManifestError::Invalid(violations)) if violations.len() == 2
+        ));
+        assert!(serde_json::to_string(&manifest).is_err());
+
+        let uri_replacement = UriReplacement {
+            host: Some(String::new()),
+            query_parameters: Some(HashMap::from([(String::new(), "a".into())])),
+            per_variant_uris: None,
+            per_rendition_uris: None,
+        };
+        manifest.ttl_seconds = 300;
+        manifest.pathway_priority = vec!["CDN-A".into(), "CDN B".into()].try_into().unwrap();
+        manifest.pathway_clones = vec![
+            PathwayClone {
+                base_id: "CDN-A".into(),
+                id: "CDN-C".into(),
+                uri_replacement: uri_replacement.clone(),
+            },
+            PathwayClone {
+                base_id: "CDN-C".into(),
+                id: "CDN-C".into(),
+                uri_replacement: UriReplacement {
+                    host: None,
+                    query_parameters: None,
+                    ..uri_replacement
+                },
+            },
+            PathwayClone {
+                base_id: "CDN-Z".into(),
+                id: "CDN-D".into(),
+                uri_replacement: UriReplacement {
+                    host: None,
+                    query_parameters: None,
+                    per_variant_uris: None,
+                    per_rendition_uris: None,
+                },
+            },
+        ];
+        assert_eq!(
+            manifest.validate(),
+            Err(vec![
+                SteeringManifestViolation::InvalidPathwayId("CDN B".into()),
+                SteeringManifestViolation::EmptyHost {
+                    clone_id: "CDN-C".into()
+                },
+                SteeringManifestViolation::EmptyQueryParameterKey {
+                    clone_id: "CDN-C".into()
+                },
+                SteeringManifestViolation::DuplicateCloneId("CDN-C".into()),
+                SteeringManifestViolation::UnknownBaseId {
+                    clone_id: "CDN-D".into(),
+                    base_id: "CDN-Z".into()
+                },
+            ])
+        );
+
+        let clone = |base_id: &str, id: &str| PathwayClone {
+            base_id: base_id.into(),
+            id: id.into(),
+            uri_replacement: UriReplacement {
+                host: Some("b.example.com".into()),
+                query_parameters: None,
+                per_variant_uris: None,
+                per_rendition_uris: None,
+            },
+        };
+        manifest.pathway_priority = vec!["CDN-A".into()].try_into().unwrap();
+        manifest.pathway_clones = vec![clone("CDN-A", "CDN-B"), clone("CDN-B", "CDN-C")];
+        assert_eq!(manifest.validate(), Ok(()));
+
+        manifest.pathway_clones = vec![clone("CDN-C", "CDN-B"), clone("CDN-B", "CDN-C")];
+        assert_eq!(
+            manifest.validate(),
+            Err(vec![SteeringManifestViolation::UnknownBaseId {
+                clone_id: "CDN-B".into(),
+                base_id: "CDN-C".into()
+            }])
+        );
+    }
+
+    #[test]
+    fn parse_steering_manifest_errors() {
+        let error = SteeringManifest::parse(
+            br#"{"VERSION": 2, "TTL": 300, "PATHWAY-PRIORITY": ["CDN-A"]}"#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("VERSION 1"), "{error}");
+
+        assert!(
+            SteeringManifest::parse(br#"{"TTL": 300, "PATHWAY-PRIORITY": ["CDN-A"]}"#).is_err()
+        );
+        assert!(
+            SteeringManifest::parse(br#"{"VERSION": 1, "PATHWAY-PRIORITY": ["CDN-A"]}"#).is_err()
+        );
+        assert!(SteeringManifest::parse(
+            br#"{"VERSION": 1, "TTL": 1, "TTL": 2, "PATHWAY-PRIORITY": ["CDN-A"]}"#
+        )
+        .is_err());
+        assert!(SteeringManifest::parse(
+            br#"{"VERSION": 1, "TTL": 1, "PATHWAY-PRIORITY": ["CDN-A"],
+                "PATHWAY-CLONES": [{"BASE-ID": "CDN-A", "URI-REPLACEMENT": {}}]}"#
+        )
+        .is_err());
+    }
+}
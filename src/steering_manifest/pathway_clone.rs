@@ -0,0 +1,628 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use url::Url;
+
+use super::{has_pathway, pathway_id, PathwayClone, SteeringManifest, UriReplacement};
+use crate::playlist::{MultivariantPlaylist, RenditionGroup};
+
+/// An error encountered while applying a [`PathwayClone`] to a
+/// [`MultivariantPlaylist`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyPathwayCloneError {
+    /// The playlist already has a pathway with the ID of the clone.
+    PathwayExists(String),
+
+    /// The playlist has no Variant Streams on the base pathway of the clone.
+    UnknownBasePathway(String),
+
+    /// A URI could not be resolved against the URI of the playlist.
+    InvalidUri {
+        /// The URI which could not be resolved.
+        uri: String,
+
+        /// Why the URI could not be resolved.
+        error: url::ParseError,
+    },
+
+    /// The host of a URI could not be replaced with [`UriReplacement::host`].
+    InvalidHost {
+        /// The URI whose host could not be replaced.
+        uri: String,
+
+        /// Why the host could not be replaced.
+        error: url::ParseError,
+    },
+}
+
+impl fmt::Display for ApplyPathwayCloneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PathwayExists(id) => write!(f, "pathway {id:?} already exists"),
+            Self::UnknownBasePathway(id) => {
+                write!(f, "no variant streams are on the base pathway {id:?}")
+            }
+            Self::InvalidUri { uri, error } => write!(f, "could not resolve URI {uri:?}: {error}"),
+            Self::InvalidHost { uri, error } => {
+                write!(f, "could not replace the host of URI {uri:?}: {error}")
+            }
+        }
+    }
+}
+
+impl Error for ApplyPathwayCloneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidUri { error, .. } | Self::InvalidHost { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl SteeringManifest {
+    /// Applies every [`PathwayClone`] in the manifest to `playlist`, in order,
+    /// as a client would. See [`PathwayClone::apply`].
+    ///
+    /// Clones whose ID is already a pathway in `playlist` are skipped, so
+    /// applying the same manifest twice has no further effect.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` on the first clone which could not be applied. Clones
+    /// before it will have been applied to `playlist`.
+    pub fn apply_pathway_clones(
+        &self,
+        playlist: &mut MultivariantPlaylist,
+        playlist_uri: &Url,
+    ) -> Result<(), ApplyPathwayCloneError> {
+        for clone in &self.pathway_clones {
+            if has_pathway(playlist, &clone.id) {
+                continue;
+            }
+
+            clone.apply(playlist, playlist_uri)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PathwayClone {
+    /// Creates a new pathway in `playlist` by copying every [`VariantStream`]
+    /// and I-frame stream on the base pathway, along with the video, audio,
+    /// and subtitle [`RenditionGroup`]s they refer to, and applying the URI
+    /// replacement rules to the copies. Relative URIs are resolved against
+    /// `playlist_uri` before their host or query is replaced.
+    ///
+    /// Copied rendition groups are given the group ID `<group id>-<clone id>`,
+    /// followed by `-2`, `-3` and so on if `playlist` already has a group of
+    /// the same type with that ID.
+    /// Closed caption groups carry no URIs, so the copies keep referring to the
+    /// original groups. Variant Streams without a pathway ID are on the
+    /// [`super::DEFAULT_PATHWAY_ID`] pathway.
+    ///
+    /// [`VariantStream`]: crate::playlist::VariantStream
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the pathway already exists, if the base pathway does
+    /// not, or if a URI could not be rewritten. `playlist` is left unchanged
+    /// on error.
+    pub fn apply(
+        &self,
+        playlist: &mut MultivariantPlaylist,
+        playlist_uri: &Url,
+    ) -> Result<(), ApplyPathwayCloneError> {
+        if has_pathway(playlist, &self.id) {
+            return Err(ApplyPathwayCloneError::PathwayExists(self.id.clone()));
+        }
+
+        let replacement = &self.uri_replacement;
+        let mut cloned_groups = ClonedGroups::default();
+        let mut variant_streams = Vec::new();
+        for variant in &playlist.variant_streams {
+            if pathway_id(variant.stream_info.pathway_id.as_deref()) != self.base_id {
+                continue;
+            }
+
+            let mut variant = variant.clone();
+            variant.stream_info.pathway_id = Some(self.id.clone());
+            variant.uri = replacement.replace_variant_uri(
+                &variant.uri,
+                variant.stream_info.stable_variant_id.as_deref(),
+                playlist_uri,
+            )?;
+
+            for (group_id, kind) in [
+                (&mut variant.video_group_id, GroupKind::Video),
+                (&mut variant.audio_group_id, GroupKind::Audio),
+                (&mut variant.subtitles_group_id, GroupKind::Subtitles),
+            ] {
+                if let Some(group_id) = group_id {
+                    *group_id = self.clone_rendition_group(
+                        &playlist.renditions_groups,
+                        &mut cloned_groups,
+                        kind,
+                        group_id,
+                        playlist_uri,
+                    )?;
+                }
+            }
+
+            variant_streams.push(variant);
+        }
+
+        if variant_streams.is_empty() {
+            return Err(ApplyPathwayCloneError::UnknownBasePathway(
+                self.base_id.clone(),
+            ));
+        }
+
+        let mut i_frame_streams = Vec::new();
+        for stream in &playlist.i_frame_streams {
+            if pathway_id(stream.stream_info.pathway_id.as_deref()) != self.base_id {
+                continue;
+            }
+
+            let mut stream = stream.clone();
+            stream.stream_info.pathway_id = Some(self.id.clone());
+            stream.uri = replacement.replace_variant_uri(
+                &stream.uri,
+                stream.stream_info.stable_variant_id.as_deref(),
+                playlist_uri,
+            )?;
+            if let Some(group_id) = &mut stream.video_group_id {
+                *group_id = self.clone_rendition_group(
+                    &playlist.renditions_groups,
+                    &mut cloned_groups,
+                    GroupKind::Video,
+                    group_id,
+                    playlist_uri,
+                )?;
+            }
+
+            i_frame_streams.push(stream);
+        }
+
+        playlist.renditions_groups.extend(cloned_groups.groups);
+        playlist.variant_streams.extend(variant_streams);
+        playlist.i_frame_streams.extend(i_frame_streams);
+
+        Ok(())
+    }
+
+    /// Copies the group of `kind` in `groups` with `group_id` into
+    /// `cloned_groups`, unless it was already copied, and returns the group ID
+    /// of the copy.
+    ///
+    /// Group IDs which don't refer to a group are returned unchanged.
+    fn clone_rendition_group(
+        &self,
+        groups: &[RenditionGroup],
+        cloned_groups: &mut ClonedGroups,
+        kind: GroupKind,
+        group_id: &str,
+        playlist_uri: &Url,
+    ) -> Result<String, ApplyPathwayCloneError> {
+        if let Some(cloned_group_id) = cloned_groups.ids.get(&(kind, group_id.into())) {
+            return Ok(cloned_group_id.clone());
+        }
+
+        let Some(group) = groups.iter().find(|group| kind.matches(group, group_id)) else {
+            return Ok(group_id.into());
+        };
+
+        let is_taken = |id: &str| {
+            groups
+                .iter()
+                .chain(&cloned_groups.groups)
+                .any(|group| kind.matches(group, id))
+        };
+        let mut cloned_group_id = format!("{group_id}-{}", self.id);
+        let mut suffix = 2;
+        while is_taken(&cloned_group_id) {
+            cloned_group_id = format!("{group_id}-{}-{suffix}", self.id);
+            suffix += 1;
+        }
+
+        let replacement = &self.uri_replacement;
+        let mut group = group.clone();
+        match &mut group {
+            RenditionGroup::Video {
+                group_id,
+                renditions,
+            } => {
+                group_id.clone_from(&cloned_group_id);
+                for rendition in renditions {
+                    if let Some(uri) = &mut rendition.uri {
+                        *uri = replacement.replace_rendition_uri(
+                            uri,
+                            rendition.info.stable_rendition_id.as_deref(),
+                            playlist_uri,
+                        )?;
+                    }
+                }
+            }
+            RenditionGroup::Audio {
+                group_id,
+                renditions,
+            } => {
+                group_id.clone_from(&cloned_group_id);
+                for rendition in renditions {
+                    if let Some(uri) = &mut rendition.uri {
+                        *uri = replacement.replace_rendition_uri(
+                            uri,
+                            rendition.info.stable_rendition_id.as_deref(),
+                            playlist_uri,
+                        )?;
+                    }
+                }
+            }
+            RenditionGroup::Subtitles {
+                group_id,
+                renditions,
+            } => {
+                group_id.clone_from(&cloned_group_id);
+                for rendition in renditions {
+                    rendition.uri = replacement.replace_rendition_uri(
+                        &rendition.uri,
+                        rendition.info.stable_rendition_id.as_deref(),
+                        playlist_uri,
+                    )?;
+                }
+            }
+            RenditionGroup::ClosedCaptions { .. } => unreachable!(),
+        }
+
+        cloned_groups.groups.push(group);
+        cloned_groups
+            .ids
+            .insert((kind, group_id.into()), cloned_group_id.clone());
+        Ok(cloned_group_id)
+    }
+}
+
+impl UriReplacement {
+    fn replace_variant_uri(
+        &self,
+        uri: &str,
+        stable_variant_id: Option<&str>,
+        playlist_uri: &Url,
+    ) -> Result<String, ApplyPathwayCloneError> {
+        self.replace_uri(
+            uri,
+            stable_variant_id,
+            self.per_variant_uris.as_ref(),
+            playlist_uri,
+        )
+    }
+
+    fn replace_rendition_uri(
+        &self,
+        uri: &str,
+        stable_rendition_id: Option<&str>,
+        playlist_uri: &Url,
+    ) -> Result<String, ApplyPathwayCloneError> {
+        self.replace_uri(
+            uri,
+            stable_rendition_id,
+            self.per_rendition_uris.as_ref(),
+            playlist_uri,
+        )
+    }
+
+    /// Applies the replacement rules to `uri`. A matching entry in
+    /// `per_id_uris` replaces the URI outright, otherwise the host and query
+    /// parameters are replaced. Query parameters which already exist in the
+    /// URI are removed, and the new ones are appended after the remaining
+    /// parameters, which are kept exactly as written.
+    fn replace_uri(
+        &self,
+        uri: &str,
+        stable_id: Option<&str>,
+        per_id_uris: Option<&HashMap<String, String>>,
+        playlist_uri: &Url,
+    ) -> Result<String, ApplyPathwayCloneError> {
+        if let Some(uri) = stable_id
+            .zip(per_id_uris)
+            .and_then(|(id, uris)| uris.get(id))
+        {
+            return Ok(uri.clone());
+        }
+
+        let params = self
+            .query_parameters
+            .as_ref()
+            .filter(|params| !params.is_empty());
+        if self.host.is_none() && params.is_none() {
+            return Ok(uri.into());
+        }
+
+        let mut url =
+            playlist_uri
+                .join(uri)
+                .map_err(|error| ApplyPathwayCloneError::InvalidUri {
+                    uri: uri.into(),
+                    error,
+                })?;
+
+        if let Some(host) = &self.host {
+            let (host, port) = split_port(host);
+            let invalid_host = |error| ApplyPathwayCloneError::InvalidHost {
+                uri: uri.into(),
+                error,
+            };
+            url.set_host(Some(host)).map_err(invalid_host)?;
+            if let Some(port) = port {
+                url.set_port(Some(port))
+                    .map_err(|()| invalid_host(url::ParseError::InvalidPort))?;
+            }
+        }
+
+        if let Some(params) = params {
+            let mut params = params.iter().collect::<Vec<_>>();
+            params.sort();
+
+            // Keep the other pairs exactly as they were written, like
+            // `SteeringQuery::apply` does.
+            let query = url
+                .query()
+                .unwrap_or_default()
+                .split('&')
+                .filter(|pair| {
+                    let name = url::form_urlencoded::parse(pair.as_bytes())
+                        .next()
+                        .map(|(name, _)| name);
+                    name.is_some_and(|name| !params.iter().any(|(param, _)| **param == name))
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            url.set_query((!query.is_empty()).then_some(&query));
+            url.query_pairs_mut().extend_pairs(params);
+        }
+
+        Ok(url.into())
+    }
+}
+
+/// Splits the port off a [`UriReplacement::host`] like `cdn.example.com:8443`.
+fn split_port(host: &str) -> (&str, Option<u16>) {
+    host.rsplit_once(':')
+        // A colon inside an IPv6 address is not a port separator.
+        .filter(|(host, _)| !host.contains(':') || host.ends_with(']'))
+        .and_then(|(host, port)| Some((host, Some(port.parse().ok()?))))
+        .unwrap_or((host, None))
+}
+
+/// The rendition groups copied while applying a [`PathwayClone`].
+#[derive(Default)]
+struct ClonedGroups {
+    groups: Vec<RenditionGroup>,
+
+    /// The group ID of each copy, by the kind and group ID of the original.
+    ids: HashMap<(GroupKind, String), String>,
+}
+
+/// The kinds of [`RenditionGroup`] which are copied along with a pathway.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum GroupKind {
+    Video,
+    Audio,
+    Subtitles,
+}
+
+impl GroupKind {
+    fn matches(self, group: &RenditionGroup, id: &str) -> bool {
+        match (self, group) {
+            (Self::Video, RenditionGroup::Video { group_id, .. })
+            | (Self::Audio, RenditionGroup::Audio { group_id, .. })
+            | (Self::Subtitles, RenditionGroup::Subtitles { group_id, .. }) => group_id == id,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAYLIST: &[u8] = br#"#EXTM3U
+#EXT-X-CONTENT-STEERING:SERVER-URI="/steering"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac",NAME="English",STABLE-RENDITION-ID="en",URI="audio/en.m3u8"
+#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID="aac",NAME="Deutsch",STABLE-RENDITION-ID="de",URI="audio/de.m3u8"
+#EXT-X-MEDIA:TYPE=CLOSED-CAPTIONS,GROUP-ID="cc",NAME="English",INSTREAM-ID="CC1"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,AUDIO="aac",CLOSED-CAPTIONS="cc",STABLE-VARIANT-ID="lo",PATHWAY-ID="CDN-A"
+low.m3u8?token=old
+#EXT-X-STREAM-INF:BANDWIDTH=2560000,AUDIO="aac",CLOSED-CAPTIONS="cc",STABLE-VARIANT-ID="hi",PATHWAY-ID="CDN-A"
+https://a.example.com/high.m3u8
+#EXT-X-I-FRAME-STREAM-INF:BANDWIDTH=86000,PATHWAY-ID="CDN-A",URI="iframe.m3u8"
+"#;
+
+    fn clone(uri_replacement: UriReplacement) -> PathwayClone {
+        PathwayClone {
+            base_id: "CDN-A".into(),
+            id: "CDN-B".into(),
+            uri_replacement,
+        }
+    }
+
+    #[test]
+    fn apply_pathway_clone() {
+        let playlist_uri = Url::parse("https://a.example.com/video/main.m3u8").unwrap();
+        let mut playlist = MultivariantPlaylist::parse(PLAYLIST).unwrap();
+        clone(UriReplacement {
+            host: Some("b.example.com".into()),
+            query_parameters: Some(HashMap::from([("token".into(), "new".into())])),
+            per_variant_uris: Some(HashMap::from([(
+                "hi".into(),
+                "https://c.example.com/high.m3u8".into(),
+            )])),
+            per_rendition_uris: Some(HashMap::from([(
+                "de".into(),
+                "https://c.example.com/de.m3u8".into(),
+            )])),
+        })
+        .apply(&mut playlist, &playlist_uri)
+        .unwrap();
+
+        assert_eq!(playlist.variant_streams.len(), 4);
+        let low = &playlist.variant_streams[2];
+        assert_eq!(low.stream_info.pathway_id.as_deref(), Some("CDN-B"));
+        assert_eq!(low.uri, "https://b.example.com/video/low.m3u8?token=new");
+        assert_eq!(low.audio_group_id.as_deref(), Some("aac-CDN-B"));
+        assert_eq!(low.closed_captions_group_id.as_deref(), Some("cc"));
+        let high = &playlist.variant_streams[3];
+        assert_eq!(high.uri, "https://c.example.com/high.m3u8");
+        assert_eq!(high.audio_group_id.as_deref(), Some("aac-CDN-B"));
+
+        assert_eq!(playlist.renditions_groups.len(), 3);
+        let RenditionGroup::Audio {
+            group_id,
+            renditions,
+        } = &playlist.renditions_groups[2]
+        else {
+            panic!("expected a cloned audio group");
+        };
+        assert_eq!(group_id, "aac-CDN-B");
+        assert_eq!(
+            renditions[0].uri.as_deref(),
+            Some("https://b.example.com/video/audio/en.m3u8?token=new")
+        );
+        assert_eq!(
+            renditions[1].uri.as_deref(),
+            Some("https://c.example.com/de.m3u8")
+        );
+
+        assert_eq!(playlist.i_frame_streams.len(), 2);
+        assert_eq!(
+            playlist.i_frame_streams[1].uri,
+            "https://b.example.com/video/iframe.m3u8?token=new"
+        );
+    }
+
+    #[test]
+    fn apply_pathway_clone_avoids_existing_group_ids() {
+        let playlist_uri = Url::parse("https://a.example.com/main.m3u8").unwrap();
+        let mut playlist = MultivariantPlaylist::parse(PLAYLIST).unwrap();
+        let mut taken = playlist.renditions_groups[0].clone();
+        if let RenditionGroup::Audio { group_id, .. } = &mut taken {
+            *group_id = "aac-CDN-B".into();
+        }
+        playlist.renditions_groups.push(taken);
+
+        clone(UriReplacement {
+            host: Some("b.example.com:8443".into()),
+            query_parameters: None,
+            per_variant_uris: None,
+            per_rendition_uris: None,
+        })
+        .apply(&mut playlist, &playlist_uri)
+        .unwrap();
+
+        let low = &playlist.variant_streams[2];
+        assert_eq!(low.uri, "https://b.example.com:8443/low.m3u8?token=old");
+        assert_eq!(low.audio_group_id.as_deref(), Some("aac-CDN-B-2"));
+        assert_eq!(
+            playlist.variant_streams[3].audio_group_id.as_deref(),
+            Some("aac-CDN-B-2")
+        );
+        assert_eq!(playlist.renditions_groups.len(), 4);
+
+        let replacement = UriReplacement {
+            host: None,
+            query_parameters: Some(HashMap::from([("token".into(), "new value".into())])),
+            per_variant_uris: None,
+            per_rendition_uris: None,
+        };
+        assert_eq!(
+            replacement
+                .replace_uri(
+                    "low.m3u8?x=a%20b&token=old&y=c~d",
+                    None,
+                    None,
+                    &playlist_uri
+                )
+                .unwrap(),
+            "https://a.example.com/low.m3u8?x=a%20b&y=c~d&token=new+value"
+        );
+
+        assert_eq!(split_port("[::1]:8443"), ("[::1]", Some(8443)));
+        assert_eq!(split_port("[::1]"), ("[::1]", None));
+        assert_eq!(split_port("cdn.example.com"), ("cdn.example.com", None));
+    }
+
+    #[test]
+    fn apply_pathway_clones() {
+        let playlist_uri = Url::parse("https://a.example.com/main.m3u8").unwrap();
+        let mut playlist = MultivariantPlaylist::parse(PLAYLIST).unwrap();
+        let manifest = SteeringManifest {
+            ttl_seconds: 300,
+            reload_uri: None,
+            pathway_priority: vec!["CDN-C".into(), "CDN-A".into()].try_into().unwrap(),
+            pathway_clones: vec![
+                clone(UriReplacement {
+                    host: Some("b.example.com".into()),
+                    query_parameters: None,
+                    per_variant_uris: None,
+                    per_rendition_uris: None,
+                }),
+                PathwayClone {
+                    base_id: "CDN-B".into(),
+                    id: "CDN-C".into(),
+                    uri_replacement: UriReplacement {
+                        host: None,
+                        query_parameters: None,
+                        per_variant_uris: None,
+                        per_rendition_uris: None,
+                    },
+                },
+            ],
+        };
+
+        manifest
+            .apply_pathway_clones(&mut playlist, &playlist_uri)
+            .unwrap();
+        let applied = playlist.clone();
+        manifest
+            .apply_pathway_clones(&mut playlist, &playlist_uri)
+            .unwrap();
+        assert_eq!(playlist, applied);
+
+        assert_eq!(playlist.variant_streams.len(), 6);
+        assert_eq!(
+            playlist.variant_streams[4].uri,
+            "https://b.example.com/low.m3u8?token=old"
+        );
+        assert_eq!(
+            playlist.variant_streams[4].audio_group_id.as_deref(),
+            Some("aac-CDN-B-CDN-C")
+        );
+
+        assert_eq!(
+            manifest.pathway_clones[0].apply(&mut playlist, &playlist_uri),
+            Err(ApplyPathwayCloneError::PathwayExists("CDN-B".into()))
+        );
+        let mut unknown_base = manifest.pathway_clones[0].clone();
+        unknown_base.base_id = "CDN-Z".into();
+        unknown_base.id = "CDN-D".into();
+        assert_eq!(
+            unknown_base.apply(&mut playlist, &playlist_uri),
+            Err(ApplyPathwayCloneError::UnknownBasePathway("CDN-Z".into()))
+        );
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+use super::{has_pathway, pathway_id, SteeringManifest};
+use crate::playlist::{MultivariantPlaylist, VariantStream};
+use crate::ContentSteering;
+
+/// The pathway a client should play, and the Variant Streams on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathwaySelection<'a> {
+    /// The ID of the selected pathway.
+    pub pathway_id: &'a str,
+
+    /// Every [`VariantStream`] on the selected pathway, in playlist order.
+    pub variant_streams: Vec<&'a VariantStream>,
+}
+
+impl<'a> PathwaySelection<'a> {
+    fn new(playlist: &'a MultivariantPlaylist, id: &'a str) -> Self {
+        Self {
+            pathway_id: id,
+            variant_streams: playlist
+                .variant_streams
+                .iter()
+                .filter(|variant| pathway_id(variant.stream_info.pathway_id.as_deref()) == id)
+                .collect(),
+        }
+    }
+}
+
+impl SteeringManifest {
+    /// Selects the pathway of `playlist` a client should play.
+    ///
+    /// This is the most preferred pathway in
+    /// [`SteeringManifest::pathway_priority`] which is in `playlist` and not in
+    /// `failed_pathways`. Pathway clones are only considered once they have been
+    /// applied with [`SteeringManifest::apply_pathway_clones`].
+    ///
+    /// Returns `None` if every pathway in the priority list has failed or is
+    /// missing from `playlist`, since the steering server has not allowed any
+    /// of the other pathways.
+    #[must_use]
+    pub fn select_pathway<'a>(
+        &'a self,
+        playlist: &'a MultivariantPlaylist,
+        failed_pathways: &HashSet<String>,
+    ) -> Option<PathwaySelection<'a>> {
+        self.pathway_priority
+            .iter()
+            .map(String::as_str)
+            .find(|id| !failed_pathways.contains(*id) && has_pathway(playlist, id))
+            .map(|id| PathwaySelection::new(playlist, id))
+    }
+}
+
+impl ContentSteering {
+    /// Selects the pathway of `playlist` a client should play before it has
+    /// loaded the [`SteeringManifest`].
+    ///
+    /// This is [`ContentSteering::pathway_id`] if it is in `playlist` and not
+    /// in `failed_pathways`, and otherwise the first pathway in `playlist`
+    /// which has not failed. Returns `None` if every pathway has failed.
+    #[must_use]
+    pub fn select_initial_pathway<'a>(
+        &'a self,
+        playlist: &'a MultivariantPlaylist,
+        failed_pathways: &HashSet<String>,
+    ) -> Option<PathwaySelection<'a>> {
+        self.pathway_id
+            .as_deref()
+            .filter(|id| !failed_pathways.contains(*id) && has_pathway(playlist, id))
+            .or_else(|| first_available_pathway(playlist, failed_pathways))
+            .map(|id| PathwaySelection::new(playlist, id))
+    }
+}
+
+fn first_available_pathway<'a>(
+    playlist: &'a MultivariantPlaylist,
+    failed_pathways: &HashSet<String>,
+) -> Option<&'a str> {
+    playlist
+        .variant_streams
+        .iter()
+        .map(|variant| pathway_id(variant.stream_info.pathway_id.as_deref()))
+        .find(|id| !failed_pathways.contains(*id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playlist() -> MultivariantPlaylist {
+        MultivariantPlaylist::parse(
+            br#"#EXTM3U
+#EXT-X-CONTENT-STEERING:SERVER-URI="/steering",PATHWAY-ID="CDN-B"
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,PATHWAY-ID="CDN-A"
+a/low.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=2560000,PATHWAY-ID="CDN-A"
+a/high.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=1280000,PATHWAY-ID="CDN-B"
+b/low.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=1280000
+default/low.m3u8
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn select_pathway() {
+        let playlist = playlist();
+        let manifest = SteeringManifest {
+            ttl_seconds: 300,
+            reload_uri: None,
+            pathway_priority: vec!["CDN-Z".into(), "CDN-A".into(), "CDN-B".into()]
+                .try_into()
+                .unwrap(),
+            pathway_clones: vec![],
+        };
+
+        let selection = manifest.select_pathway(&playlist, &HashSet::new()).unwrap();
+        assert_eq!(selection.pathway_id, "CDN-A");
+        assert_eq!(
+            selection.variant_streams,
+            vec![&playlist.variant_streams[0], &playlist.variant_streams[1]]
+        );
+
+        let mut failed = HashSet::from(["CDN-A".into()]);
+        let selection = manifest.select_pathway(&playlist, &failed).unwrap();
+        assert_eq!(selection.pathway_id, "CDN-B");
+        assert_eq!(
+            selection.variant_streams,
+            vec![&playlist.variant_streams[2]]
+        );
+
+        // The default pathway is not in the priority list, so it is never
+        // selected.
+        failed.insert("CDN-B".into());
+        assert_eq!(manifest.select_pathway(&playlist, &failed), None);
+    }
+
+    #[test]
+    fn select_initial_pathway() {
+        let playlist = playlist();
+        let steering = &playlist.content_steering[0];
+
+        let selection = steering
+            .select_initial_pathway(&playlist, &HashSet::new())
+            .unwrap();
+        assert_eq!(selection.pathway_id, "CDN-B");
+
+        let selection = steering
+            .select_initial_pathway(&playlist, &HashSet::from(["CDN-B".into()]))
+            .unwrap();
+        assert_eq!(selection.pathway_id, "CDN-A");
+        assert_eq!(selection.variant_streams.len(), 2);
+    }
+}
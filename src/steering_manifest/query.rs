@@ -0,0 +1,168 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use url::Url;
+
+use super::SteeringManifest;
+use crate::ContentSteering;
+
+const PATHWAY_PARAMETER: &str = "_HLS_pathway";
+const THROUGHPUT_PARAMETER: &str = "_HLS_throughput";
+
+/// The query parameters a client adds to the URI of a [`SteeringManifest`]
+/// when requesting it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SteeringQuery {
+    /// The ID of the pathway the client is currently using, sent as
+    /// `_HLS_pathway`.
+    pub pathway_id: Option<String>,
+
+    /// The throughput the client has most recently measured, sent as
+    /// `_HLS_throughput`.
+    pub throughput_bits_per_second: Option<u64>,
+}
+
+impl SteeringQuery {
+    /// Resolves `uri` against `base_uri` and adds the query parameters to it.
+    ///
+    /// Any `_HLS_pathway` or `_HLS_throughput` parameters already in `uri` are
+    /// removed, while every other parameter is kept as is.
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` if `uri` can not be resolved against `base_uri`.
+    pub fn apply(&self, uri: &str, base_uri: &Url) -> Result<Url, url::ParseError> {
+        let mut uri = base_uri.join(uri)?;
+
+        let query = uri
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|pair| {
+                let name = pair.split_once('=').map_or(*pair, |(name, _)| name);
+                !pair.is_empty() && name != PATHWAY_PARAMETER && name != THROUGHPUT_PARAMETER
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        uri.set_query((!query.is_empty()).then_some(&query));
+
+        if self.pathway_id.is_none() && self.throughput_bits_per_second.is_none() {
+            return Ok(uri);
+        }
+
+        {
+            let mut pairs = uri.query_pairs_mut();
+            if let Some(pathway_id) = &self.pathway_id {
+                pairs.append_pair(PATHWAY_PARAMETER, pathway_id);
+            }
+            if let Some(throughput) = self.throughput_bits_per_second {
+                pairs.append_pair(THROUGHPUT_PARAMETER, &throughput.to_string());
+            }
+        }
+
+        Ok(uri)
+    }
+}
+
+impl ContentSteering {
+    /// Returns the URI a client should use to make it's first request for the
+    /// [`SteeringManifest`], resolving [`ContentSteering::server_uri`] against
+    /// the URI of the Multivariant Playlist. See [`SteeringQuery::apply`].
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` if the server URI can not be resolved.
+    pub fn manifest_uri(
+        &self,
+        playlist_uri: &Url,
+        query: &SteeringQuery,
+    ) -> Result<Url, url::ParseError> {
+        query.apply(&self.server_uri, playlist_uri)
+    }
+}
+
+impl SteeringManifest {
+    /// Returns the URI a client should use to reload the manifest, given the
+    /// URI it was last requested from.
+    ///
+    /// This is [`SteeringManifest::reload_uri`] resolved against
+    /// `previous_uri`, or `previous_uri` itself if there is no reload URI. See
+    /// [`SteeringQuery::apply`].
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` if the reload URI can not be resolved.
+    pub fn next_uri(
+        &self,
+        previous_uri: &Url,
+        query: &SteeringQuery,
+    ) -> Result<Url, url::ParseError> {
+        query.apply(
+            self.reload_uri.as_deref().unwrap_or(previous_uri.as_str()),
+            previous_uri,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steering_query() {
+        let playlist_uri = Url::parse("https://example.com/video/main.m3u8").unwrap();
+        let query = SteeringQuery {
+            pathway_id: Some("CDN-A".into()),
+            throughput_bits_per_second: Some(4_200_000),
+        };
+
+        let steering = ContentSteering {
+            server_uri: "/steering?video=00012".into(),
+            pathway_id: None,
+        };
+        let uri = steering.manifest_uri(&playlist_uri, &query).unwrap();
+        assert_eq!(
+            uri.as_str(),
+            "https://example.com/steering?video=00012&_HLS_pathway=CDN-A&_HLS_throughput=4200000"
+        );
+
+        let mut manifest = SteeringManifest {
+            ttl_seconds: 300,
+            reload_uri: None,
+            pathway_priority: vec!["CDN-A".into()].try_into().unwrap(),
+            pathway_clones: vec![],
+        };
+        let query = SteeringQuery {
+            pathway_id: Some("CDN-B".into()),
+            throughput_bits_per_second: None,
+        };
+        assert_eq!(
+            manifest.next_uri(&uri, &query).unwrap().as_str(),
+            "https://example.com/steering?video=00012&_HLS_pathway=CDN-B"
+        );
+
+        manifest.reload_uri = Some("next?session=a%20b&_HLS_throughput=1".into());
+        assert_eq!(
+            manifest.next_uri(&uri, &query).unwrap().as_str(),
+            "https://example.com/next?session=a%20b&_HLS_pathway=CDN-B"
+        );
+        assert_eq!(
+            manifest
+                .next_uri(&uri, &SteeringQuery::default())
+                .unwrap()
+                .as_str(),
+            "https://example.com/next?session=a%20b"
+        );
+    }
+}
@@ -14,7 +14,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod parse;
 mod serialize;
+mod validate;
+
+pub use parse::ParseTagError;
+pub(crate) use validate::validate_uri_line;
+pub use validate::TagViolation;
 
 /// A representation of all possible tags.
 #[derive(Debug, Clone, PartialEq)]
@@ -24,9 +30,7 @@ pub enum Tag {
 
     /// The EXT-X-VERSION tag indicates the compatibility version of the
     /// Playlist file, its associated media, and its server.
-    XVersion {
-        version: u8,
-    },
+    XVersion { version: u8 },
 
     /// The EXT-X-DEFINE tag provides a Playlist variable definition or
     /// declaration.
@@ -77,9 +81,9 @@ pub enum Tag {
     /// does not contain media data and SHOULD NOT be loaded by clients.
     XGap,
 
-    XBitrate {
-        kbps: u64,
-    },
+    /// The EXT-X-BITRATE tag identifies the approximate segment bit rate of
+    /// the Media Segments to which it applies.
+    XBitrate { kbps: u64 },
 
     /// The EXT-X-PART tag identifies a Partial Segment.
     XPart {
@@ -91,22 +95,16 @@ pub enum Tag {
     },
     /// The EXT-X-TARGETDURATION tag specifies the maximum Media Segment
     /// duration.
-    XTargetDuration {
-        target_duration_seconds: u64,
-    },
+    XTargetDuration { target_duration_seconds: u64 },
 
     /// The EXT-X-MEDIA-SEQUENCE tag indicates the Media Sequence Number of
     /// the first Media Segment that appears in a Playlist file.
-    XMediaSequence {
-        sequence_number: u64,
-    },
+    XMediaSequence { sequence_number: u64 },
 
     /// The EXT-X-DISCONTINUITY-SEQUENCE tag allows synchronization between
     /// different Renditions of the same Variant Stream or different Variant
     /// Streams that have EXT-X-DISCONTINUITY tags in their Media Playlists.
-    XDiscontinuitySequence {
-        sequence_number: u64,
-    },
+    XDiscontinuitySequence { sequence_number: u64 },
 
     /// The EXT-X-ENDLIST tag indicates that no more Media Segments will be
     /// added to the Media Playlist file.
@@ -122,9 +120,7 @@ pub enum Tag {
 
     /// The EXT-X-PART-INF tag provides information about the Partial
     /// Segments in the Playlist.
-    XPartInf {
-        part_target_duration_seconds: f64,
-    },
+    XPartInf { part_target_duration_seconds: f64 },
 
     /// The EXT-X-SERVER-CONTROL tag allows the Server to indicate support
     /// for Delivery Directives.
@@ -222,3 +218,369 @@ pub enum MediaType {
         in_stream_id: crate::InStreamId,
     },
 }
+
+/// A borrowed version of [`Tag`], which refers to its values instead of
+/// owning them.
+///
+/// Playlists are serialized through `TagRef`s, so writing a tag never has to
+/// clone the strings and lists it contains. Every variant has the same
+/// meaning as the [`Tag`] variant of the same name.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TagRef<'a> {
+    /// The EXTM3U tag indicates that the file is an Extended M3U Playlist file.
+    M3u,
+
+    /// The EXT-X-VERSION tag indicates the compatibility version of the
+    /// Playlist file, its associated media, and its server.
+    XVersion { version: u8 },
+
+    /// The EXT-X-DEFINE tag provides a Playlist variable definition or
+    /// declaration.
+    XDefine(&'a crate::DefinitionType),
+
+    /// The EXT-X-START tag indicates a preferred point at which to start
+    /// playing a Playlist.
+    XStart {
+        offset_seconds: f64,
+        is_precise: bool,
+    },
+
+    /// The EXT-X-INDEPENDENT-SEGMENTS tag indicates that all media samples
+    /// in a Media Segment can be decoded without information from other
+    /// segments.
+    XIndependentSegments,
+
+    /// The EXTINF tag specifies the duration of a Media Segment.
+    Inf {
+        duration_seconds: &'a crate::FloatOrInteger,
+        title: &'a str,
+    },
+
+    /// The EXT-X-BYTERANGE tag indicates that a Media Segment is a sub-range
+    /// of the resource identified by its URI.
+    XByterange(&'a crate::ByteRange),
+
+    /// The EXT-X-DISCONTINUITY tag indicates a discontinuity between the
+    /// Media Segment that follows it and the one that preceded it.
+    XDiscontinuity,
+
+    /// Media Segments MAY be encrypted.  The EXT-X-KEY tag specifies how to
+    /// decrypt them.
+    XKey(Option<&'a crate::EncryptionMethod>),
+
+    /// The EXT-X-MAP tag specifies how to obtain the Media Initialization
+    /// Section required to parse the applicable Media Segments.
+    XMap {
+        uri: &'a str,
+        range: Option<&'a crate::ByteRangeWithOffset>,
+    },
+
+    /// The EXT-X-PROGRAM-DATE-TIME tag associates the first sample of a
+    /// Media Segment with an absolute date and/or time.
+    XProgramDateTime(&'a chrono::DateTime<chrono::FixedOffset>),
+
+    /// The EXT-X-GAP tag indicates that the segment URI to which it applies
+    /// does not contain media data and SHOULD NOT be loaded by clients.
+    XGap,
+
+    /// The EXT-X-BITRATE tag identifies the approximate segment bit rate of
+    /// the Media Segments to which it applies.
+    XBitrate { kbps: u64 },
+
+    /// The EXT-X-PART tag identifies a Partial Segment.
+    XPart {
+        uri: &'a str,
+        duration_seconds: f64,
+        is_independent: bool,
+        byte_range: Option<&'a crate::ByteRange>,
+        is_gap: bool,
+    },
+
+    /// The EXT-X-TARGETDURATION tag specifies the maximum Media Segment
+    /// duration.
+    XTargetDuration { target_duration_seconds: u64 },
+
+    /// The EXT-X-MEDIA-SEQUENCE tag indicates the Media Sequence Number of
+    /// the first Media Segment that appears in a Playlist file.
+    XMediaSequence { sequence_number: u64 },
+
+    /// The EXT-X-DISCONTINUITY-SEQUENCE tag allows synchronization between
+    /// different Renditions of the same Variant Stream or different Variant
+    /// Streams that have EXT-X-DISCONTINUITY tags in their Media Playlists.
+    XDiscontinuitySequence { sequence_number: u64 },
+
+    /// The EXT-X-ENDLIST tag indicates that no more Media Segments will be
+    /// added to the Media Playlist file.
+    XEndList,
+
+    /// The EXT-X-PLAYLIST-TYPE tag provides mutability information about the
+    /// Media Playlist file.
+    XPlaylistType(&'a crate::PlaylistType),
+
+    /// The EXT-X-I-FRAMES-ONLY tag indicates that each Media Segment in the
+    /// Playlist describes a single I-frame.
+    XIFramesOnly,
+
+    /// The EXT-X-PART-INF tag provides information about the Partial
+    /// Segments in the Playlist.
+    XPartInf { part_target_duration_seconds: f64 },
+
+    /// The EXT-X-SERVER-CONTROL tag allows the Server to indicate support
+    /// for Delivery Directives.
+    XServerControl {
+        delta_update_info: Option<&'a crate::DeltaUpdateInfo>,
+        hold_back: Option<f64>,
+        part_hold_back: Option<f64>,
+        can_block_reload: bool,
+    },
+
+    /// The EXT-X-MEDIA tag is used to relate Media Playlists that contain
+    /// alternative Renditions of the same content.
+    XMedia {
+        media_type: MediaTypeRef<'a>,
+        group_id: &'a str,
+        language: Option<&'a str>,
+        assoc_language: Option<&'a str>,
+        name: &'a str,
+        stable_rendition_id: Option<&'a str>,
+        playback_priority: &'a crate::RenditionPlaybackPriority,
+        characteristics: &'a [String],
+    },
+
+    /// The EXT-X-STREAM-INF tag specifies a Variant Stream, which is a set
+    /// of Renditions that can be combined to play the presentation.
+    XStreamInf {
+        stream_inf: &'a crate::StreamInf,
+        frame_rate: Option<f64>,
+        audio_group_id: Option<&'a str>,
+        video_group_id: Option<&'a str>,
+        subtitles_group_id: Option<&'a str>,
+        closed_captions_group_id: Option<&'a str>,
+        uri: &'a str,
+    },
+
+    /// The EXT-X-I-FRAME-STREAM-INF tag identifies a Media Playlist file
+    /// containing the I-frames of a multimedia presentation.
+    XIFrameStreamInf {
+        stream_inf: &'a crate::StreamInf,
+        video_group_id: Option<&'a str>,
+        uri: &'a str,
+    },
+
+    /// The EXT-X-SESSION-DATA tag allows arbitrary session data to be
+    /// carried in a Multivariant Playlist.
+    XSessionData(&'a crate::SessionData),
+
+    /// The EXT-X-SESSION-KEY tag allows encryption keys from Media Playlists
+    /// to be specified in a Master Playlist.
+    XSessionKey(&'a crate::EncryptionMethod),
+
+    /// The EXT-X-CONTENT-STEERING tag allows a server to provide a Content
+    /// Steering (Section 7) Manifest.
+    XContentSteering(&'a crate::ContentSteering),
+
+    /// The EXT-X-DATERANGE tag associates a Date Range (i.e., a range of
+    /// time defined by a starting and ending date) with a set of attribute/
+    /// value pairs.
+    XDateRange(&'a crate::DateRange),
+
+    /// A server produces a Playlist Delta Update by replacing
+    /// tags earlier than the Skip Boundary with an EXT-X-SKIP tag.
+    XSkip {
+        number_of_skipped_segments: u64,
+        recently_removed_dataranges: &'a [String],
+    },
+
+    /// The EXT-X-PRELOAD-HINT tag allows a Client loading media from a live
+    /// stream to reduce the time to obtain a resource from the Server by
+    /// issuing its request before the resource is available to be delivered.
+    XPreloadHint(&'a crate::PreloadHint),
+
+    /// The EXT-X-RENDITION-REPORT tag carries information about an
+    /// associated Rendition that is as up-to-date as the Playlist that
+    /// contains it.
+    XRenditionReport(&'a crate::RenditionReport),
+}
+
+/// A borrowed version of [`MediaType`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaTypeRef<'a> {
+    /// An audio rendition.
+    Audio {
+        uri: Option<&'a str>,
+        channels: Option<&'a crate::AudioChannelInformation>,
+        bit_depth: Option<u64>,
+        sample_rate: Option<u64>,
+    },
+
+    /// A video rendition, such as a different camera angle.
+    Video { uri: Option<&'a str> },
+
+    /// A subtitles rendition.
+    Subtitles { uri: &'a str, forced: bool },
+
+    /// Closed captions carried in the video of a Variant Stream.
+    ClosedCaptions { in_stream_id: &'a crate::InStreamId },
+}
+
+impl<'a> From<&'a Tag> for TagRef<'a> {
+    fn from(tag: &'a Tag) -> Self {
+        match tag {
+            Tag::M3u => Self::M3u,
+            Tag::XVersion { version } => Self::XVersion { version: *version },
+            Tag::XDefine(definition) => Self::XDefine(definition),
+            Tag::XStart {
+                offset_seconds,
+                is_precise,
+            } => Self::XStart {
+                offset_seconds: *offset_seconds,
+                is_precise: *is_precise,
+            },
+            Tag::XIndependentSegments => Self::XIndependentSegments,
+            Tag::Inf {
+                duration_seconds,
+                title,
+            } => Self::Inf {
+                duration_seconds,
+                title,
+            },
+            Tag::XByterange(byte_range) => Self::XByterange(byte_range),
+            Tag::XDiscontinuity => Self::XDiscontinuity,
+            Tag::XKey(method) => Self::XKey(method.as_ref()),
+            Tag::XMap { uri, range } => Self::XMap {
+                uri,
+                range: range.as_ref(),
+            },
+            Tag::XProgramDateTime(time) => Self::XProgramDateTime(time),
+            Tag::XGap => Self::XGap,
+            Tag::XBitrate { kbps } => Self::XBitrate { kbps: *kbps },
+            Tag::XPart {
+                uri,
+                duration_seconds,
+                is_independent,
+                byte_range,
+                is_gap,
+            } => Self::XPart {
+                uri,
+                duration_seconds: *duration_seconds,
+                is_independent: *is_independent,
+                byte_range: byte_range.as_ref(),
+                is_gap: *is_gap,
+            },
+            Tag::XTargetDuration {
+                target_duration_seconds,
+            } => Self::XTargetDuration {
+                target_duration_seconds: *target_duration_seconds,
+            },
+            Tag::XMediaSequence { sequence_number } => Self::XMediaSequence {
+                sequence_number: *sequence_number,
+            },
+            Tag::XDiscontinuitySequence { sequence_number } => Self::XDiscontinuitySequence {
+                sequence_number: *sequence_number,
+            },
+            Tag::XEndList => Self::XEndList,
+            Tag::XPlaylistType(playlist_type) => Self::XPlaylistType(playlist_type),
+            Tag::XIFramesOnly => Self::XIFramesOnly,
+            Tag::XPartInf {
+                part_target_duration_seconds,
+            } => Self::XPartInf {
+                part_target_duration_seconds: *part_target_duration_seconds,
+            },
+            Tag::XServerControl {
+                delta_update_info,
+                hold_back,
+                part_hold_back,
+                can_block_reload,
+            } => Self::XServerControl {
+                delta_update_info: delta_update_info.as_ref(),
+                hold_back: *hold_back,
+                part_hold_back: *part_hold_back,
+                can_block_reload: *can_block_reload,
+            },
+            Tag::XMedia {
+                media_type,
+                group_id,
+                language,
+                assoc_language,
+                name,
+                stable_rendition_id,
+                playback_priority,
+                characteristics,
+            } => Self::XMedia {
+                media_type: media_type.into(),
+                group_id,
+                language: language.as_deref(),
+                assoc_language: assoc_language.as_deref(),
+                name,
+                stable_rendition_id: stable_rendition_id.as_deref(),
+                playback_priority,
+                characteristics,
+            },
+            Tag::XStreamInf {
+                stream_inf,
+                frame_rate,
+                audio_group_id,
+                video_group_id,
+                subtitles_group_id,
+                closed_captions_group_id,
+                uri,
+            } => Self::XStreamInf {
+                stream_inf,
+                frame_rate: *frame_rate,
+                audio_group_id: audio_group_id.as_deref(),
+                video_group_id: video_group_id.as_deref(),
+                subtitles_group_id: subtitles_group_id.as_deref(),
+                closed_captions_group_id: closed_captions_group_id.as_deref(),
+                uri,
+            },
+            Tag::XIFrameStreamInf {
+                stream_inf,
+                video_group_id,
+                uri,
+            } => Self::XIFrameStreamInf {
+                stream_inf,
+                video_group_id: video_group_id.as_deref(),
+                uri,
+            },
+            Tag::XSessionData(data) => Self::XSessionData(data),
+            Tag::XSessionKey(method) => Self::XSessionKey(method),
+            Tag::XContentSteering(steering) => Self::XContentSteering(steering),
+            Tag::XDateRange(date_range) => Self::XDateRange(date_range),
+            Tag::XSkip {
+                number_of_skipped_segments,
+                recently_removed_dataranges,
+            } => Self::XSkip {
+                number_of_skipped_segments: *number_of_skipped_segments,
+                recently_removed_dataranges,
+            },
+            Tag::XPreloadHint(hint) => Self::XPreloadHint(hint),
+            Tag::XRenditionReport(report) => Self::XRenditionReport(report),
+        }
+    }
+}
+
+impl<'a> From<&'a MediaType> for MediaTypeRef<'a> {
+    fn from(media_type: &'a MediaType) -> Self {
+        match media_type {
+            MediaType::Audio {
+                uri,
+                channels,
+                bit_depth,
+                sample_rate,
+            } => Self::Audio {
+                uri: uri.as_deref(),
+                channels: channels.as_ref(),
+                bit_depth: *bit_depth,
+                sample_rate: *sample_rate,
+            },
+            MediaType::Video { uri } => Self::Video {
+                uri: uri.as_deref(),
+            },
+            MediaType::Subtitles { uri, forced } => Self::Subtitles {
+                uri,
+                forced: *forced,
+            },
+            MediaType::ClosedCaptions { in_stream_id } => Self::ClosedCaptions { in_stream_id },
+        }
+    }
+}
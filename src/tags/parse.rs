@@ -0,0 +1,1435 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, error::Error, fmt, num::NonZeroU8, str::FromStr};
+
+use crate::{
+    AttributeValue, AudioChannelInformation, ByteRange, ByteRangeWithOffset,
+    ContentProtectionConfiguration, ContentSteering, DateRange, DateRangeCue, DateRangeCuePosition,
+    DefinitionType, DeltaUpdateInfo, EncryptionMethod, FloatOrInteger, HdcpLevel, InStreamId,
+    KeyFormat, PlaylistType, PreloadHint, PreloadHintType, RenditionPlaybackPriority,
+    RenditionReport, Resolution, SessionData, SessionDataValue, StreamInf, SupplementalCodec,
+    UriFormat, VideoChannelSpecifier, VideoRange,
+};
+
+use super::{MediaType, Tag};
+
+/// An error encountered while parsing a [`Tag`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTagError {
+    /// The line does not begin with `#EXT`.
+    NotATag,
+
+    /// The tag name is not one this library recognizes.
+    UnknownTag(String),
+
+    /// The tag requires a value, but none was given.
+    MissingValue,
+
+    /// The value of a tag without an attribute list is malformed.
+    InvalidValue(String),
+
+    /// The attribute list of the tag could not be split into attributes.
+    MalformedAttributeList,
+
+    /// A required attribute is missing from the attribute list.
+    MissingAttribute(&'static str),
+
+    /// An attribute has a value which is malformed or not allowed.
+    InvalidAttribute {
+        /// The name of the offending attribute.
+        name: String,
+
+        /// The value of the offending attribute, as written.
+        value: String,
+    },
+
+    /// An EXT-X-STREAM-INF tag was not followed by a URI line.
+    MissingUri,
+
+    /// There was input left over after the tag was parsed.
+    TrailingInput,
+}
+
+impl fmt::Display for ParseTagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotATag => write!(f, "line is not a tag"),
+            Self::UnknownTag(name) => write!(f, "unknown tag {name}"),
+            Self::MissingValue => write!(f, "tag is missing its value"),
+            Self::InvalidValue(value) => write!(f, "invalid tag value {value:?}"),
+            Self::MalformedAttributeList => write!(f, "malformed attribute list"),
+            Self::MissingAttribute(name) => write!(f, "missing required attribute {name}"),
+            Self::InvalidAttribute { name, value } => {
+                write!(f, "invalid value {value:?} for attribute {name}")
+            }
+            Self::MissingUri => write!(f, "EXT-X-STREAM-INF tag is not followed by a URI"),
+            Self::TrailingInput => write!(f, "unexpected input after tag"),
+        }
+    }
+}
+
+impl Error for ParseTagError {}
+
+impl FromStr for Tag {
+    type Err = ParseTagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Tag {
+    /// Parses a single extended M3U playlist tag from `input`. This is the
+    /// inverse of [`Tag::serialize`], so a trailing newline is accepted, and
+    /// an EXT-X-STREAM-INF tag must be followed by its URI on the next line.
+    ///
+    /// Unrecognized attributes are ignored, as required of clients by the HLS spec.
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` if `input` is not a tag recognized by this library, or if
+    /// the tag is malformed.
+    pub fn parse(input: &str) -> Result<Self, ParseTagError> {
+        let input = strip_line_ending(input);
+        let (line, rest) = match input.split_once('\n') {
+            Some((line, rest)) => (strip_line_ending(line), Some(rest)),
+            None => (input, None),
+        };
+
+        if Self::is_followed_by_uri(line) {
+            match rest {
+                Some(uri) if !uri.is_empty() && !uri.contains('\n') && !uri.starts_with('#') => {
+                    Self::parse_line(line, Some(uri))
+                }
+                _ => Err(ParseTagError::MissingUri),
+            }
+        } else if rest.is_some() {
+            Err(ParseTagError::TrailingInput)
+        } else {
+            Self::parse_line(line, None)
+        }
+    }
+
+    /// Returns true if the tag on `line` is followed by a URI line which is
+    /// a part of the tag.
+    pub(crate) fn is_followed_by_uri(line: &str) -> bool {
+        split_name(line).0 == "#EXT-X-STREAM-INF"
+    }
+
+    /// Parses the tag on `line`. `uri` is the URI line following the tag, and is
+    /// only used for tags where [`Tag::is_followed_by_uri`] is true.
+    pub(crate) fn parse_line(line: &str, uri: Option<&str>) -> Result<Self, ParseTagError> {
+        if !line.starts_with("#EXT") {
+            return Err(ParseTagError::NotATag);
+        }
+
+        let (name, value) = split_name(line);
+        let tag = match name {
+            "#EXTM3U" => no_value(value, Self::M3u)?,
+            "#EXT-X-VERSION" => Self::XVersion {
+                version: parse_value(value)?,
+            },
+            "#EXT-X-DEFINE" => Self::parse_x_define(&Attributes::parse(value)?)?,
+            "#EXT-X-START" => {
+                let attributes = Attributes::parse(value)?;
+                Self::XStart {
+                    offset_seconds: attributes.required("TIME-OFFSET", Attributes::float)?,
+                    is_precise: attributes.yes("PRECISE")?,
+                }
+            }
+            "#EXT-X-INDEPENDENT-SEGMENTS" => no_value(value, Self::XIndependentSegments)?,
+            "#EXTINF" => Self::parse_inf(value.ok_or(ParseTagError::MissingValue)?)?,
+            "#EXT-X-BYTERANGE" => {
+                let value = value.ok_or(ParseTagError::MissingValue)?;
+                Self::XByterange(
+                    ByteRange::parse(value)
+                        .ok_or_else(|| ParseTagError::InvalidValue(value.into()))?,
+                )
+            }
+            "#EXT-X-DISCONTINUITY" => no_value(value, Self::XDiscontinuity)?,
+            "#EXT-X-KEY" => Self::XKey(EncryptionMethod::parse(&Attributes::parse(value)?)?),
+            "#EXT-X-MAP" => {
+                let attributes = Attributes::parse(value)?;
+                Self::XMap {
+                    uri: attributes.required("URI", Attributes::quoted)?.into(),
+                    range: attributes.byte_range_with_offset("BYTERANGE")?,
+                }
+            }
+            "#EXT-X-PROGRAM-DATE-TIME" => {
+                let value = value.ok_or(ParseTagError::MissingValue)?;
+                Self::XProgramDateTime(
+                    chrono::DateTime::parse_from_rfc3339(value)
+                        .map_err(|_| ParseTagError::InvalidValue(value.into()))?,
+                )
+            }
+            "#EXT-X-GAP" => no_value(value, Self::XGap)?,
+            "#EXT-X-BITRATE" => Self::XBitrate {
+                kbps: parse_value(value)?,
+            },
+            "#EXT-X-PART" => {
+                let attributes = Attributes::parse(value)?;
+                Self::XPart {
+                    uri: attributes.required("URI", Attributes::quoted)?.into(),
+                    duration_seconds: attributes.required("DURATION", Attributes::float)?,
+                    is_independent: attributes.yes("INDEPENDENT")?,
+                    byte_range: attributes.byte_range("BYTERANGE")?,
+                    is_gap: attributes.yes("GAP")?,
+                }
+            }
+            "#EXT-X-TARGETDURATION" => Self::XTargetDuration {
+                target_duration_seconds: parse_value(value)?,
+            },
+            "#EXT-X-MEDIA-SEQUENCE" => Self::XMediaSequence {
+                sequence_number: parse_value(value)?,
+            },
+            "#EXT-X-DISCONTINUITY-SEQUENCE" => Self::XDiscontinuitySequence {
+                sequence_number: parse_value(value)?,
+            },
+            "#EXT-X-ENDLIST" => no_value(value, Self::XEndList)?,
+            "#EXT-X-PLAYLIST-TYPE" => match value {
+                Some("EVENT") => Self::XPlaylistType(PlaylistType::Event),
+                Some("VOD") => Self::XPlaylistType(PlaylistType::Vod),
+                Some(value) => return Err(ParseTagError::InvalidValue(value.into())),
+                None => return Err(ParseTagError::MissingValue),
+            },
+            "#EXT-X-I-FRAMES-ONLY" => no_value(value, Self::XIFramesOnly)?,
+            "#EXT-X-PART-INF" => Self::XPartInf {
+                part_target_duration_seconds: Attributes::parse(value)?
+                    .required("PART-TARGET", Attributes::float)?,
+            },
+            "#EXT-X-SERVER-CONTROL" => Self::parse_x_server_control(&Attributes::parse(value)?)?,
+            "#EXT-X-MEDIA" => Self::parse_x_media(&Attributes::parse(value)?)?,
+            "#EXT-X-STREAM-INF" => {
+                let attributes = Attributes::parse(value)?;
+                let closed_captions_group_id = match attributes.get("CLOSED-CAPTIONS") {
+                    Some(RawValue::Unquoted("NONE")) => None,
+                    _ => attributes.quoted("CLOSED-CAPTIONS")?,
+                };
+
+                Self::XStreamInf {
+                    stream_inf: StreamInf::parse(&attributes)?,
+                    frame_rate: attributes.float("FRAME-RATE")?,
+                    audio_group_id: attributes.quoted("AUDIO")?.map(Into::into),
+                    video_group_id: attributes.quoted("VIDEO")?.map(Into::into),
+                    subtitles_group_id: attributes.quoted("SUBTITLES")?.map(Into::into),
+                    closed_captions_group_id: closed_captions_group_id.map(Into::into),
+                    uri: uri.ok_or(ParseTagError::MissingUri)?.into(),
+                }
+            }
+            "#EXT-X-I-FRAME-STREAM-INF" => {
+                let attributes = Attributes::parse(value)?;
+                Self::XIFrameStreamInf {
+                    stream_inf: StreamInf::parse(&attributes)?,
+                    video_group_id: attributes.quoted("VIDEO")?.map(Into::into),
+                    uri: attributes.required("URI", Attributes::quoted)?.into(),
+                }
+            }
+            "#EXT-X-SESSION-DATA" => {
+                Self::XSessionData(SessionData::parse(&Attributes::parse(value)?)?)
+            }
+            "#EXT-X-SESSION-KEY" => {
+                let attributes = Attributes::parse(value)?;
+                Self::XSessionKey(EncryptionMethod::parse(&attributes)?.ok_or_else(|| {
+                    ParseTagError::InvalidAttribute {
+                        name: "METHOD".into(),
+                        value: "NONE".into(),
+                    }
+                })?)
+            }
+            "#EXT-X-CONTENT-STEERING" => {
+                let attributes = Attributes::parse(value)?;
+                Self::XContentSteering(ContentSteering {
+                    server_uri: attributes
+                        .required("SERVER-URI", Attributes::quoted)?
+                        .into(),
+                    pathway_id: attributes.quoted("PATHWAY-ID")?.map(Into::into),
+                })
+            }
+            "#EXT-X-DATERANGE" => Self::XDateRange(DateRange::parse(&Attributes::parse(value)?)?),
+            "#EXT-X-SKIP" => {
+                let attributes = Attributes::parse(value)?;
+                Self::XSkip {
+                    number_of_skipped_segments: attributes
+                        .required("SKIPPED-SEGMENTS", Attributes::decimal_integer)?,
+                    recently_removed_dataranges: attributes
+                        .quoted("RECENTLY-REMOVED-DATERANGES")?
+                        .map(|ids| ids.split('\t').map(Into::into).collect())
+                        .unwrap_or_default(),
+                }
+            }
+            "#EXT-X-PRELOAD-HINT" => {
+                let attributes = Attributes::parse(value)?;
+                Self::XPreloadHint(PreloadHint {
+                    hint_type: match attributes.required("TYPE", Attributes::enumerated)? {
+                        "PART" => PreloadHintType::Part,
+                        "MAP" => PreloadHintType::Map,
+                        other => return Err(invalid_attribute("TYPE", other)),
+                    },
+                    uri: attributes.required("URI", Attributes::quoted)?.into(),
+                    start_byte_offset: attributes.decimal_integer("BYTERANGE-START")?.unwrap_or(0),
+                    length_in_bytes: attributes.decimal_integer("BYTERANGE-LENGTH")?,
+                })
+            }
+            "#EXT-X-RENDITION-REPORT" => {
+                let attributes = Attributes::parse(value)?;
+                Self::XRenditionReport(RenditionReport {
+                    uri: attributes.required("URI", Attributes::quoted)?.into(),
+                    last_sequence_number: attributes.decimal_integer("LAST-MSN")?,
+                    last_part_index: attributes.decimal_integer("LAST-PART")?,
+                })
+            }
+            name => return Err(ParseTagError::UnknownTag(name.into())),
+        };
+
+        Ok(tag)
+    }
+
+    fn parse_x_define(attributes: &Attributes) -> Result<Self, ParseTagError> {
+        if let Some(name) = attributes.quoted("NAME")? {
+            return Ok(Self::XDefine(DefinitionType::Inline {
+                name: name.into(),
+                value: attributes.required("VALUE", Attributes::quoted)?.into(),
+            }));
+        }
+
+        if let Some(name) = attributes.quoted("IMPORT")? {
+            return Ok(Self::XDefine(DefinitionType::Import { name: name.into() }));
+        }
+
+        if let Some(name) = attributes.quoted("QUERYPARAM")? {
+            return Ok(Self::XDefine(DefinitionType::QueryParameter {
+                name: name.into(),
+            }));
+        }
+
+        Err(ParseTagError::MissingAttribute("NAME"))
+    }
+
+    fn parse_inf(value: &str) -> Result<Self, ParseTagError> {
+        let (duration, title) = value.split_once(',').unwrap_or((value, ""));
+
+        let duration_seconds = if duration.contains('.') {
+            FloatOrInteger::Float(
+                parse_float(duration)
+                    .ok_or_else(|| ParseTagError::InvalidValue(duration.into()))?,
+            )
+        } else {
+            FloatOrInteger::Integer(
+                parse_integer(duration)
+                    .ok_or_else(|| ParseTagError::InvalidValue(duration.into()))?,
+            )
+        };
+
+        Ok(Self::Inf {
+            duration_seconds,
+            title: title.into(),
+        })
+    }
+
+    fn parse_x_server_control(attributes: &Attributes) -> Result<Self, ParseTagError> {
+        let delta_update_info = attributes
+            .float("CAN-SKIP-UNTIL")?
+            .map(|skip_boundary_seconds| -> Result<_, ParseTagError> {
+                Ok(DeltaUpdateInfo {
+                    skip_boundary_seconds,
+                    can_skip_dateranges: attributes.yes("CAN-SKIP-DATERANGES")?,
+                })
+            })
+            .transpose()?;
+
+        Ok(Self::XServerControl {
+            delta_update_info,
+            hold_back: attributes.float("HOLD-BACK")?,
+            part_hold_back: attributes.float("PART-HOLD-BACK")?,
+            can_block_reload: attributes.yes("CAN-BLOCK-RELOAD")?,
+        })
+    }
+
+    fn parse_x_media(attributes: &Attributes) -> Result<Self, ParseTagError> {
+        let uri = attributes.quoted("URI")?.map(String::from);
+        let media_type = match attributes.required("TYPE", Attributes::enumerated)? {
+            "AUDIO" => MediaType::Audio {
+                uri,
+                channels: attributes
+                    .quoted("CHANNELS")?
+                    .map(|channels| {
+                        AudioChannelInformation::parse(channels)
+                            .ok_or_else(|| invalid_attribute("CHANNELS", channels))
+                    })
+                    .transpose()?,
+                bit_depth: attributes.decimal_integer("BIT-DEPTH")?,
+                sample_rate: attributes.decimal_integer("SAMPLE-RATE")?,
+            },
+            "VIDEO" => MediaType::Video { uri },
+            "SUBTITLES" => MediaType::Subtitles {
+                uri: uri.ok_or(ParseTagError::MissingAttribute("URI"))?,
+                forced: attributes.yes("FORCED")?,
+            },
+            "CLOSED-CAPTIONS" => {
+                let in_stream_id = attributes.required("INSTREAM-ID", Attributes::quoted)?;
+                MediaType::ClosedCaptions {
+                    in_stream_id: InStreamId::parse(in_stream_id)
+                        .ok_or_else(|| invalid_attribute("INSTREAM-ID", in_stream_id))?,
+                }
+            }
+            other => return Err(invalid_attribute("TYPE", other)),
+        };
+
+        let playback_priority = if attributes.yes("DEFAULT")? {
+            RenditionPlaybackPriority::Default
+        } else if attributes.yes("AUTOSELECT")? {
+            RenditionPlaybackPriority::AutoSelect
+        } else {
+            RenditionPlaybackPriority::None
+        };
+
+        Ok(Self::XMedia {
+            media_type,
+            group_id: attributes.required("GROUP-ID", Attributes::quoted)?.into(),
+            language: attributes.quoted("LANGUAGE")?.map(Into::into),
+            assoc_language: attributes.quoted("ASSOC-LANGUAGE")?.map(Into::into),
+            name: attributes.required("NAME", Attributes::quoted)?.into(),
+            stable_rendition_id: attributes.quoted("STABLE-RENDITION-ID")?.map(Into::into),
+            playback_priority,
+            characteristics: attributes
+                .quoted("CHARACTERISTICS")?
+                .map(|characteristics| characteristics.split(',').map(Into::into).collect())
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl ByteRange {
+    fn parse(value: &str) -> Option<Self> {
+        let (length, offset) = match value.split_once('@') {
+            Some((length, offset)) => (length, Some(parse_integer(offset)?)),
+            None => (value, None),
+        };
+
+        Some(Self {
+            length_bytes: parse_integer(length)?,
+            start_offset_bytes: offset,
+        })
+    }
+}
+
+impl EncryptionMethod {
+    fn parse(attributes: &Attributes) -> Result<Option<Self>, ParseTagError> {
+        let method = attributes.required("METHOD", Attributes::enumerated)?;
+        if method == "NONE" {
+            return Ok(None);
+        }
+
+        let uri = attributes.required("URI", Attributes::quoted)?.into();
+        let iv = attributes
+            .get("IV")
+            .map(|value| {
+                let RawValue::Unquoted(iv) = value else {
+                    return Err(invalid_attribute("IV", value.as_str()));
+                };
+
+                iv.strip_prefix("0x")
+                    .or_else(|| iv.strip_prefix("0X"))
+                    .and_then(|hex| u128::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| invalid_attribute("IV", iv))
+            })
+            .transpose()?;
+        let key_format_versions = attributes
+            .quoted("KEYFORMATVERSIONS")?
+            .map(|versions| {
+                versions
+                    .split('/')
+                    .map(|version| {
+                        parse_integer(version)
+                            .ok_or_else(|| invalid_attribute("KEYFORMATVERSIONS", versions))
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        let method = match method {
+            "AES-128" => Self::Aes128 {
+                uri,
+                iv,
+                key_format: match attributes.quoted("KEYFORMAT")? {
+                    None | Some("identity") => KeyFormat::Identity,
+                    Some(other) => KeyFormat::Other(other.into()),
+                },
+                key_format_versions,
+            },
+            "SAMPLE-AES" => Self::SampleAes {
+                uri,
+                iv,
+                key_format_versions,
+            },
+            "SAMPLE-AES-CTR" => Self::SampleAesCtr {
+                uri,
+                key_format_versions,
+            },
+            other => return Err(invalid_attribute("METHOD", other)),
+        };
+
+        Ok(Some(method))
+    }
+}
+
+impl StreamInf {
+    fn parse(attributes: &Attributes) -> Result<Self, ParseTagError> {
+        let resolution = attributes
+            .enumerated("RESOLUTION")?
+            .map(|resolution| {
+                resolution
+                    .split_once('x')
+                    .and_then(|(width, height)| {
+                        Some(Resolution {
+                            width: parse_integer(width)?,
+                            height: parse_integer(height)?,
+                        })
+                    })
+                    .ok_or_else(|| invalid_attribute("RESOLUTION", resolution))
+            })
+            .transpose()?;
+
+        let hdcp_level = match attributes.enumerated("HDCP-LEVEL")? {
+            None => None,
+            Some("NONE") => Some(HdcpLevel::None),
+            Some("TYPE-0") => Some(HdcpLevel::Type0),
+            Some("TYPE-1") => Some(HdcpLevel::Type1),
+            Some(other) => return Err(invalid_attribute("HDCP-LEVEL", other)),
+        };
+
+        let allowed_cpc = attributes
+            .quoted("ALLOWED-CPC")?
+            .map(|configurations| {
+                configurations
+                    .split(',')
+                    .map(|configuration| {
+                        let (key_format, labels) = configuration
+                            .rsplit_once(':')
+                            .ok_or_else(|| invalid_attribute("ALLOWED-CPC", configurations))?;
+
+                        Ok(ContentProtectionConfiguration {
+                            key_format: key_format.into(),
+                            cpc_labels: if labels.is_empty() {
+                                vec![]
+                            } else {
+                                labels.split('/').map(Into::into).collect()
+                            },
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?
+            .unwrap_or_default();
+
+        let video_range = match attributes.enumerated("VIDEO-RANGE")? {
+            None | Some("SDR") => VideoRange::Sdr,
+            Some("HLG") => VideoRange::Hlg,
+            Some("PQ") => VideoRange::Pq,
+            Some(other) => VideoRange::Other(other.into()),
+        };
+
+        let required_video_layout = attributes
+            .quoted("REQ-VIDEO-LAYOUT")?
+            .map(|layout| {
+                layout
+                    .split(',')
+                    .filter_map(|specifier| match specifier {
+                        "CH-STEREO" => Some(VideoChannelSpecifier::Stereo),
+                        "CH-MONO" => Some(VideoChannelSpecifier::Mono),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            bandwidth_bits_per_second: attributes
+                .required("BANDWIDTH", Attributes::decimal_integer)?,
+            average_bandwidth_bits_per_second: attributes.decimal_integer("AVERAGE-BANDWIDTH")?,
+            score: attributes.float("SCORE")?,
+            codecs: attributes
+                .quoted("CODECS")?
+                .map(|codecs| codecs.split(',').map(Into::into).collect())
+                .unwrap_or_default(),
+            supplemental_codecs: attributes
+                .quoted("SUPPLEMENTAL-CODECS")?
+                .map(|codecs| {
+                    codecs
+                        .split(',')
+                        .map(|codec| {
+                            let mut parts = codec.split('/');
+                            SupplementalCodec {
+                                supplemental_codec: parts.next().unwrap_or_default().into(),
+                                compatibility_brands: parts.map(Into::into).collect(),
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            resolution,
+            hdcp_level,
+            allowed_cpc,
+            video_range,
+            required_video_layout,
+            stable_variant_id: attributes.quoted("STABLE-VARIANT-ID")?.map(Into::into),
+            pathway_id: attributes.quoted("PATHWAY-ID")?.map(Into::into),
+        })
+    }
+}
+
+impl SessionData {
+    fn parse(attributes: &Attributes) -> Result<Self, ParseTagError> {
+        let value = if let Some(value) = attributes.quoted("VALUE")? {
+            SessionDataValue::Value {
+                value: value.into(),
+                language: attributes.quoted("LANGUAGE")?.map(Into::into),
+            }
+        } else if let Some(uri) = attributes.quoted("URI")? {
+            SessionDataValue::Uri {
+                uri: uri.into(),
+                format: match attributes.enumerated("FORMAT")? {
+                    None | Some("JSON") => UriFormat::Json,
+                    Some("RAW") => UriFormat::Raw,
+                    Some(other) => return Err(invalid_attribute("FORMAT", other)),
+                },
+            }
+        } else {
+            return Err(ParseTagError::MissingAttribute("VALUE"));
+        };
+
+        Ok(Self {
+            data_id: attributes.required("DATA-ID", Attributes::quoted)?.into(),
+            value,
+        })
+    }
+}
+
+impl DateRange {
+    fn parse(attributes: &Attributes) -> Result<Self, ParseTagError> {
+        let cue = attributes
+            .quoted("CUE")?
+            .map(|cue| {
+                let mut once = false;
+                let mut position = DateRangeCuePosition::Neither;
+                for trigger in cue.split(',').filter(|trigger| !trigger.is_empty()) {
+                    match trigger {
+                        "ONCE" => once = true,
+                        "PRE" => position = DateRangeCuePosition::Pre,
+                        "POST" => position = DateRangeCuePosition::Post,
+                        _ => return Err(invalid_attribute("CUE", cue)),
+                    }
+                }
+
+                Ok(DateRangeCue { once, position })
+            })
+            .transpose()?;
+
+        let mut client_attributes = HashMap::new();
+        for (name, value) in &attributes.0 {
+            let Some(name) = name.strip_prefix("X-") else {
+                continue;
+            };
+
+            let value = match value {
+                RawValue::Quoted(string) => AttributeValue::String((*string).into()),
+                RawValue::Unquoted(other) => {
+                    if let Some(bytes) = parse_hex(other) {
+                        AttributeValue::Bytes(bytes)
+                    } else if let Some(float) = parse_float(other) {
+                        AttributeValue::Float(float)
+                    } else {
+                        return Err(invalid_attribute(&format!("X-{name}"), other));
+                    }
+                }
+            };
+            client_attributes.insert(name.into(), value);
+        }
+
+        Ok(Self {
+            id: attributes.required("ID", Attributes::quoted)?.into(),
+            class: attributes.quoted("CLASS")?.map(Into::into),
+            start_date: attributes.required("START-DATE", Attributes::date)?,
+            cue,
+            end_date: attributes.date("END-DATE")?,
+            duration_seconds: attributes.float("DURATION")?,
+            planned_duration_seconds: attributes.float("PLANNED-DURATION")?,
+            client_attributes,
+            scte35_cmd: attributes.hex("SCTE35-CMD")?.unwrap_or_default(),
+            scte35_in: attributes.hex("SCTE35-IN")?.unwrap_or_default(),
+            scte35_out: attributes.hex("SCTE35-OUT")?.unwrap_or_default(),
+            end_on_next: attributes.yes("END-ON-NEXT")?,
+        })
+    }
+}
+
+impl AudioChannelInformation {
+    fn parse(value: &str) -> Option<Self> {
+        let mut parameters = value.split('/');
+        let number_of_channels = parse_integer(parameters.next()?)?;
+
+        let Some(identifiers) = parameters.next() else {
+            return Some(Self::NumberOfChannelsOnly { number_of_channels });
+        };
+        let audio_coding_identifiers = if identifiers == "-" {
+            vec![]
+        } else {
+            identifiers.split(',').map(Into::into).collect()
+        };
+
+        let Some(special_usage) = parameters.next() else {
+            return Some(Self::WithAudioCodingIdentifiers {
+                number_of_channels,
+                audio_coding_identifiers,
+            });
+        };
+        let (mut binaural, mut immersive, mut downmix) = (false, false, false);
+        for identifier in special_usage.split(',') {
+            match identifier {
+                "BINAURAL" => binaural = true,
+                "IMMERSIVE" => immersive = true,
+                "DOWNMIX" => downmix = true,
+                _ => (),
+            }
+        }
+
+        Some(Self::WithSpecialUsageIdentifiers {
+            number_of_channels,
+            audio_coding_identifiers,
+            binaural,
+            immersive,
+            downmix,
+        })
+    }
+}
+
+impl InStreamId {
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "CC1" => Some(Self::Cc1),
+            "CC2" => Some(Self::Cc2),
+            "CC3" => Some(Self::Cc3),
+            "CC4" => Some(Self::Cc4),
+            other => {
+                let service = other
+                    .strip_prefix("SERVICE")
+                    .and_then(|number| number.parse::<NonZeroU8>().ok())?;
+
+                (service.get() <= 63).then_some(Self::Service(service))
+            }
+        }
+    }
+}
+
+/// An unparsed attribute value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RawValue<'a> {
+    /// A quoted-string, without the surrounding quotes.
+    Quoted(&'a str),
+
+    /// Any other kind of attribute value.
+    Unquoted(&'a str),
+}
+
+impl<'a> RawValue<'a> {
+    const fn as_str(&self) -> &'a str {
+        match self {
+            Self::Quoted(value) | Self::Unquoted(value) => value,
+        }
+    }
+}
+
+/// An attribute list split into its attributes, in the order they were written.
+struct Attributes<'a>(Vec<(&'a str, RawValue<'a>)>);
+
+impl<'a> Attributes<'a> {
+    fn parse(input: Option<&'a str>) -> Result<Self, ParseTagError> {
+        let mut input = input.ok_or(ParseTagError::MissingValue)?;
+        let mut attributes = Vec::new();
+
+        while !input.is_empty() {
+            let (name, rest) = input
+                .split_once('=')
+                .ok_or(ParseTagError::MalformedAttributeList)?;
+            if name.is_empty()
+                || !name
+                    .bytes()
+                    .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit() || byte == b'-')
+            {
+                return Err(ParseTagError::MalformedAttributeList);
+            }
+
+            let (value, rest) = if let Some(quoted) = rest.strip_prefix('"') {
+                let (value, rest) = quoted
+                    .split_once('"')
+                    .ok_or(ParseTagError::MalformedAttributeList)?;
+
+                (RawValue::Quoted(value), rest)
+            } else {
+                let end = rest.find(',').unwrap_or(rest.len());
+
+                (RawValue::Unquoted(&rest[..end]), &rest[end..])
+            };
+
+            input = match rest.strip_prefix(',') {
+                Some(rest) if !rest.is_empty() => rest,
+                None if rest.is_empty() => rest,
+                _ => return Err(ParseTagError::MalformedAttributeList),
+            };
+            attributes.push((name, value));
+        }
+
+        Ok(Self(attributes))
+    }
+
+    fn get(&self, name: &str) -> Option<RawValue<'a>> {
+        self.0
+            .iter()
+            .find(|(attribute_name, _)| *attribute_name == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Parses a required attribute with `parse`, one of the other methods on `Attributes`.
+    fn required<T>(
+        &self,
+        name: &'static str,
+        parse: impl FnOnce(&Self, &'static str) -> Result<Option<T>, ParseTagError>,
+    ) -> Result<T, ParseTagError> {
+        parse(self, name)?.ok_or(ParseTagError::MissingAttribute(name))
+    }
+
+    fn quoted(&self, name: &'static str) -> Result<Option<&'a str>, ParseTagError> {
+        match self.get(name) {
+            Some(RawValue::Quoted(value)) => Ok(Some(value)),
+            Some(RawValue::Unquoted(value)) => Err(invalid_attribute(name, value)),
+            None => Ok(None),
+        }
+    }
+
+    fn enumerated(&self, name: &'static str) -> Result<Option<&'a str>, ParseTagError> {
+        match self.get(name) {
+            Some(RawValue::Unquoted(value)) => Ok(Some(value)),
+            Some(RawValue::Quoted(value)) => Err(invalid_attribute(name, value)),
+            None => Ok(None),
+        }
+    }
+
+    fn yes(&self, name: &'static str) -> Result<bool, ParseTagError> {
+        match self.enumerated(name)? {
+            Some("YES") => Ok(true),
+            Some("NO") | None => Ok(false),
+            Some(other) => Err(invalid_attribute(name, other)),
+        }
+    }
+
+    fn decimal_integer(&self, name: &'static str) -> Result<Option<u64>, ParseTagError> {
+        self.enumerated(name)?
+            .map(|value| parse_integer(value).ok_or_else(|| invalid_attribute(name, value)))
+            .transpose()
+    }
+
+    fn float(&self, name: &'static str) -> Result<Option<f64>, ParseTagError> {
+        self.enumerated(name)?
+            .map(|value| parse_float(value).ok_or_else(|| invalid_attribute(name, value)))
+            .transpose()
+    }
+
+    fn hex(&self, name: &'static str) -> Result<Option<Vec<u8>>, ParseTagError> {
+        self.enumerated(name)?
+            .map(|value| parse_hex(value).ok_or_else(|| invalid_attribute(name, value)))
+            .transpose()
+    }
+
+    fn date(
+        &self,
+        name: &'static str,
+    ) -> Result<Option<chrono::DateTime<chrono::FixedOffset>>, ParseTagError> {
+        self.quoted(name)?
+            .map(|value| {
+                chrono::DateTime::parse_from_rfc3339(value)
+                    .map_err(|_| invalid_attribute(name, value))
+            })
+            .transpose()
+    }
+
+    fn byte_range(&self, name: &'static str) -> Result<Option<ByteRange>, ParseTagError> {
+        self.quoted(name)?
+            .map(|value| ByteRange::parse(value).ok_or_else(|| invalid_attribute(name, value)))
+            .transpose()
+    }
+
+    fn byte_range_with_offset(
+        &self,
+        name: &'static str,
+    ) -> Result<Option<ByteRangeWithOffset>, ParseTagError> {
+        self.quoted(name)?
+            .map(|value| match ByteRange::parse(value) {
+                Some(ByteRange {
+                    length_bytes,
+                    start_offset_bytes: Some(start_offset_bytes),
+                }) => Ok(ByteRangeWithOffset {
+                    length_bytes,
+                    start_offset_bytes,
+                }),
+                _ => Err(invalid_attribute(name, value)),
+            })
+            .transpose()
+    }
+}
+
+fn invalid_attribute(name: &str, value: &str) -> ParseTagError {
+    ParseTagError::InvalidAttribute {
+        name: name.into(),
+        value: value.into(),
+    }
+}
+
+fn strip_line_ending(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Splits a tag line into its name and value, if it has a value.
+fn split_name(line: &str) -> (&str, Option<&str>) {
+    match line.split_once(':') {
+        Some((name, value)) => (name, Some(value)),
+        None => (line, None),
+    }
+}
+
+fn no_value(value: Option<&str>, tag: Tag) -> Result<Tag, ParseTagError> {
+    value.map_or(Ok(tag), |value| {
+        Err(ParseTagError::InvalidValue(value.into()))
+    })
+}
+
+fn parse_value<T: FromStr>(value: Option<&str>) -> Result<T, ParseTagError> {
+    let value = value.ok_or(ParseTagError::MissingValue)?;
+    if !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(ParseTagError::InvalidValue(value.into()));
+    }
+
+    value
+        .parse()
+        .map_err(|_| ParseTagError::InvalidValue(value.into()))
+}
+
+fn parse_integer(value: &str) -> Option<u64> {
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    value.parse().ok()
+}
+
+fn parse_float(value: &str) -> Option<f64> {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    if digits.is_empty()
+        || !digits
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || byte == b'.')
+    {
+        return None;
+    }
+
+    value.parse().ok()
+}
+
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))?;
+
+    if digits.len() % 2 == 0 {
+        hex::decode(digits).ok()
+    } else {
+        hex::decode(format!("0{digits}")).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::playlist::{
+        AudioRendition, ClosedCaptionRendition, RenditionGroup, RenditionInfo, SubtitleRendition,
+    };
+
+    use super::*;
+
+    fn round_trip(tag: &Tag) {
+        let mut output = Vec::new();
+        tag.serialize(&mut output).unwrap();
+        assert_eq!(
+            &Tag::parse(std::str::from_utf8(&output).unwrap()).unwrap(),
+            tag
+        );
+    }
+
+    #[test]
+    fn parse_basic_tags() {
+        assert_eq!(Tag::parse("#EXTM3U").unwrap(), Tag::M3u);
+        assert_eq!(
+            Tag::parse("#EXT-X-VERSION:12\n").unwrap(),
+            Tag::XVersion { version: 12 }
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-INDEPENDENT-SEGMENTS\r\n").unwrap(),
+            Tag::XIndependentSegments
+        );
+        assert_eq!(
+            "#EXT-X-TARGETDURATION:6".parse::<Tag>().unwrap(),
+            Tag::XTargetDuration {
+                target_duration_seconds: 6
+            }
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-PLAYLIST-TYPE:VOD").unwrap(),
+            Tag::XPlaylistType(PlaylistType::Vod)
+        );
+        assert_eq!(Tag::parse("#EXT-X-ENDLIST").unwrap(), Tag::XEndList);
+        assert_eq!(
+            Tag::parse("#EXT-X-BITRATE:8000").unwrap(),
+            Tag::XBitrate { kbps: 8000 }
+        );
+    }
+
+    #[test]
+    fn parse_inf() {
+        assert_eq!(
+            Tag::parse("#EXTINF:5.34").unwrap(),
+            Tag::Inf {
+                duration_seconds: FloatOrInteger::Float(5.34),
+                title: String::new(),
+            }
+        );
+        assert_eq!(
+            Tag::parse("#EXTINF:5,super, cool title").unwrap(),
+            Tag::Inf {
+                duration_seconds: FloatOrInteger::Integer(5),
+                title: "super, cool title".into(),
+            }
+        );
+        assert_eq!(
+            Tag::parse("#EXTINF:five"),
+            Err(ParseTagError::InvalidValue("five".into()))
+        );
+    }
+
+    #[test]
+    fn parse_x_define() {
+        round_trip(&Tag::XDefine(DefinitionType::Inline {
+            name: "cool-param_A0".into(),
+            value: "I am so cool".into(),
+        }));
+        round_trip(&Tag::XDefine(DefinitionType::Import {
+            name: "foobar-_A0".into(),
+        }));
+        round_trip(&Tag::XDefine(DefinitionType::QueryParameter {
+            name: "bAz-_42".into(),
+        }));
+    }
+
+    #[test]
+    fn parse_x_start() {
+        round_trip(&Tag::XStart {
+            offset_seconds: -84.0,
+            is_precise: false,
+        });
+        round_trip(&Tag::XStart {
+            offset_seconds: 5.0053,
+            is_precise: true,
+        });
+    }
+
+    #[test]
+    fn parse_x_key() {
+        round_trip(&Tag::XKey(None));
+        round_trip(&Tag::XKey(Some(EncryptionMethod::Aes128 {
+            uri: "https://example.com/foo.key".into(),
+            iv: Some(0x0F91_DC05),
+            key_format: KeyFormat::Other("super cool key format".into()),
+            key_format_versions: vec![1, 16],
+        })));
+        round_trip(&Tag::XKey(Some(EncryptionMethod::SampleAes {
+            uri: "https://example.com/foo.key".into(),
+            iv: None,
+            key_format_versions: vec![1, 16],
+        })));
+        round_trip(&Tag::XSessionKey(EncryptionMethod::SampleAesCtr {
+            uri: "https://example.com/foo.key".into(),
+            key_format_versions: vec![1, 16],
+        }));
+
+        assert_eq!(
+            Tag::parse("#EXT-X-KEY:METHOD=AES-128"),
+            Err(ParseTagError::MissingAttribute("URI"))
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-KEY:METHOD=AES-128,URI=\"a.key\",IV=0xZZ"),
+            Err(ParseTagError::InvalidAttribute {
+                name: "IV".into(),
+                value: "0xZZ".into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_x_map_and_byterange() {
+        round_trip(&Tag::XMap {
+            uri: "https://example.com/0.mp4".into(),
+            range: Some(ByteRangeWithOffset {
+                length_bytes: 400,
+                start_offset_bytes: 0,
+            }),
+        });
+        round_trip(&Tag::XByterange(ByteRange {
+            length_bytes: 1200,
+            start_offset_bytes: Some(158),
+        }));
+        round_trip(&Tag::XByterange(ByteRange {
+            length_bytes: 1200,
+            start_offset_bytes: None,
+        }));
+    }
+
+    #[test]
+    fn parse_x_program_date_time() {
+        round_trip(&Tag::XProgramDateTime(
+            chrono::DateTime::parse_from_rfc3339("2010-02-19T14:54:23.031+08:00").unwrap(),
+        ));
+    }
+
+    #[test]
+    fn parse_x_part() {
+        round_trip(&Tag::XPart {
+            uri: "https://example.com/1.mp4".into(),
+            duration_seconds: 2.5,
+            is_independent: true,
+            byte_range: Some(ByteRange {
+                length_bytes: 400,
+                start_offset_bytes: Some(0),
+            }),
+            is_gap: true,
+        });
+        round_trip(&Tag::XPartInf {
+            part_target_duration_seconds: 2.5,
+        });
+    }
+
+    #[test]
+    fn parse_x_server_control() {
+        round_trip(&Tag::XServerControl {
+            delta_update_info: Some(DeltaUpdateInfo {
+                skip_boundary_seconds: 20.873,
+                can_skip_dateranges: true,
+            }),
+            hold_back: Some(10.0),
+            part_hold_back: Some(10.285),
+            can_block_reload: true,
+        });
+        round_trip(&Tag::XServerControl {
+            delta_update_info: None,
+            hold_back: None,
+            part_hold_back: None,
+            can_block_reload: false,
+        });
+    }
+
+    #[test]
+    fn parse_x_media() {
+        let groups = [
+            RenditionGroup::Audio {
+                group_id: "really cool group".into(),
+                renditions: vec![AudioRendition {
+                    bit_depth: Some(16),
+                    sample_rate: Some(40000),
+                    channels: Some(AudioChannelInformation::WithSpecialUsageIdentifiers {
+                        number_of_channels: 2,
+                        audio_coding_identifiers: vec!["idk".into(), "kinda weird".into()],
+                        binaural: true,
+                        immersive: false,
+                        downmix: true,
+                    }),
+                    info: RenditionInfo {
+                        language: Some("en-US".into()),
+                        assoc_language: Some("de".into()),
+                        name: "english audio".into(),
+                        priority: RenditionPlaybackPriority::Default,
+                        characteristics: vec![
+                            "public.accessibility.describes-video".into(),
+                            "private.cool.example".into(),
+                        ],
+                        stable_rendition_id: Some("azBY09+/=.-_".into()),
+                    },
+                    uri: Some("https://example.com/1.m3u8".into()),
+                }],
+            },
+            RenditionGroup::Subtitles {
+                group_id: "subs".into(),
+                renditions: vec![SubtitleRendition {
+                    info: RenditionInfo {
+                        language: None,
+                        assoc_language: None,
+                        name: "forced".into(),
+                        priority: RenditionPlaybackPriority::AutoSelect,
+                        characteristics: vec![],
+                        stable_rendition_id: None,
+                    },
+                    forced: true,
+                    uri: "whyeven.m3u8".into(),
+                }],
+            },
+            RenditionGroup::ClosedCaptions {
+                group_id: "cc".into(),
+                renditions: vec![ClosedCaptionRendition {
+                    in_stream_id: InStreamId::Service(NonZeroU8::new(63).unwrap()),
+                    info: RenditionInfo {
+                        language: None,
+                        assoc_language: None,
+                        name: "captions".into(),
+                        priority: RenditionPlaybackPriority::None,
+                        characteristics: vec![],
+                        stable_rendition_id: None,
+                    },
+                }],
+            },
+        ];
+
+        for group in groups {
+            let mut output = Vec::new();
+            crate::playlist::MultivariantPlaylist {
+                renditions_groups: vec![group.clone()],
+                ..Default::default()
+            }
+            .serialize(&mut output)
+            .unwrap();
+
+            let line = std::str::from_utf8(&output)
+                .unwrap()
+                .lines()
+                .find(|line| line.starts_with("#EXT-X-MEDIA"))
+                .unwrap();
+            round_trip(&Tag::parse(line).unwrap());
+        }
+
+        assert_eq!(
+            Tag::parse("#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"subs\",NAME=\"English\""),
+            Err(ParseTagError::MissingAttribute("URI"))
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-MEDIA:TYPE=CLOSED-CAPTIONS,GROUP-ID=\"cc\",NAME=\"cc\",INSTREAM-ID=\"SERVICE64\""),
+            Err(ParseTagError::InvalidAttribute {
+                name: "INSTREAM-ID".into(),
+                value: "SERVICE64".into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_x_stream_inf() {
+        let tag = Tag::XStreamInf {
+            stream_inf: StreamInf {
+                bandwidth_bits_per_second: 82006,
+                average_bandwidth_bits_per_second: Some(80000),
+                score: Some(2.0),
+                codecs: vec!["mp4a.40.2".into(), "avc1.4d401e".into()],
+                supplemental_codecs: vec![SupplementalCodec {
+                    supplemental_codec: "dvh1.08.07".into(),
+                    compatibility_brands: vec!["db4h".into(), "idk".into()],
+                }],
+                resolution: Some(Resolution {
+                    width: 1080,
+                    height: 1920,
+                }),
+                hdcp_level: Some(HdcpLevel::Type1),
+                allowed_cpc: vec![
+                    ContentProtectionConfiguration {
+                        key_format: "urn:uuid:edef8ba9-79d6-4ace-a3c8-27dcd51d21ed".into(),
+                        cpc_labels: vec!["SMART-TV".into(), "PC".into()],
+                    },
+                    ContentProtectionConfiguration {
+                        key_format: "com.example.drm2".into(),
+                        cpc_labels: vec![],
+                    },
+                ],
+                video_range: VideoRange::Pq,
+                required_video_layout: vec![
+                    VideoChannelSpecifier::Stereo,
+                    VideoChannelSpecifier::Mono,
+                ],
+                stable_variant_id: Some("azBY09+/=.-_".into()),
+                pathway_id: Some("cool-pathway".into()),
+            },
+            frame_rate: Some(59.943),
+            audio_group_id: Some("great-audio".into()),
+            video_group_id: Some("great-video".into()),
+            subtitles_group_id: Some("great-subtitles".into()),
+            closed_captions_group_id: Some("great-closed-captions".into()),
+            uri: "great-playlist.m3u8".into(),
+        };
+        round_trip(&tag);
+
+        assert_eq!(
+            Tag::parse("#EXT-X-STREAM-INF:BANDWIDTH=82006"),
+            Err(ParseTagError::MissingUri)
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-STREAM-INF:AVERAGE-BANDWIDTH=82006\n1.m3u8"),
+            Err(ParseTagError::MissingAttribute("BANDWIDTH"))
+        );
+        assert!(matches!(
+            Tag::parse("#EXT-X-STREAM-INF:BANDWIDTH=1,CLOSED-CAPTIONS=NONE\n1.m3u8").unwrap(),
+            Tag::XStreamInf {
+                closed_captions_group_id: None,
+                ..
+            }
+        ));
+
+        round_trip(&Tag::XIFrameStreamInf {
+            stream_inf: StreamInf {
+                bandwidth_bits_per_second: 8000,
+                average_bandwidth_bits_per_second: None,
+                score: None,
+                codecs: vec![],
+                supplemental_codecs: vec![],
+                resolution: None,
+                hdcp_level: None,
+                allowed_cpc: vec![],
+                video_range: VideoRange::Sdr,
+                required_video_layout: vec![],
+                stable_variant_id: None,
+                pathway_id: None,
+            },
+            video_group_id: Some("great-video".into()),
+            uri: "https://example.com/example.m3u8".into(),
+        });
+    }
+
+    #[test]
+    fn parse_x_session_data() {
+        round_trip(&Tag::XSessionData(SessionData {
+            data_id: "com.example.movie.title".into(),
+            value: SessionDataValue::Value {
+                value: "I'm important, really".into(),
+                language: Some("en".into()),
+            },
+        }));
+        round_trip(&Tag::XSessionData(SessionData {
+            data_id: "com.example.movie.title".into(),
+            value: SessionDataValue::Uri {
+                uri: "/important.bin".into(),
+                format: UriFormat::Raw,
+            },
+        }));
+    }
+
+    #[test]
+    fn parse_x_content_steering() {
+        round_trip(&Tag::XContentSteering(ContentSteering {
+            server_uri: "https://example.com/manifest.json".into(),
+            pathway_id: Some("hi".into()),
+        }));
+    }
+
+    #[test]
+    fn parse_x_daterange() {
+        let mut client_attributes = HashMap::new();
+        client_attributes.insert(
+            "COM-EXAMPLE-STRING".into(),
+            AttributeValue::String("a".into()),
+        );
+        client_attributes.insert(
+            "COM-EXAMPLE-BYTES".into(),
+            AttributeValue::Bytes(vec![0xAB, 0x01]),
+        );
+        client_attributes.insert("COM-EXAMPLE-FLOAT".into(), AttributeValue::Float(-2.5));
+
+        round_trip(&Tag::XDateRange(DateRange {
+            id: "splice-6FFFFFF0".into(),
+            class: Some("com.example.ad".into()),
+            start_date: chrono::DateTime::parse_from_rfc3339("2014-03-05T11:15:00Z").unwrap(),
+            cue: Some(DateRangeCue {
+                once: true,
+                position: DateRangeCuePosition::Pre,
+            }),
+            end_date: None,
+            duration_seconds: Some(59.993),
+            planned_duration_seconds: Some(60.0),
+            client_attributes,
+            scte35_cmd: vec![],
+            scte35_in: vec![],
+            scte35_out: vec![0xFC, 0x00, 0x2F],
+            end_on_next: false,
+        }));
+
+        assert_eq!(
+            Tag::parse("#EXT-X-DATERANGE:ID=\"a\",START-DATE=\"yesterday\""),
+            Err(ParseTagError::InvalidAttribute {
+                name: "START-DATE".into(),
+                value: "yesterday".into()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_low_latency_tags() {
+        round_trip(&Tag::XSkip {
+            number_of_skipped_segments: 42,
+            recently_removed_dataranges: vec!["one".into(), "two".into()],
+        });
+        round_trip(&Tag::XPreloadHint(PreloadHint {
+            hint_type: PreloadHintType::Part,
+            uri: "https://example.com/1.mp4".into(),
+            start_byte_offset: 400,
+            length_in_bytes: Some(400),
+        }));
+        round_trip(&Tag::XRenditionReport(RenditionReport {
+            uri: "/2.m3u8".into(),
+            last_sequence_number: Some(420),
+            last_part_index: Some(1),
+        }));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(
+            Tag::parse("https://example.com/1.mp4"),
+            Err(ParseTagError::NotATag)
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-COOL-TAG:YES"),
+            Err(ParseTagError::UnknownTag("#EXT-X-COOL-TAG".into()))
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-VERSION"),
+            Err(ParseTagError::MissingValue)
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-ENDLIST\n#EXTM3U"),
+            Err(ParseTagError::TrailingInput)
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-PART:URI=\"1.mp4,DURATION=1"),
+            Err(ParseTagError::MalformedAttributeList)
+        );
+        assert_eq!(
+            Tag::parse("#EXT-X-PART:URI=1.mp4,DURATION=1"),
+            Err(ParseTagError::InvalidAttribute {
+                name: "URI".into(),
+                value: "1.mp4".into()
+            })
+        );
+    }
+}
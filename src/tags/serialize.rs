@@ -12,18 +12,35 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::io;
+use std::{fmt, io};
 
 use crate::{
     ByteRange, ContentSteering, DateRange, PreloadHint, RenditionPlaybackPriority, RenditionReport,
     SessionData, StreamInf,
 };
 
-use super::{MediaType, Tag};
+use super::{MediaTypeRef, Tag, TagRef};
+
+/// Formats the tag exactly as [`Tag::serialize`] writes it, including the
+/// trailing newline.
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_to(f)
+    }
+}
+
+/// Formats the tag exactly as [`TagRef::serialize`] writes it, including the
+/// trailing newline.
+impl fmt::Display for TagRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_to(f)
+    }
+}
 
 impl Tag {
     /// Serializes the `Tag` as a extended M3U playlist tag into `output`.
-    /// Guaranteed to write valid UTF-8 only.
+    /// Guaranteed to write valid UTF-8 only. To serialize into a `String`,
+    /// use the [`fmt::Display`] implementation instead.
     ///
     /// This method makes lots of small calls to write on `output`. If the implementation
     /// of write on `output` makes a syscall, like with a `TcpStream`, you should wrap it
@@ -32,14 +49,40 @@ impl Tag {
     /// # Note
     ///
     /// This method is not guaranteed to write valid M3U tags. It's your job to create
-    /// valid input.
+    /// valid input, which can be checked with [`Tag::validate`]. Values containing
+    /// double quotes or newlines are written as is, so use [`Tag::serialize_strict`]
+    /// if any of them come from untrusted input.
+    ///
+    /// # Errors
+    ///
+    /// May return `Err` when encountering an io error on `output`.
+    pub fn serialize(&self, output: impl io::Write) -> io::Result<()> {
+        TagRef::from(self).serialize(output)
+    }
+
+    /// Writes the `Tag` like [`Tag::serialize`], into anything implementing
+    /// [`fmt::Write`].
+    pub(crate) fn write_to(&self, output: impl fmt::Write) -> fmt::Result {
+        TagRef::from(self).write_to(output)
+    }
+}
+
+impl TagRef<'_> {
+    /// Serializes the `TagRef` like [`Tag::serialize`], without cloning any
+    /// of its values.
     ///
     /// # Errors
     ///
     /// May return `Err` when encountering an io error on `output`.
-    pub fn serialize(&self, mut output: impl io::Write) -> io::Result<()> {
+    pub fn serialize(self, output: impl io::Write) -> io::Result<()> {
+        crate::write_io(output, |output| self.write_to(output))
+    }
+
+    /// Writes the `TagRef` like [`TagRef::serialize`], into anything
+    /// implementing [`fmt::Write`].
+    pub(crate) fn write_to(self, mut output: impl fmt::Write) -> fmt::Result {
         match self {
-            Self::M3u => output.write_all(b"#EXTM3U")?,
+            Self::M3u => output.write_str("#EXTM3U")?,
             Self::XVersion { version } => write!(output, "#EXT-X-VERSION:{version}")?,
             Self::XDefine(definition) => match definition {
                 crate::DefinitionType::Inline { name, value } => {
@@ -57,11 +100,11 @@ impl Tag {
                 is_precise,
             } => {
                 write!(output, "#EXT-X-START:TIME-OFFSET={offset_seconds}")?;
-                if *is_precise {
+                if is_precise {
                     write!(output, ",PRECISE=YES")?;
                 }
             }
-            Self::XIndependentSegments => output.write_all(b"#EXT-X-INDEPENDENT-SEGMENTS")?,
+            Self::XIndependentSegments => output.write_str("#EXT-X-INDEPENDENT-SEGMENTS")?,
             Self::Inf {
                 duration_seconds,
                 title,
@@ -99,7 +142,8 @@ impl Tag {
                 }
             }
             Self::XProgramDateTime(time) => {
-                write!(output, "#EXT-X-PROGRAM-DATE-TIME:{}", time.to_rfc3339())?;
+                write!(output, "#EXT-X-PROGRAM-DATE-TIME:")?;
+                serialize_date_time(&mut output, time)?;
             }
             Self::XGap => write!(output, "#EXT-X-GAP")?,
             Self::XBitrate { kbps } => write!(output, "#EXT-X-BITRATE:{kbps}")?,
@@ -112,10 +156,10 @@ impl Tag {
             } => Self::serialize_x_part(
                 &mut output,
                 uri,
-                *duration_seconds,
-                *is_independent,
+                duration_seconds,
+                is_independent,
                 byte_range,
-                *is_gap,
+                is_gap,
             )?,
             Self::XTargetDuration {
                 target_duration_seconds,
@@ -148,7 +192,7 @@ impl Tag {
                 delta_update_info,
                 hold_back,
                 part_hold_back,
-                *can_block_reload,
+                can_block_reload,
             )?,
             Self::XMedia {
                 media_type,
@@ -211,7 +255,7 @@ impl Tag {
                 recently_removed_dataranges,
             } => Self::serialize_x_skip(
                 &mut output,
-                *number_of_skipped_segments,
+                number_of_skipped_segments,
                 recently_removed_dataranges,
             )?,
             Self::XPreloadHint(preload_hint) => {
@@ -222,41 +266,40 @@ impl Tag {
             }
         };
 
-        output.write_all(b"\n")?;
-        Ok(())
+        output.write_str("\n")
     }
 
     #[allow(clippy::too_many_arguments)]
     fn serialize_x_media(
-        mut output: impl io::Write,
-        media_type: &MediaType,
-        group_id: &String,
-        language: &Option<String>,
-        assoc_language: &Option<String>,
-        name: &String,
-        stable_rendition_id: &Option<String>,
+        mut output: impl fmt::Write,
+        media_type: MediaTypeRef<'_>,
+        group_id: &str,
+        language: Option<&str>,
+        assoc_language: Option<&str>,
+        name: &str,
+        stable_rendition_id: Option<&str>,
         playback_priority: &RenditionPlaybackPriority,
         characteristics: &[String],
-    ) -> io::Result<()> {
+    ) -> fmt::Result {
         match media_type {
-            MediaType::Audio { uri, .. } => {
+            MediaTypeRef::Audio { uri, .. } => {
                 write!(output, "#EXT-X-MEDIA:TYPE=AUDIO")?;
 
                 if let Some(uri) = uri {
                     write!(output, ",URI=\"{uri}\"")?;
                 }
             }
-            MediaType::Video { uri } => {
+            MediaTypeRef::Video { uri } => {
                 write!(output, "#EXT-X-MEDIA:TYPE=VIDEO")?;
 
                 if let Some(uri) = uri {
                     write!(output, ",URI=\"{uri}\"")?;
                 }
             }
-            MediaType::Subtitles { uri, .. } => {
+            MediaTypeRef::Subtitles { uri, .. } => {
                 write!(output, "#EXT-X-MEDIA:TYPE=SUBTITLES,URI=\"{uri}\"")?;
             }
-            MediaType::ClosedCaptions { .. } => {
+            MediaTypeRef::ClosedCaptions { .. } => {
                 write!(output, "#EXT-X-MEDIA:TYPE=CLOSED-CAPTIONS")?;
             }
         };
@@ -283,11 +326,11 @@ impl Tag {
             RenditionPlaybackPriority::None => (),
         }
 
-        if let MediaType::Subtitles { forced: true, .. } = media_type {
+        if let MediaTypeRef::Subtitles { forced: true, .. } = media_type {
             write!(output, ",FORCED=YES")?;
         }
 
-        if let MediaType::ClosedCaptions { in_stream_id } = media_type {
+        if let MediaTypeRef::ClosedCaptions { in_stream_id } = media_type {
             match in_stream_id {
                 crate::InStreamId::Cc1 => write!(output, ",INSTREAM-ID=\"CC1\"")?,
                 crate::InStreamId::Cc2 => write!(output, ",INSTREAM-ID=\"CC2\"")?,
@@ -297,7 +340,7 @@ impl Tag {
             }
         }
 
-        if let MediaType::Audio {
+        if let MediaTypeRef::Audio {
             bit_depth,
             sample_rate,
             ..
@@ -330,7 +373,7 @@ impl Tag {
             write!(output, "\"")?;
         }
 
-        if let MediaType::Audio {
+        if let MediaTypeRef::Audio {
             channels: Some(channels),
             ..
         } = media_type
@@ -410,15 +453,15 @@ impl Tag {
 
     #[allow(clippy::too_many_arguments)]
     fn serialize_x_stream_inf(
-        mut output: impl io::Write,
+        mut output: impl fmt::Write,
         stream_inf: &StreamInf,
-        frame_rate: &Option<f64>,
-        audio_group_id: &Option<String>,
-        video_group_id: &Option<String>,
-        subtitles_group_id: &Option<String>,
-        closed_captions_group_id: &Option<String>,
-        uri: &String,
-    ) -> io::Result<()> {
+        frame_rate: Option<f64>,
+        audio_group_id: Option<&str>,
+        video_group_id: Option<&str>,
+        subtitles_group_id: Option<&str>,
+        closed_captions_group_id: Option<&str>,
+        uri: &str,
+    ) -> fmt::Result {
         write!(output, "#EXT-X-STREAM-INF:")?;
         stream_inf.serialize(&mut output)?;
 
@@ -445,11 +488,11 @@ impl Tag {
     }
 
     fn serialize_x_i_frame_stream_inf(
-        mut output: impl io::Write,
+        mut output: impl fmt::Write,
         stream_inf: &StreamInf,
-        video_group_id: &Option<String>,
-        uri: &String,
-    ) -> io::Result<()> {
+        video_group_id: Option<&str>,
+        uri: &str,
+    ) -> fmt::Result {
         write!(output, "#EXT-X-I-FRAME-STREAM-INF:")?;
         stream_inf.serialize(&mut output)?;
 
@@ -463,9 +506,9 @@ impl Tag {
     }
 
     fn serialize_x_session_data(
-        mut output: impl io::Write,
+        mut output: impl fmt::Write,
         session_data: &SessionData,
-    ) -> io::Result<()> {
+    ) -> fmt::Result {
         write!(
             output,
             "#EXT-X-SESSION-DATA:DATA-ID=\"{}\"",
@@ -494,9 +537,9 @@ impl Tag {
     }
 
     fn serialize_x_content_steering(
-        mut output: impl io::Write,
+        mut output: impl fmt::Write,
         content_steering: &ContentSteering,
-    ) -> io::Result<()> {
+    ) -> fmt::Result {
         write!(
             output,
             "#EXT-X-CONTENT-STEERING:SERVER-URI=\"{}\"",
@@ -511,9 +554,9 @@ impl Tag {
     }
 
     fn serialize_x_rendition_report(
-        mut output: impl io::Write,
+        mut output: impl fmt::Write,
         report: &RenditionReport,
-    ) -> io::Result<()> {
+    ) -> fmt::Result {
         let uri = &report.uri;
         write!(output, "#EXT-X-RENDITION-REPORT:URI=\"{uri}\"")?;
 
@@ -529,9 +572,9 @@ impl Tag {
     }
 
     fn serialize_x_preload_hint(
-        mut output: impl io::Write,
+        mut output: impl fmt::Write,
         preload_hint: &PreloadHint,
-    ) -> io::Result<()> {
+    ) -> fmt::Result {
         let hint_type = match preload_hint.hint_type {
             crate::PreloadHintType::Part => "PART",
             crate::PreloadHintType::Map => "MAP",
@@ -558,10 +601,10 @@ impl Tag {
     }
 
     fn serialize_x_skip(
-        mut output: impl io::Write,
+        mut output: impl fmt::Write,
         number_of_skipped_segments: u64,
         recently_removed_dataranges: &[String],
-    ) -> io::Result<()> {
+    ) -> fmt::Result {
         write!(
             output,
             "#EXT-X-SKIP:SKIPPED-SEGMENTS={number_of_skipped_segments}"
@@ -588,18 +631,16 @@ impl Tag {
         Ok(())
     }
 
-    fn serialize_x_daterange(mut output: impl io::Write, daterange: &DateRange) -> io::Result<()> {
+    fn serialize_x_daterange(mut output: impl fmt::Write, daterange: &DateRange) -> fmt::Result {
         write!(output, "#EXT-X-DATERANGE:ID=\"{}\"", daterange.id)?;
 
         if let Some(class) = &daterange.class {
             write!(output, ",CLASS=\"{class}\"")?;
         }
 
-        write!(
-            output,
-            ",START-DATE=\"{}\"",
-            daterange.start_date.to_rfc3339()
-        )?;
+        write!(output, ",START-DATE=\"")?;
+        serialize_date_time(&mut output, &daterange.start_date)?;
+        write!(output, "\"")?;
 
         if let Some(cue) = &daterange.cue {
             match cue.position {
@@ -623,7 +664,9 @@ impl Tag {
         }
 
         if let Some(end_date) = daterange.end_date {
-            write!(output, ",END-DATE=\"{}\"", end_date.to_rfc3339())?;
+            write!(output, ",END-DATE=\"")?;
+            serialize_date_time(&mut output, &end_date)?;
+            write!(output, "\"")?;
         }
 
         if let Some(duration) = daterange.duration_seconds {
@@ -639,34 +682,26 @@ impl Tag {
             match attribute_value {
                 crate::AttributeValue::String(string) => write!(output, "\"{string}\"")?,
                 crate::AttributeValue::Bytes(bytes) => {
-                    write!(output, "0x{}", hex::encode_upper(bytes.clone()))?;
+                    write!(output, "0x")?;
+                    serialize_hex(&mut output, bytes)?;
                 }
                 crate::AttributeValue::Float(float) => write!(output, "{float}")?,
             };
         }
 
         if !daterange.scte35_cmd.is_empty() {
-            write!(
-                output,
-                ",SCTE35-CMD=0x{}",
-                hex::encode_upper(daterange.scte35_cmd.clone())
-            )?;
+            write!(output, ",SCTE35-CMD=0x")?;
+            serialize_hex(&mut output, &daterange.scte35_cmd)?;
         }
 
         if !daterange.scte35_out.is_empty() {
-            write!(
-                output,
-                ",SCTE35-OUT=0x{}",
-                hex::encode_upper(daterange.scte35_out.clone())
-            )?;
+            write!(output, ",SCTE35-OUT=0x")?;
+            serialize_hex(&mut output, &daterange.scte35_out)?;
         }
 
         if !daterange.scte35_in.is_empty() {
-            write!(
-                output,
-                ",SCTE35-IN=0x{}",
-                hex::encode_upper(daterange.scte35_in.clone())
-            )?;
+            write!(output, ",SCTE35-IN=0x")?;
+            serialize_hex(&mut output, &daterange.scte35_in)?;
         }
 
         if daterange.end_on_next {
@@ -677,13 +712,13 @@ impl Tag {
     }
 
     fn serialize_x_part(
-        mut output: impl io::Write,
-        uri: &String,
+        mut output: impl fmt::Write,
+        uri: &str,
         duration_seconds: f64,
         is_independent: bool,
-        byte_range: &Option<ByteRange>,
+        byte_range: Option<&ByteRange>,
         is_gap: bool,
-    ) -> io::Result<()> {
+    ) -> fmt::Result {
         write!(
             output,
             "#EXT-X-PART:URI=\"{uri}\",DURATION={duration_seconds}"
@@ -707,12 +742,12 @@ impl Tag {
     }
 
     fn serialize_x_server_control(
-        mut output: impl io::Write,
-        delta_update_info: &Option<crate::DeltaUpdateInfo>,
-        hold_back: &Option<f64>,
-        part_hold_back: &Option<f64>,
+        mut output: impl fmt::Write,
+        delta_update_info: Option<&crate::DeltaUpdateInfo>,
+        hold_back: Option<f64>,
+        part_hold_back: Option<f64>,
         can_block_reload: bool,
-    ) -> io::Result<()> {
+    ) -> fmt::Result {
         let mut has_written_attribute = false;
         write!(output, "#EXT-X-SERVER-CONTROL:")?;
 
@@ -761,6 +796,23 @@ impl Tag {
     }
 }
 
+/// Writes `time` exactly like [`chrono::DateTime::to_rfc3339`], but without
+/// allocating a `String`.
+fn serialize_date_time(
+    mut output: impl fmt::Write,
+    time: &chrono::DateTime<chrono::FixedOffset>,
+) -> fmt::Result {
+    write!(output, "{}", time.format("%Y-%m-%dT%H:%M:%S%.f%:z"))
+}
+
+fn serialize_hex(mut output: impl fmt::Write, bytes: &[u8]) -> fmt::Result {
+    for byte in bytes {
+        write!(output, "{byte:02X}")?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -790,6 +842,10 @@ mod tests {
             .serialize(&mut output)
             .unwrap();
         assert_eq!(output, b"#EXT-X-VERSION:12\n");
+        assert_eq!(
+            Tag::XVersion { version: 12 }.to_string(),
+            "#EXT-X-VERSION:12\n"
+        );
     }
 
     #[rstest]
@@ -1587,4 +1643,21 @@ mod tests {
             b"#EXT-X-CONTENT-STEERING:SERVER-URI=\"https://example.com/manifest.json\"\n"
         );
     }
+
+    #[rstest]
+    #[case("2010-02-19T14:54:23.031+08:00")]
+    #[case("2024-01-01T00:00:00+00:00")]
+    #[case("1999-12-31T23:59:59.123456-05:30")]
+    #[case("2024-06-30T12:00:00.000000001+14:00")]
+    fn serialize_date_time_like_rfc3339(mut output: Vec<u8>, #[case] time: &str) {
+        let time = chrono::DateTime::parse_from_rfc3339(time).unwrap();
+        let tag = Tag::XProgramDateTime(time);
+        tag.serialize(&mut output).unwrap();
+
+        assert_eq!(
+            output,
+            format!("#EXT-X-PROGRAM-DATE-TIME:{}\n", time.to_rfc3339()).into_bytes()
+        );
+        assert_eq!(TagRef::from(&tag).to_string().into_bytes(), output);
+    }
 }
@@ -0,0 +1,616 @@
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{error::Error, fmt, io};
+
+use super::{MediaTypeRef, Tag, TagRef};
+use crate::{
+    AttributeValue, AudioChannelInformation, ContentSteering, DateRange, DefinitionType,
+    EncryptionMethod, KeyFormat, PreloadHint, RenditionReport, SerializeError, SessionData,
+    SessionDataValue, StreamInf, VideoRange,
+};
+
+/// A value in a [`Tag`] which can not be serialized as valid M3U.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagViolation {
+    /// A quoted-string attribute value contains a double quote, carriage
+    /// return, or line feed.
+    InvalidQuotedString {
+        /// The name of the attribute, e.g. `URI`.
+        attribute: &'static str,
+
+        /// The invalid value.
+        value: String,
+    },
+
+    /// An item of a list attribute, like `CODECS`, contains a character used
+    /// to separate the items of the list, or is not a valid quoted-string.
+    InvalidListItem {
+        /// The name of the attribute, e.g. `CODECS`.
+        attribute: &'static str,
+
+        /// The invalid item.
+        value: String,
+    },
+
+    /// An enumerated-string attribute value is empty, or contains a double
+    /// quote, comma, or whitespace.
+    InvalidEnumeratedString {
+        /// The name of the attribute, e.g. `VIDEO-RANGE`.
+        attribute: &'static str,
+
+        /// The invalid value.
+        value: String,
+    },
+
+    /// The name of a client-defined EXT-X-DATERANGE attribute, without the
+    /// `X-` prefix, is empty or contains characters other than uppercase
+    /// letters, digits, and `-`.
+    InvalidAttributeName(String),
+
+    /// The string value of a client-defined EXT-X-DATERANGE attribute is not
+    /// a valid quoted-string.
+    InvalidClientAttribute {
+        /// The name of the attribute, without the `X-` prefix.
+        name: String,
+
+        /// The invalid value.
+        value: String,
+    },
+
+    /// The name of an EXT-X-DEFINE variable is empty or contains characters
+    /// other than letters, digits, `-`, and `_`.
+    InvalidVariableName(String),
+
+    /// A URI line is empty, begins with `#`, or contains a carriage return or
+    /// line feed.
+    InvalidUriLine(String),
+
+    /// The title of an EXTINF tag contains a carriage return or line feed.
+    InvalidTitle(String),
+
+    /// The ID of an EXT-X-DATERANGE tag is empty.
+    EmptyDateRangeId,
+}
+
+impl fmt::Display for TagViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidQuotedString { attribute, value } => {
+                write!(
+                    f,
+                    "{attribute} value {value:?} is not a valid quoted string"
+                )
+            }
+            Self::InvalidListItem { attribute, value } => {
+                write!(f, "{attribute} item {value:?} is not valid in a list")
+            }
+            Self::InvalidEnumeratedString { attribute, value } => {
+                write!(
+                    f,
+                    "{attribute} value {value:?} is not a valid enumerated string"
+                )
+            }
+            Self::InvalidAttributeName(name) => {
+                write!(f, "X-{name} is not a valid attribute name")
+            }
+            Self::InvalidClientAttribute { name, value } => {
+                write!(f, "X-{name} value {value:?} is not a valid quoted string")
+            }
+            Self::InvalidVariableName(name) => {
+                write!(f, "{name:?} is not a valid variable name")
+            }
+            Self::InvalidUriLine(uri) => write!(f, "{uri:?} is not a valid URI line"),
+            Self::InvalidTitle(title) => write!(f, "EXTINF title {title:?} contains a newline"),
+            Self::EmptyDateRangeId => write!(f, "EXT-X-DATERANGE has an empty ID"),
+        }
+    }
+}
+
+impl Error for TagViolation {}
+
+impl Tag {
+    /// Checks that every value in the tag can be serialized as valid M3U,
+    /// returning every value which can not.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with at least one violation if the tag is invalid.
+    pub fn validate(&self) -> Result<(), Vec<TagViolation>> {
+        TagRef::from(self).validate()
+    }
+
+    /// Serializes the `Tag` like [`Tag::serialize`], but only if it passes
+    /// [`Tag::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializeError::Invalid`] without writing anything if the tag
+    /// is invalid, and [`SerializeError::Io`] when encountering an io error on
+    /// `output`.
+    pub fn serialize_strict(
+        &self,
+        output: impl io::Write,
+    ) -> Result<(), SerializeError<TagViolation>> {
+        TagRef::from(self).serialize_strict(output)
+    }
+}
+
+impl TagRef<'_> {
+    /// Checks the tag like [`Tag::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` with at least one violation if the tag is invalid.
+    pub fn validate(self) -> Result<(), Vec<TagViolation>> {
+        let mut violations = Vec::new();
+        self.validate_into(&mut violations);
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+
+    /// Serializes the tag like [`TagRef::serialize`], but only if it passes
+    /// [`TagRef::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializeError::Invalid`] without writing anything if the tag
+    /// is invalid, and [`SerializeError::Io`] when encountering an io error on
+    /// `output`.
+    pub fn serialize_strict(
+        self,
+        output: impl io::Write,
+    ) -> Result<(), SerializeError<TagViolation>> {
+        self.validate().map_err(SerializeError::Invalid)?;
+        self.serialize(output)?;
+
+        Ok(())
+    }
+
+    pub(crate) fn validate_into(self, violations: &mut Vec<TagViolation>) {
+        match self {
+            Self::XDefine(definition) => definition.validate_into(violations),
+            Self::Inf { title, .. } if title.contains(['\r', '\n']) => {
+                violations.push(TagViolation::InvalidTitle(title.into()));
+            }
+            Self::XKey(Some(method)) | Self::XSessionKey(method) => {
+                method.validate_into(violations);
+            }
+            Self::XMap { uri, .. } | Self::XPart { uri, .. } => {
+                validate_quoted_string("URI", uri, violations);
+            }
+            Self::XMedia {
+                media_type,
+                group_id,
+                language,
+                assoc_language,
+                name,
+                stable_rendition_id,
+                characteristics,
+                ..
+            } => {
+                media_type.validate_into(violations);
+                validate_quoted_string("GROUP-ID", group_id, violations);
+                validate_optional_quoted_string("LANGUAGE", language, violations);
+                validate_optional_quoted_string("ASSOC-LANGUAGE", assoc_language, violations);
+                validate_quoted_string("NAME", name, violations);
+                validate_optional_quoted_string(
+                    "STABLE-RENDITION-ID",
+                    stable_rendition_id,
+                    violations,
+                );
+                for characteristic in characteristics {
+                    validate_list_item("CHARACTERISTICS", characteristic, &[','], violations);
+                }
+            }
+            Self::XStreamInf {
+                stream_inf,
+                audio_group_id,
+                video_group_id,
+                subtitles_group_id,
+                closed_captions_group_id,
+                uri,
+                ..
+            } => {
+                stream_inf.validate_into(violations);
+                validate_optional_quoted_string("AUDIO", audio_group_id, violations);
+                validate_optional_quoted_string("VIDEO", video_group_id, violations);
+                validate_optional_quoted_string("SUBTITLES", subtitles_group_id, violations);
+                validate_optional_quoted_string(
+                    "CLOSED-CAPTIONS",
+                    closed_captions_group_id,
+                    violations,
+                );
+                validate_uri_line(uri, violations);
+            }
+            Self::XIFrameStreamInf {
+                stream_inf,
+                video_group_id,
+                uri,
+            } => {
+                stream_inf.validate_into(violations);
+                validate_optional_quoted_string("VIDEO", video_group_id, violations);
+                validate_quoted_string("URI", uri, violations);
+            }
+            Self::XSessionData(data) => data.validate_into(violations),
+            Self::XContentSteering(steering) => steering.validate_into(violations),
+            Self::XDateRange(date_range) => date_range.validate_into(violations),
+            Self::XSkip {
+                recently_removed_dataranges,
+                ..
+            } => {
+                for id in recently_removed_dataranges {
+                    validate_list_item("RECENTLY-REMOVED-DATERANGES", id, &['\t'], violations);
+                }
+            }
+            Self::XPreloadHint(hint) => hint.validate_into(violations),
+            Self::XRenditionReport(report) => report.validate_into(violations),
+            _ => (),
+        }
+    }
+}
+
+/// Checks a URI which is written on its own line, like the URI of a Media
+/// Segment.
+pub fn validate_uri_line(uri: &str, violations: &mut Vec<TagViolation>) {
+    if uri.is_empty() || uri.starts_with('#') || uri.contains(['\r', '\n']) {
+        violations.push(TagViolation::InvalidUriLine(uri.into()));
+    }
+}
+
+fn validate_quoted_string(
+    attribute: &'static str,
+    value: &str,
+    violations: &mut Vec<TagViolation>,
+) {
+    if value.contains(['"', '\r', '\n']) {
+        violations.push(TagViolation::InvalidQuotedString {
+            attribute,
+            value: value.into(),
+        });
+    }
+}
+
+fn validate_optional_quoted_string(
+    attribute: &'static str,
+    value: Option<&str>,
+    violations: &mut Vec<TagViolation>,
+) {
+    if let Some(value) = value {
+        validate_quoted_string(attribute, value, violations);
+    }
+}
+
+/// Checks an item of a list which is written inside a single quoted-string,
+/// where the items are joined by one of `separators`.
+fn validate_list_item(
+    attribute: &'static str,
+    item: &str,
+    separators: &[char],
+    violations: &mut Vec<TagViolation>,
+) {
+    if item.contains(['"', '\r', '\n']) {
+        violations.push(TagViolation::InvalidQuotedString {
+            attribute,
+            value: item.into(),
+        });
+    } else if item.contains(separators) {
+        violations.push(TagViolation::InvalidListItem {
+            attribute,
+            value: item.into(),
+        });
+    }
+}
+
+impl DefinitionType {
+    fn validate_into(&self, violations: &mut Vec<TagViolation>) {
+        let (Self::Inline { name, .. } | Self::Import { name } | Self::QueryParameter { name }) =
+            self;
+        if !crate::variables::is_valid_name(name) {
+            violations.push(TagViolation::InvalidVariableName(name.clone()));
+        }
+
+        if let Self::Inline { value, .. } = self {
+            validate_quoted_string("VALUE", value, violations);
+        }
+    }
+}
+
+impl MediaTypeRef<'_> {
+    fn validate_into(self, violations: &mut Vec<TagViolation>) {
+        match self {
+            Self::Audio { uri, channels, .. } => {
+                validate_optional_quoted_string("URI", uri, violations);
+                if let Some(
+                    AudioChannelInformation::WithAudioCodingIdentifiers {
+                        audio_coding_identifiers,
+                        ..
+                    }
+                    | AudioChannelInformation::WithSpecialUsageIdentifiers {
+                        audio_coding_identifiers,
+                        ..
+                    },
+                ) = channels
+                {
+                    for identifier in audio_coding_identifiers {
+                        validate_list_item("CHANNELS", identifier, &[',', '/'], violations);
+                    }
+                }
+            }
+            Self::Video { uri } => validate_optional_quoted_string("URI", uri, violations),
+            Self::Subtitles { uri, .. } => validate_quoted_string("URI", uri, violations),
+            Self::ClosedCaptions { .. } => (),
+        }
+    }
+}
+
+impl EncryptionMethod {
+    fn validate_into(&self, violations: &mut Vec<TagViolation>) {
+        let (Self::Aes128 { uri, .. }
+        | Self::SampleAes { uri, .. }
+        | Self::SampleAesCtr { uri, .. }) = self;
+        validate_quoted_string("URI", uri, violations);
+
+        if let Self::Aes128 {
+            key_format: KeyFormat::Other(key_format),
+            ..
+        } = self
+        {
+            validate_quoted_string("KEYFORMAT", key_format, violations);
+        }
+    }
+}
+
+impl StreamInf {
+    fn validate_into(&self, violations: &mut Vec<TagViolation>) {
+        for codec in &self.codecs {
+            validate_list_item("CODECS", codec, &[','], violations);
+        }
+
+        for codec in &self.supplemental_codecs {
+            validate_list_item(
+                "SUPPLEMENTAL-CODECS",
+                &codec.supplemental_codec,
+                &[',', '/'],
+                violations,
+            );
+            for brand in &codec.compatibility_brands {
+                validate_list_item("SUPPLEMENTAL-CODECS", brand, &[',', '/'], violations);
+            }
+        }
+
+        for configuration in &self.allowed_cpc {
+            validate_list_item(
+                "ALLOWED-CPC",
+                &configuration.key_format,
+                &[',', ':'],
+                violations,
+            );
+            for label in &configuration.cpc_labels {
+                validate_list_item("ALLOWED-CPC", label, &[',', '/'], violations);
+            }
+        }
+
+        if let VideoRange::Other(other) = &self.video_range {
+            if other.is_empty()
+                || other.contains(|c: char| c == '"' || c == ',' || c.is_whitespace())
+            {
+                violations.push(TagViolation::InvalidEnumeratedString {
+                    attribute: "VIDEO-RANGE",
+                    value: other.clone(),
+                });
+            }
+        }
+
+        validate_optional_quoted_string(
+            "STABLE-VARIANT-ID",
+            self.stable_variant_id.as_deref(),
+            violations,
+        );
+        validate_optional_quoted_string("PATHWAY-ID", self.pathway_id.as_deref(), violations);
+    }
+}
+
+impl SessionData {
+    fn validate_into(&self, violations: &mut Vec<TagViolation>) {
+        validate_quoted_string("DATA-ID", &self.data_id, violations);
+        match &self.value {
+            SessionDataValue::Value { value, language } => {
+                validate_quoted_string("VALUE", value, violations);
+                validate_optional_quoted_string("LANGUAGE", language.as_deref(), violations);
+            }
+            SessionDataValue::Uri { uri, .. } => validate_quoted_string("URI", uri, violations),
+        }
+    }
+}
+
+impl ContentSteering {
+    fn validate_into(&self, violations: &mut Vec<TagViolation>) {
+        validate_quoted_string("SERVER-URI", &self.server_uri, violations);
+        validate_optional_quoted_string("PATHWAY-ID", self.pathway_id.as_deref(), violations);
+    }
+}
+
+impl DateRange {
+    fn validate_into(&self, violations: &mut Vec<TagViolation>) {
+        if self.id.is_empty() {
+            violations.push(TagViolation::EmptyDateRangeId);
+        }
+        validate_quoted_string("ID", &self.id, violations);
+        validate_optional_quoted_string("CLASS", self.class.as_deref(), violations);
+
+        for (name, value) in &self.client_attributes {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-')
+            {
+                violations.push(TagViolation::InvalidAttributeName(name.clone()));
+            }
+
+            if let AttributeValue::String(string) = value {
+                if string.contains(['"', '\r', '\n']) {
+                    violations.push(TagViolation::InvalidClientAttribute {
+                        name: name.clone(),
+                        value: string.clone(),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl PreloadHint {
+    fn validate_into(&self, violations: &mut Vec<TagViolation>) {
+        validate_quoted_string("URI", &self.uri, violations);
+    }
+}
+
+impl RenditionReport {
+    fn validate_into(&self, violations: &mut Vec<TagViolation>) {
+        validate_quoted_string("URI", &self.uri, violations);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::tags::MediaType;
+
+    #[test]
+    fn validate_tags() {
+        assert_eq!(
+            Tag::XMedia {
+                media_type: MediaType::Video {
+                    uri: Some("video.m3u8".into())
+                },
+                group_id: "group".into(),
+                language: None,
+                assoc_language: None,
+                name: "\"English\"".into(),
+                stable_rendition_id: None,
+                playback_priority: crate::RenditionPlaybackPriority::Default,
+                characteristics: vec![],
+            }
+            .validate(),
+            Err(vec![TagViolation::InvalidQuotedString {
+                attribute: "NAME",
+                value: "\"English\"".into()
+            }])
+        );
+        assert_eq!(
+            Tag::Inf {
+                duration_seconds: crate::FloatOrInteger::Integer(6),
+                title: "two\nlines".into()
+            }
+            .validate(),
+            Err(vec![TagViolation::InvalidTitle("two\nlines".into())])
+        );
+        assert_eq!(
+            Tag::XContentSteering(ContentSteering {
+                server_uri: "/steering".into(),
+                pathway_id: None
+            })
+            .validate(),
+            Ok(())
+        );
+
+        let mut client_attributes = HashMap::new();
+        client_attributes.insert(
+            "COOL".into(),
+            AttributeValue::String("\",END-ON-NEXT=YES".into()),
+        );
+        client_attributes.insert("lowercase".into(), AttributeValue::Float(1.0));
+        let mut violations = Tag::XDateRange(DateRange {
+            id: "ad\"".into(),
+            class: None,
+            start_date: chrono::DateTime::parse_from_rfc3339("2010-02-19T14:54:23.031+08:00")
+                .unwrap(),
+            cue: None,
+            end_date: None,
+            duration_seconds: None,
+            planned_duration_seconds: None,
+            client_attributes,
+            scte35_cmd: vec![],
+            scte35_in: vec![],
+            scte35_out: vec![],
+            end_on_next: false,
+        })
+        .validate()
+        .unwrap_err();
+        violations.sort_by_key(ToString::to_string);
+        assert_eq!(
+            violations,
+            vec![
+                TagViolation::InvalidQuotedString {
+                    attribute: "ID",
+                    value: "ad\"".into()
+                },
+                TagViolation::InvalidClientAttribute {
+                    name: "COOL".into(),
+                    value: "\",END-ON-NEXT=YES".into()
+                },
+                TagViolation::InvalidAttributeName("lowercase".into()),
+            ]
+        );
+
+        assert_eq!(
+            Tag::XSkip {
+                number_of_skipped_segments: 3,
+                recently_removed_dataranges: vec!["one\ttwo".into()],
+            }
+            .validate(),
+            Err(vec![TagViolation::InvalidListItem {
+                attribute: "RECENTLY-REMOVED-DATERANGES",
+                value: "one\ttwo".into()
+            }])
+        );
+        assert_eq!(
+            Tag::XDefine(DefinitionType::Inline {
+                name: "a b".into(),
+                value: "\n".into()
+            })
+            .validate(),
+            Err(vec![
+                TagViolation::InvalidVariableName("a b".into()),
+                TagViolation::InvalidQuotedString {
+                    attribute: "VALUE",
+                    value: "\n".into()
+                }
+            ])
+        );
+
+        let mut output = Vec::new();
+        let error = Tag::XPart {
+            uri: "part\r\n.mp4".into(),
+            duration_seconds: 1.0,
+            is_independent: false,
+            byte_range: None,
+            is_gap: false,
+        }
+        .serialize_strict(&mut output)
+        .unwrap_err();
+        assert!(matches!(error, SerializeError::Invalid(violations) if violations.len() == 1));
+        assert!(output.is_empty());
+
+        Tag::XGap.serialize_strict(&mut output).unwrap();
+        assert_eq!(output, b"#EXT-X-GAP\n");
+    }
+}
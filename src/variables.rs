@@ -0,0 +1,503 @@
+//! Resolution and substitution of EXT-X-DEFINE variables.
+//!
+//! A playlist defines variables with EXT-X-DEFINE tags, and references them
+//! as `{$name}` in URI lines, quoted-string attribute values, and
+//! hexadecimal-sequence attribute values.
+
+// Copyright 2024 Logan Wemyss
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{borrow::Cow, collections::HashMap, error::Error, fmt, iter, ops::Range};
+
+use crate::DefinitionType;
+
+/// An error encountered while resolving or substituting variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariableError {
+    /// A variable was referenced, but never defined.
+    Undefined(String),
+
+    /// More than one EXT-X-DEFINE tag defines the same variable.
+    DuplicateDefinition(String),
+
+    /// A variable was imported, but is not defined in the Multivariant
+    /// Playlist.
+    ImportNotFound(String),
+
+    /// A variable was defined from a query parameter which is not in the URI
+    /// of the playlist.
+    QueryParameterNotFound(String),
+
+    /// A variable was referenced in a tag attribute, but its value contains
+    /// characters which are not allowed in that attribute, such as a `"` in a
+    /// quoted-string.
+    InvalidValue(String),
+}
+
+impl fmt::Display for VariableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Undefined(name) => write!(f, "variable {name:?} is not defined"),
+            Self::DuplicateDefinition(name) => {
+                write!(f, "variable {name:?} is defined more than once")
+            }
+            Self::ImportNotFound(name) => write!(
+                f,
+                "imported variable {name:?} is not defined in the multivariant playlist"
+            ),
+            Self::QueryParameterNotFound(name) => {
+                write!(f, "query parameter {name:?} is not in the playlist URI")
+            }
+            Self::InvalidValue(name) => write!(
+                f,
+                "the value of variable {name:?} is not allowed in the attribute referencing it"
+            ),
+        }
+    }
+}
+
+impl Error for VariableError {}
+
+/// The values of the variables defined by a playlist.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Variables {
+    values: HashMap<String, String>,
+}
+
+impl Variables {
+    /// Creates an empty set of variables.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resolves the value of each of a playlist's `definitions`.
+    ///
+    /// `imports` are the variables of the Multivariant Playlist, and are
+    /// used by [`DefinitionType::Import`]. Pass [`Variables::new`] when
+    /// resolving the variables of a Multivariant Playlist. `query` is the
+    /// query component of the playlist's URI, without the leading `?`, and is
+    /// used by [`DefinitionType::QueryParameter`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if a variable is defined more than once, or if an
+    /// imported variable or query parameter does not exist.
+    pub fn resolve(
+        definitions: &[DefinitionType],
+        imports: &Self,
+        query: Option<&str>,
+    ) -> Result<Self, VariableError> {
+        let mut variables = Self::new();
+        for definition in definitions {
+            variables.define(definition, imports, query)?;
+        }
+
+        Ok(variables)
+    }
+
+    /// Resolves the value of a single definition and adds it to the set.
+    /// See [`Variables::resolve`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if the variable is already defined, or if an imported
+    /// variable or query parameter does not exist.
+    pub fn define(
+        &mut self,
+        definition: &DefinitionType,
+        imports: &Self,
+        query: Option<&str>,
+    ) -> Result<(), VariableError> {
+        let (name, value) = match definition {
+            DefinitionType::Inline { name, value } => (name, value.clone()),
+            DefinitionType::Import { name } => (
+                name,
+                imports
+                    .get(name)
+                    .ok_or_else(|| VariableError::ImportNotFound(name.clone()))?
+                    .into(),
+            ),
+            DefinitionType::QueryParameter { name } => (
+                name,
+                query
+                    .and_then(|query| query_parameter(query, name))
+                    .ok_or_else(|| VariableError::QueryParameterNotFound(name.clone()))?,
+            ),
+        };
+
+        if self.values.contains_key(name) {
+            return Err(VariableError::DuplicateDefinition(name.clone()));
+        }
+        self.values.insert(name.clone(), value);
+
+        Ok(())
+    }
+
+    /// Returns the value of the variable named `name`, if it is defined.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Returns `true` if no variables are defined.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Replaces every `{$name}` reference in `input` with the value of the
+    /// variable. Braces which do not surround a valid variable name are left
+    /// as is.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err` if a referenced variable is not defined.
+    pub fn substitute<'a>(&self, input: &'a str) -> Result<Cow<'a, str>, VariableError> {
+        self.substitute_checked(input, |_| true)
+    }
+
+    /// Like [`Variables::substitute`], but fails if the value of a referenced
+    /// variable is not accepted by `is_allowed`.
+    fn substitute_checked<'a>(
+        &self,
+        input: &'a str,
+        is_allowed: impl Fn(&str) -> bool,
+    ) -> Result<Cow<'a, str>, VariableError> {
+        if !input.contains("{$") {
+            return Ok(Cow::Borrowed(input));
+        }
+
+        let mut output = String::with_capacity(input.len());
+        let mut rest = input;
+        while let Some(start) = rest.find("{$") {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let Some(end) = rest.find('}') else {
+                break;
+            };
+            let name = &rest[2..end];
+            if is_valid_name(name) {
+                let value = self
+                    .get(name)
+                    .ok_or_else(|| VariableError::Undefined(name.into()))?;
+                if !is_allowed(value) {
+                    return Err(VariableError::InvalidValue(name.into()));
+                }
+                output.push_str(value);
+            } else {
+                output.push_str(&rest[..=end]);
+            }
+            rest = &rest[end + 1..];
+        }
+        output.push_str(rest);
+
+        Ok(Cow::Owned(output))
+    }
+
+    /// Substitutes the variable references in the quoted-string and
+    /// hexadecimal-sequence attribute values of a tag line. Values which
+    /// would end the attribute they are substituted into, and so could add
+    /// attributes to the tag, are rejected.
+    pub(crate) fn substitute_tag<'a>(&self, line: &'a str) -> Result<Cow<'a, str>, VariableError> {
+        let Some((name, value)) = tag_attributes(line).filter(|(_, value)| value.contains("{$"))
+        else {
+            return Ok(Cow::Borrowed(line));
+        };
+
+        let mut output = String::with_capacity(line.len());
+        output.push_str(name);
+        output.push(':');
+
+        let mut copied = 0;
+        for (range, is_allowed) in substitutable_values(value) {
+            output.push_str(&value[copied..range.start]);
+            output.push_str(&self.substitute_checked(&value[range.clone()], is_allowed)?);
+            copied = range.end;
+        }
+        output.push_str(&value[copied..]);
+
+        Ok(Cow::Owned(output))
+    }
+}
+
+/// Returns `true` if `input` contains a reference to any variable.
+pub(crate) fn has_reference(input: &str) -> bool {
+    let mut rest = input;
+    while let Some(start) = rest.find("{$") {
+        rest = &rest[start..];
+        let Some(end) = rest.find('}') else {
+            return false;
+        };
+        if is_valid_name(&rest[2..end]) {
+            return true;
+        }
+        rest = &rest[end + 1..];
+    }
+
+    false
+}
+
+/// Returns `true` if a quoted-string or hexadecimal-sequence attribute value
+/// in the tag on `line` contains a reference to any variable.
+pub(crate) fn tag_has_reference(line: &str) -> bool {
+    tag_attributes(line).is_some_and(|(_, value)| {
+        substitutable_values(value).any(|(range, _)| has_reference(&value[range]))
+    })
+}
+
+/// Splits a tag line into its name and attributes, unless variables can not
+/// be referenced in the tag.
+fn tag_attributes(line: &str) -> Option<(&str, &str)> {
+    line.split_once(':').filter(|(name, _)| *name != "#EXTINF")
+}
+
+/// Finds the quoted-string and hexadecimal-sequence values in the attributes
+/// of a tag, along with a check for the values which can be substituted into
+/// each of them.
+fn substitutable_values(
+    attributes: &str,
+) -> impl Iterator<Item = (Range<usize>, fn(&str) -> bool)> + '_ {
+    // Indexing bytes keeps `i` on a char boundary whenever it is used to
+    // slice, since both '"' and '=' are ASCII.
+    let bytes = attributes.as_bytes();
+    let mut i = 0;
+    iter::from_fn(move || {
+        while i < bytes.len() {
+            let (end, is_allowed): (_, fn(&str) -> bool) = if bytes[i] == b'"' {
+                i += 1;
+                (
+                    attributes[i..]
+                        .find('"')
+                        .map_or(attributes.len(), |end| i + end),
+                    is_quoted_string_value,
+                )
+            } else if bytes[i..].starts_with(b"=0x") || bytes[i..].starts_with(b"=0X") {
+                i += 1;
+                (
+                    attributes[i..]
+                        .find(',')
+                        .map_or(attributes.len(), |end| i + end),
+                    is_hexadecimal_value,
+                )
+            } else {
+                i += 1;
+                continue;
+            };
+
+            let start = i;
+            i = end + 1;
+            return Some((start..end, is_allowed));
+        }
+
+        None
+    })
+}
+
+fn is_quoted_string_value(value: &str) -> bool {
+    !value.contains(['"', '\r', '\n'])
+}
+
+fn is_hexadecimal_value(value: &str) -> bool {
+    value.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Returns `true` if `name` only contains the characters allowed in a
+/// variable name.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Finds the percent-decoded value of the parameter called `name` in `query`.
+pub(crate) fn query_parameter(query: &str, name: &str) -> Option<String> {
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (percent_decode(key) == name).then(|| percent_decode(value))
+    })
+}
+
+fn percent_decode(input: &str) -> String {
+    let mut output = Vec::with_capacity(input.len());
+    let mut bytes = input.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = bytes.clone().take(2).collect::<Vec<_>>();
+            if let Some(decoded) = std::str::from_utf8(&hex)
+                .ok()
+                .filter(|hex| hex.len() == 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                output.push(decoded);
+                bytes.nth(1);
+                continue;
+            }
+        }
+        output.push(byte);
+    }
+
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_variables() {
+        let parent = Variables::resolve(
+            &[DefinitionType::QueryParameter {
+                name: "token".into(),
+            }],
+            &Variables::new(),
+            Some("a=1&token=abc%20123"),
+        )
+        .unwrap();
+        assert_eq!(parent.get("token"), Some("abc 123"));
+
+        let variables = Variables::resolve(
+            &[
+                DefinitionType::Inline {
+                    name: "host".into(),
+                    value: "https://example.com".into(),
+                },
+                DefinitionType::Import {
+                    name: "token".into(),
+                },
+            ],
+            &parent,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            variables.substitute("{$host}/1.ts?t={$token}&{x}").unwrap(),
+            "https://example.com/1.ts?t=abc 123&{x}"
+        );
+        assert_eq!(
+            variables.substitute("{$missing}"),
+            Err(VariableError::Undefined("missing".into()))
+        );
+
+        assert_eq!(
+            Variables::resolve(
+                &[DefinitionType::Import {
+                    name: "other".into()
+                }],
+                &parent,
+                None
+            ),
+            Err(VariableError::ImportNotFound("other".into()))
+        );
+        assert_eq!(
+            Variables::resolve(
+                &[
+                    DefinitionType::Inline {
+                        name: "token".into(),
+                        value: String::new()
+                    },
+                    DefinitionType::Import {
+                        name: "token".into()
+                    }
+                ],
+                &parent,
+                None
+            ),
+            Err(VariableError::DuplicateDefinition("token".into()))
+        );
+        assert_eq!(
+            Variables::resolve(
+                &[DefinitionType::QueryParameter {
+                    name: "token".into()
+                }],
+                &Variables::new(),
+                Some("tokens=1")
+            ),
+            Err(VariableError::QueryParameterNotFound("token".into()))
+        );
+    }
+
+    #[test]
+    fn substitute_tag() {
+        let variables = Variables::resolve(
+            &[
+                DefinitionType::Inline {
+                    name: "id".into(),
+                    value: "ad-1".into(),
+                },
+                DefinitionType::Inline {
+                    name: "cmd".into(),
+                    value: "FC30".into(),
+                },
+            ],
+            &Variables::new(),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            variables
+                .substitute_tag(
+                    "#EXT-X-DATERANGE:ID=\"{$id}\",X-COUNT={$id},SCTE35-CMD=0x{$cmd},X-A=\"{$cmd}\""
+                )
+                .unwrap(),
+            "#EXT-X-DATERANGE:ID=\"ad-1\",X-COUNT={$id},SCTE35-CMD=0xFC30,X-A=\"FC30\""
+        );
+        assert_eq!(
+            variables
+                .substitute_tag("#EXT-X-STREAM-INF:VIDEO-RANGE=é,PATHWAY-ID=\"{$id}\"")
+                .unwrap(),
+            "#EXT-X-STREAM-INF:VIDEO-RANGE=é,PATHWAY-ID=\"ad-1\""
+        );
+        assert_eq!(
+            variables.substitute_tag("#EXTINF:5,\"{$id}\"").unwrap(),
+            "#EXTINF:5,\"{$id}\""
+        );
+
+        let variables = Variables::resolve(
+            &[
+                DefinitionType::Inline {
+                    name: "quote".into(),
+                    value: "a\",URI=\"b".into(),
+                },
+                DefinitionType::Inline {
+                    name: "hex".into(),
+                    value: "00,X-A=1".into(),
+                },
+            ],
+            &Variables::new(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            variables.substitute_tag("#EXT-X-DATERANGE:ID=\"{$quote}\""),
+            Err(VariableError::InvalidValue("quote".into()))
+        );
+        assert_eq!(
+            variables.substitute_tag("#EXT-X-DATERANGE:ID=\"a\",SCTE35-CMD=0x{$hex}"),
+            Err(VariableError::InvalidValue("hex".into()))
+        );
+        assert_eq!(variables.substitute("{$quote}").unwrap(), "a\",URI=\"b");
+
+        assert!(has_reference("a{b}{$x}"));
+        assert!(!has_reference("{$x y}{$"));
+        assert!(tag_has_reference(
+            "#EXT-X-DATERANGE:X-A=1,SCTE35-CMD=0x{$x}"
+        ));
+        assert!(!tag_has_reference("#EXT-X-DATERANGE:X-A={$x}"));
+        assert!(!tag_has_reference("#EXTINF:5,\"{$x}\""));
+    }
+}